@@ -277,6 +277,7 @@ async function runClient(pg: any): Promise<void> {
   const maxFeeBps = 2000;
   const dustThreshold = 10;
   const creatorClaimLockSecs = 60 * 60 * 24 * 7;
+  const minimumLiquidity = 1_000;
 
   try {
     console.log("Calling init_global...");
@@ -289,7 +290,8 @@ async function runClient(pg: any): Promise<void> {
           governance,
           maxFeeBps,
           dustThreshold,
-          creatorClaimLockSecs
+          creatorClaimLockSecs,
+          minimumLiquidity
         )
         .accounts({
           global: globalPda,
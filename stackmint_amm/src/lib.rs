@@ -1,5 +1,12 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, TokenAccount, Token, Transfer, MintTo, Burn};
+use anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
+use anchor_lang::solana_program::{compute_units::sol_remaining_compute_units, instruction::Instruction, program::invoke_signed};
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked, MintTo, Burn};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    onchain::invoke_transfer_checked,
+};
 
 declare_id!("7zcYfbAQNpGXpkfn5tXh7zMhJzm5UkQJeLbv2871cjVt");
 
@@ -10,12 +17,91 @@ const INTERNAL_PRECISION_DECIMALS: u8 = 9; // normalize to 9 decimals internally
 const FALLBACK_DUST_THRESHOLD: u64 = 10; // in token smallest units (adjust per token if desired)
 const FALLBACK_CREATOR_CLAIM_LOCK_SECS: i64 = 60 * 60 * 24 * 7; // 7 days timelock default
 const FALLBACK_MAX_FEE_BPS: u16 = 2000; // 20% max fee cap (safety)
+const FALLBACK_MINIMUM_LIQUIDITY: u64 = 1_000; // LP permanently locked on a pool's first deposit
+const MAX_PROTOCOL_FEE_BPS: u16 = 5_000; // protocol share of the swap fee; with creator shares capped at 50% they never exceed it
+const MAX_DUST_THRESHOLD: u64 = 1_000_000; // native units; above this the sweep would start taking real reserves
+const MAX_CREATOR_CLAIM_LOCK_SECS: i64 = 60 * 60 * 24 * 90;
+// oracle guardrail defaults applied at pool creation (tunable via set_pool_params)
+const DEFAULT_ORACLE_MAX_STALENESS_SECS: i64 = 60; // reject prices published more than 60s ago
+const DEFAULT_ORACLE_MAX_CONF_BPS: u16 = 200; // reject prices whose confidence interval exceeds 2% of price
+
+// Pyth v2 price account layout (see pyth-sdk-solana `SolanaPriceAccount`)
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_VERSION_2: u32 = 2;
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_EXPO_OFFSET: usize = 20;
+const PYTH_TIMESTAMP_OFFSET: usize = 96;
+const PYTH_AGG_PRICE_OFFSET: usize = 208;
+const PYTH_AGG_CONF_OFFSET: usize = 216;
+const PYTH_AGG_STATUS_OFFSET: usize = 224;
+const PYTH_PRICE_ACCOUNT_MIN_LEN: usize = 240;
+const OBSERVATION_CAPACITY: usize = 64; // TWAP ring buffer length (at most one observation per second)
+const ROUTE_ACCOUNTS_PER_HOP: usize = 11; // remaining_accounts consumed per `route_swap` hop
+const MAX_ROUTE_HOPS: usize = 4;
+const MAX_TRANSFER_HOOK_PROGRAMS: usize = 8; // capacity of `Global.transfer_hook_programs`
+const MAX_GOVERNANCE_SIGNERS: usize = 10; // capacity of `GovernanceConfig.signers`
+const MAX_REBALANCE_HOOK_PROGRAMS: usize = 8; // capacity of `Global.rebalance_hook_programs`
+const REBALANCE_HOOK_TIMELOCK_SECS: i64 = 60 * 60 * 24 * 2; // delay before a queued rebalance-hook change applies
+const MAX_REBALANCE_HOOK_COMPUTE_UNITS: u64 = 60_000; // most one rebalance-hook CPI may consume
+const REBALANCE_HOOK_COMPUTE_HEADROOM: u64 = 10_000; // kept back for the crank to finish after the hook
+// sha256("global:on_rebalance")[..8], so hook programs written in Anchor expose the callback as `on_rebalance`
+const REBALANCE_HOOK_IX_DISCRIMINATOR: [u8; 8] = [147, 245, 218, 61, 205, 54, 120, 206];
+const FALLBACK_PARAMS_TIMELOCK_SECS: i64 = 60 * 60 * 24 * 2; // delay before queued pool params apply
+const MIN_PARAMS_TIMELOCK_SECS: i64 = 60 * 60 * 24; // bounds of `Global.params_timelock_secs`
+const MAX_PARAMS_TIMELOCK_SECS: i64 = 60 * 60 * 24 * 30;
+// independently pausable operations (`Global.pause_flags` / `Pool.pause_flags`). LP exits through
+// `emergency_withdraw` and concentrated-liquidity decrease/collect are never pausable.
+const PAUSE_SWAPS: u8 = 1 << 0; // swaps, route hops, flash loans and both zaps
+const PAUSE_DEPOSITS: u8 = 1 << 1;
+const PAUSE_WITHDRAWALS: u8 = 1 << 2; // remove_liquidity (both variants)
+const PAUSE_MINT: u8 = 1 << 3; // mint_stack_via_pool and launch buys
+const PAUSE_REDEEM: u8 = 1 << 4; // redeem_stack_via_pool and launch sells
+const PAUSE_CREATOR_CLAIMS: u8 = 1 << 5;
+const PAUSE_ALL: u8 = PAUSE_SWAPS | PAUSE_DEPOSITS | PAUSE_WITHDRAWALS | PAUSE_MINT | PAUSE_REDEEM | PAUSE_CREATOR_CLAIMS;
+const PAUSE_CL_ALL: u8 = PAUSE_SWAPS | PAUSE_DEPOSITS | PAUSE_CREATOR_CLAIMS; // the operations a `ClPool` has
+const MIN_AMP: u64 = 1; // StableSwap amplification bounds (A, not A * n^n)
+const MAX_AMP: u64 = 10_000;
+const MAX_AMP_CHANGE: u64 = 10; // a single ramp may move amp by at most this factor
+const MIN_AMP_RAMP_SECS: i64 = 60 * 60 * 24; // ramps run for at least a day
+const STABLE_MAX_ITERATIONS: usize = 255; // Newton iterations for D and y before giving up
+const ZAP_SEARCH_ITERATIONS: usize = 32; // bisection steps for a zap split without a closed form
+const MIN_WEIGHT_BPS: u16 = 500; // weighted pools: each side keeps between 5% and 95% of the value
+const MAX_WEIGHT_BPS: u16 = 9_500;
+const WAD: u128 = 1_000_000_000_000_000_000; // fixed-point scale of the weighted-curve pow/ln/exp math
+const LN2_WAD: i128 = 693_147_180_559_945_309;
+const MAX_POW_RELATIVE_ERROR: u128 = 10_000; // 1e-14: pow results are padded by this much toward the pool
+const CL_MIN_TICK: i32 = -443_636; // concentrated liquidity: sqrt(1.0001^tick) stays within Q64.64
+const CL_MAX_TICK: i32 = 443_636;
+const CL_MAX_TICK_SPACING: u16 = 1_000;
+const CL_Q64: u128 = 1 << 64;
+const TICK_ARRAY_SIZE: usize = 32; // ticks per `TickArray` account
+const MAX_TICK_ARRAYS_PER_SWAP: usize = 3;
+// floor(2^64 / sqrt(1.0001)^(2^i)) in Q64.64, i = 0..18 (see `sqrt_price_at_tick`)
+const CL_SQRT_PRICE_FACTORS: [u128; 19] = [
+    0xfffcb933bd6fad37, 0xfff97272373d4132, 0xfff2e50f5f656932, 0xffe5caca7e10e4e6,
+    0xffcb9843d60f6159, 0xff973b41fa98c081, 0xff2ea16466c96a38, 0xfe5dee046a99a2a8,
+    0xfcbe86c7900a88ae, 0xf987a7253ac41317, 0xf3392b0822b70005, 0xe7159475a2c29b74,
+    0xd097f3bdfd2022b8, 0xa9f746462d870fdf, 0x70d869a156d2a1b8, 0x31be135f97d08fd9,
+    0x9aa508b5b7a84e1, 0x5d6af8dedb8119, 0x2216e584f5fa,
+];
+
+// Pyth legacy (v2) oracle program ids; Pyth v2 price accounts must be owned by one of these
+mod pyth_mainnet {
+    use super::*;
+    declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+mod pyth_devnet {
+    use super::*;
+    declare_id!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+}
 
 #[program]
 pub mod stackmint_amm {
     use super::*;
 
     /// Initialize global state — includes role pubkeys & versioning & configurable limits
+    #[allow(clippy::too_many_arguments)]
     pub fn init_global(
         ctx: Context<InitGlobal>,
         protocol_fee_bps: u16,
@@ -25,10 +111,12 @@ pub mod stackmint_amm {
         max_fee_bps: u16,
         dust_threshold: u64,
         creator_claim_lock_secs: i64,
+        minimum_liquidity: u64,
     ) -> Result<()> {
         // Basic bounds
         require!(protocol_fee_bps <= BPS_DENOM as u16, AmmError::InvalidFee);
-        require!(max_fee_bps <= BPS_DENOM as u16, AmmError::InvalidFee);
+        require!(max_fee_bps <= FALLBACK_MAX_FEE_BPS, AmmError::InvalidFee);
+        require!(dust_threshold <= MAX_DUST_THRESHOLD, AmmError::InvalidGlobalParams);
 
         let g = &mut ctx.accounts.global;
         g.version = 1;
@@ -44,11 +132,219 @@ pub mod stackmint_amm {
         g.max_fee_bps = max_fee_bps;
         g.dust_threshold = dust_threshold;
         g.creator_claim_lock_secs = creator_claim_lock_secs;
+        g.minimum_liquidity = minimum_liquidity;
+        g.transfer_hook_programs = Vec::new();
+        g.rebalance_hook_programs = Vec::new();
+        g.params_timelock_secs = 0;
+        g.pending_admin = None;
+        g.pending_pauser = None;
+        g.pending_fee_manager = None;
+        g.pending_governance = None;
+        g.pause_flags = 0;
 
         emit!(GlobalInitialized { admin: g.admin });
         Ok(())
     }
 
+    /// Replace the allowlist of Token-2022 transfer-hook programs a stack mint may use (admin or governance).
+    /// Checked when a stack is registered and when a pool is created; pools already live are unaffected.
+    pub fn set_transfer_hook_programs(ctx: Context<UpdateGlobal>, programs: Vec<Pubkey>) -> Result<()> {
+        let g = &mut ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
+        require!(programs.len() <= MAX_TRANSFER_HOOK_PROGRAMS, AmmError::TooManyTransferHookPrograms);
+
+        g.transfer_hook_programs = programs.clone();
+        emit!(TransferHookProgramsUpdated { by: caller, programs });
+        Ok(())
+    }
+
+    /// Replace the allowlist of rebalance-hook programs stacks may install (admin or governance).
+    /// Takes effect immediately: trades skip a stack's hook as soon as its program is off the list.
+    pub fn set_rebalance_hook_programs(ctx: Context<UpdateGlobal>, programs: Vec<Pubkey>) -> Result<()> {
+        let g = &mut ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
+        require!(programs.len() <= MAX_REBALANCE_HOOK_PROGRAMS, AmmError::TooManyRebalanceHookPrograms);
+
+        g.rebalance_hook_programs = programs.clone();
+        emit!(RebalanceHookProgramsUpdated { by: caller, programs });
+        Ok(())
+    }
+
+    /// Set the delay between `queue_pool_params` and `execute_pool_params` (admin or governance; 0 = fallback).
+    /// Bounded below so the notice LPs get can't be waived by shortening it right before queueing.
+    pub fn set_params_timelock(ctx: Context<UpdateGlobal>, secs: i64) -> Result<()> {
+        let g = &mut ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
+        require!(secs == 0 || (MIN_PARAMS_TIMELOCK_SECS..=MAX_PARAMS_TIMELOCK_SECS).contains(&secs), AmmError::InvalidTimelock);
+
+        g.params_timelock_secs = secs;
+        emit!(ParamsTimelockUpdated { by: caller, secs });
+        Ok(())
+    }
+
+    /// Propose a new holder for one of the `Global` roles (admin or governance); `None` withdraws the proposal.
+    /// The role only moves once the proposed key signs `accept_role`, so a typo can't lock a role away.
+    pub fn propose_role(ctx: Context<UpdateGlobal>, role: GlobalRole, new_holder: Option<Pubkey>) -> Result<()> {
+        let g = &mut ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
+
+        let current = *g.role_holder(role);
+        *g.pending_role_holder(role) = new_holder;
+        emit!(RoleProposed { role, current, proposed: new_holder, by: caller });
+        Ok(())
+    }
+
+    /// Take over a role proposed to the signer
+    pub fn accept_role(ctx: Context<AcceptRole>, role: GlobalRole) -> Result<()> {
+        let g = &mut ctx.accounts.global;
+        let new_holder = ctx.accounts.new_holder.key();
+        let pending = g.pending_role_holder(role).take().ok_or(AmmError::NoPendingRole)?;
+        require_keys_eq!(pending, new_holder, AmmError::Unauthorized);
+
+        let previous = std::mem::replace(g.role_holder(role), new_holder);
+        emit!(RoleTransferred { role, previous, new_holder });
+        Ok(())
+    }
+
+    /// Update protocol-wide fee and safety params (admin or governance); `None` leaves a value unchanged and
+    /// `0` selects the fallback where one exists. Passing `treasury` sets the sweep destination pools created later record.
+    pub fn set_global_params(
+        ctx: Context<SetGlobalParams>,
+        protocol_fee_bps: Option<u16>,
+        max_fee_bps: Option<u16>,
+        dust_threshold: Option<u64>,
+        creator_claim_lock_secs: Option<i64>,
+    ) -> Result<()> {
+        let g = &mut ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
+
+        if let Some(bps) = protocol_fee_bps {
+            require!(bps <= MAX_PROTOCOL_FEE_BPS, AmmError::InvalidFee);
+            g.protocol_fee_bps = bps;
+        }
+        if let Some(bps) = max_fee_bps {
+            // pools already above a lowered cap keep their fee until it is next changed
+            require!(bps <= FALLBACK_MAX_FEE_BPS, AmmError::InvalidFee);
+            g.max_fee_bps = bps;
+        }
+        if let Some(threshold) = dust_threshold {
+            require!(threshold <= MAX_DUST_THRESHOLD, AmmError::InvalidGlobalParams);
+            g.dust_threshold = threshold;
+        }
+        if let Some(secs) = creator_claim_lock_secs {
+            require!((0..=MAX_CREATOR_CLAIM_LOCK_SECS).contains(&secs), AmmError::InvalidGlobalParams);
+            g.creator_claim_lock_secs = secs;
+        }
+        if let Some(treasury) = &ctx.accounts.treasury {
+            g.treasury = treasury.key();
+        }
+
+        emit!(GlobalParamsUpdated {
+            by: caller,
+            protocol_fee_bps: g.protocol_fee_bps,
+            max_fee_bps: g.max_fee_bps,
+            dust_threshold: g.dust_threshold,
+            creator_claim_lock_secs: g.creator_claim_lock_secs,
+            treasury: g.treasury,
+        });
+        Ok(())
+    }
+
+    /// Queue a change of the stack's rebalance hook (creator only); `None` clears it. The hook program must be
+    /// on `Global.rebalance_hook_programs`. Applies via `apply_rebalance_hook` after `REBALANCE_HOOK_TIMELOCK_SECS`;
+    /// queueing again replaces the pending change and restarts the delay.
+    pub fn queue_rebalance_hook(ctx: Context<SetRebalanceHook>, hook: Option<Pubkey>, threshold_bps: u16) -> Result<()> {
+        let stack_info = &mut ctx.accounts.stack_info;
+        require_keys_eq!(ctx.accounts.creator.key(), stack_info.creator, AmmError::Unauthorized);
+        require!(threshold_bps as u128 <= BPS_DENOM, AmmError::InvalidFee);
+        if let Some(program) = hook {
+            require!(ctx.accounts.global.rebalance_hook_programs.contains(&program), AmmError::RebalanceHookNotAllowed);
+        }
+
+        let eta = Clock::get()?.unix_timestamp.checked_add(REBALANCE_HOOK_TIMELOCK_SECS).ok_or(AmmError::MathOverflow)?;
+        stack_info.pending_rebalance_hook = Some(PendingRebalanceHook { hook, threshold_bps, eta });
+        emit!(RebalanceHookQueued { stack_mint: stack_info.stack_mint, hook, threshold_bps, eta });
+        Ok(())
+    }
+
+    /// Apply the queued rebalance-hook change once its timelock has passed (creator only)
+    pub fn apply_rebalance_hook(ctx: Context<SetRebalanceHook>) -> Result<()> {
+        let stack_info = &mut ctx.accounts.stack_info;
+        require_keys_eq!(ctx.accounts.creator.key(), stack_info.creator, AmmError::Unauthorized);
+        let pending = stack_info.pending_rebalance_hook.ok_or(AmmError::NoPendingRebalanceHook)?;
+        require!(Clock::get()?.unix_timestamp >= pending.eta, AmmError::RebalanceHookTimelocked);
+        // the allowlist may have changed while the change was queued
+        if let Some(program) = pending.hook {
+            require!(ctx.accounts.global.rebalance_hook_programs.contains(&program), AmmError::RebalanceHookNotAllowed);
+        }
+
+        stack_info.rebalance_hook = pending.hook;
+        stack_info.rebalance_threshold_bps = pending.threshold_bps;
+        stack_info.pending_rebalance_hook = None;
+        emit!(RebalanceHookUpdated { stack_mint: stack_info.stack_mint, hook: pending.hook, threshold_bps: pending.threshold_bps });
+        Ok(())
+    }
+
+    /// Run the stack's rebalance hook on the request recorded by the last qualifying trade (anyone may crank).
+    /// The hook gets its own instruction so a failing or expensive hook never reverts a trade; the request stays
+    /// pending until a call succeeds or a newer trade replaces it. The CPI must fit in
+    /// `MAX_REBALANCE_HOOK_COMPUTE_UNITS` with `REBALANCE_HOOK_COMPUTE_HEADROOM` to spare.
+    pub fn execute_rebalance_hook(ctx: Context<ExecuteRebalanceHook>) -> Result<()> {
+        let stack_info_key = ctx.accounts.stack_info.key();
+        let stack_info = &mut ctx.accounts.stack_info;
+        let args = stack_info.pending_rebalance.take().ok_or(AmmError::NoPendingRebalance)?;
+        // the hook may have been replaced or delisted since the trade
+        let hook = stack_info.rebalance_hook.ok_or(AmmError::RebalanceHookNotAllowed)?;
+        require!(ctx.accounts.global.rebalance_hook_programs.contains(&hook), AmmError::RebalanceHookNotAllowed);
+        require_keys_eq!(ctx.accounts.hook_program.key(), hook, AmmError::InvalidRebalanceHookAccounts);
+        require!(ctx.accounts.hook_program.executable, AmmError::InvalidRebalanceHookAccounts);
+        require_keys_eq!(ctx.accounts.pool.key(), args.pool, AmmError::InvalidRebalanceHookAccounts);
+        let (state_key, _) = Pubkey::find_program_address(&[b"rebalance_hook", stack_info_key.as_ref()], &hook);
+        require_keys_eq!(ctx.accounts.hook_state.key(), state_key, AmmError::InvalidRebalanceHookAccounts);
+
+        let signer_key = ctx.accounts.hook_signer.key();
+        let (_, signer_bump) = Pubkey::find_program_address(&[b"rebalance_hook_signer", stack_info_key.as_ref()], &crate::ID);
+        let mut data = REBALANCE_HOOK_IX_DISCRIMINATOR.to_vec();
+        args.serialize(&mut data)?;
+        let ix = Instruction {
+            program_id: hook,
+            accounts: vec![
+                AccountMeta::new_readonly(signer_key, true),
+                AccountMeta::new_readonly(args.pool, false),
+                AccountMeta::new_readonly(stack_info_key, false),
+                AccountMeta::new(state_key, false),
+            ],
+            data,
+        };
+
+        let budget_before = sol_remaining_compute_units();
+        require!(
+            budget_before >= MAX_REBALANCE_HOOK_COMPUTE_UNITS + REBALANCE_HOOK_COMPUTE_HEADROOM,
+            AmmError::RebalanceHookComputeBudget
+        );
+        invoke_signed(
+            &ix,
+            &[
+                ctx.accounts.hook_signer.to_account_info(),
+                ctx.accounts.pool.to_account_info(),
+                stack_info.to_account_info(),
+                ctx.accounts.hook_state.to_account_info(),
+                ctx.accounts.hook_program.to_account_info(),
+            ],
+            &[&[b"rebalance_hook_signer", stack_info_key.as_ref(), &[signer_bump]]],
+        )?;
+        let compute_units = budget_before.saturating_sub(sol_remaining_compute_units());
+        require!(compute_units <= MAX_REBALANCE_HOOK_COMPUTE_UNITS, AmmError::RebalanceHookComputeExceeded);
+
+        emit!(RebalanceHookInvoked { stack_mint: args.stack_mint, pool: args.pool, hook, trade: args.trade, compute_units });
+        Ok(())
+    }
+
     /// Register a stack: provide stack_mint where program is the mint authority PDA
     pub fn register_stack(
         ctx: Context<RegisterStack>,
@@ -56,9 +352,10 @@ pub mod stackmint_amm {
     ) -> Result<()> {
         let stack_info = &mut ctx.accounts.stack_info;
         require!(creator_fee_bps <= 5000, AmmError::InvalidFee); // arbitrary cap
+        validate_mint_extensions(&ctx.accounts.stack_mint, Some(&ctx.accounts.global.transfer_hook_programs))?;
 
         // Validate that provided stack_mint_auth is the expected PDA for this mint.
-        let (expected_pda, _bump_auth) = Pubkey::find_program_address(
+        let (expected_pda, bump_auth) = Pubkey::find_program_address(
             &[b"stack_mint_auth", ctx.accounts.stack_mint.key().as_ref()],
             &crate::ID,
         );
@@ -79,7 +376,16 @@ pub mod stackmint_amm {
         stack_info.stack_mint = ctx.accounts.stack_mint.key();
         stack_info.creator_fee_bps = creator_fee_bps;
         stack_info.rebalance_hook = None;
+        stack_info.rebalance_threshold_bps = 0;
+        stack_info.pending_rebalance_hook = None;
+        stack_info.pending_rebalance = None;
+        stack_info.launch = None;
+        stack_info.supply_limits = SupplyLimits::default();
+        stack_info.mint_window = RateWindow::default();
+        stack_info.redeem_window = RateWindow::default();
+        stack_info.backing = None;
         stack_info.bump = bump_stack_info;
+        stack_info.mint_auth_bump = bump_auth;
         emit!(StackRegistered {
             stack_mint: stack_info.stack_mint,
             creator: stack_info.creator,
@@ -88,17 +394,273 @@ pub mod stackmint_amm {
         Ok(())
     }
 
+    /// Set the stack's supply cap and mint/redeem limits for `mint_stack_via_pool` / `redeem_stack_via_pool`
+    /// (creator, admin or governance). Zero disables a limit; the sliding windows restart from empty.
+    pub fn set_supply_limits(ctx: Context<SetSupplyLimits>, limits: SupplyLimits) -> Result<()> {
+        let stack_info = &mut ctx.accounts.stack_info;
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.authority.key();
+        require!(caller == stack_info.creator || caller == g.admin || caller == g.governance, AmmError::Unauthorized);
+        // window limits need a window to slide over
+        require!(
+            limits.window_secs > 0 || (limits.mint_per_window == 0 && limits.redeem_per_window == 0),
+            AmmError::InvalidSupplyLimits
+        );
+
+        stack_info.supply_limits = limits;
+        stack_info.mint_window = RateWindow::default();
+        stack_info.redeem_window = RateWindow::default();
+        emit!(SupplyLimitsUpdated { stack_mint: stack_info.stack_mint, by: caller, limits });
+        Ok(())
+    }
+
+    /// Set up NAV issuance for a registered stack (creator only, once). `mint_stack_via_pool` /
+    /// `redeem_stack_via_pool` then mint and redeem against `backing_vault`, a quote token account owned by the
+    /// `["vault_authority", stack_info]` PDA. `initial_nav_wad` prices mints while nothing is backed (quote per
+    /// stack, native units, WAD); `fee_bps` of every mint and redeem stays in the reserve.
+    pub fn init_backing(ctx: Context<InitBacking>, initial_nav_wad: u128, fee_bps: u16) -> Result<()> {
+        let stack_info_key = ctx.accounts.stack_info.key();
+        let stack_info = &mut ctx.accounts.stack_info;
+        require!(ctx.accounts.creator.key() == stack_info.creator, AmmError::Unauthorized);
+        require!(stack_info.backing.is_none(), AmmError::BackingAlreadyInitialized);
+        require!(initial_nav_wad > 0, AmmError::InvalidBacking);
+        let g = &ctx.accounts.global;
+        let max_fee = if g.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { g.max_fee_bps };
+        require!(fee_bps <= max_fee && (fee_bps as u128) < BPS_DENOM, AmmError::InvalidFee);
+        validate_mint_extensions(&ctx.accounts.quote_mint, None)?;
+
+        let (expected_authority, vault_authority_bump) = Pubkey::find_program_address(
+            &[b"vault_authority", stack_info_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(ctx.accounts.backing_vault.owner, expected_authority, AmmError::InvalidVaultOwner);
+        if let Some(curve) = stack_info.launch {
+            // the launch reserve shares the authority PDA but must stay a separate account
+            require_keys_neq!(ctx.accounts.backing_vault.key(), curve.quote_vault, AmmError::InvalidBacking);
+        }
+
+        stack_info.backing = Some(Backing {
+            quote_mint: ctx.accounts.quote_mint.key(),
+            vault: ctx.accounts.backing_vault.key(),
+            vault_authority_bump,
+            initial_nav_wad,
+            fee_bps,
+            reserve: 0,
+            backed_supply: 0,
+        });
+        emit!(BackingInitialized {
+            stack_mint: stack_info.stack_mint,
+            quote_mint: ctx.accounts.quote_mint.key(),
+            vault: ctx.accounts.backing_vault.key(),
+            initial_nav_wad,
+            fee_bps,
+        });
+        Ok(())
+    }
+
+    /// Start a bonding-curve launch for a registered stack (creator only). Stack is minted to buyers along
+    /// the curve and their quote is held in `launch_vault` until the sold stack's market cap at the curve
+    /// price reaches `graduation_market_cap`; then `pool` is seeded and unpaused. `pool` must be an empty pool
+    /// for this stack; it stays paused for the whole launch so nobody can seed it at another price first.
+    pub fn start_launch(
+        ctx: Context<StartLaunch>,
+        kind: BondingCurveKind,
+        start_price_wad: u128,
+        curve_scale: u64,
+        graduation_market_cap: u64,
+    ) -> Result<()> {
+        let stack_info_key = ctx.accounts.stack_info.key();
+        let stack_info = &mut ctx.accounts.stack_info;
+        require!(ctx.accounts.creator.key() == stack_info.creator, AmmError::Unauthorized);
+        require!(stack_info.launch.is_none(), AmmError::LaunchAlreadyStarted);
+        require!(start_price_wad > 0 && curve_scale > 0 && graduation_market_cap > 0, AmmError::InvalidBondingCurve);
+
+        let pool = &mut ctx.accounts.pool;
+        require_keys_eq!(pool.stack_mint, stack_info.stack_mint, AmmError::InvalidBondingCurve);
+        require!(pool.total_lp_supply == 0, AmmError::InvalidBondingCurve);
+
+        // the launch reserve is held by the same vault-authority PDA scheme as pools, keyed by StackInfo
+        let (expected_authority, launch_authority_bump) = Pubkey::find_program_address(
+            &[b"vault_authority", stack_info_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(ctx.accounts.launch_vault.owner, expected_authority, AmmError::InvalidVaultOwner);
+        require_keys_eq!(ctx.accounts.launch_vault.mint, pool.quote_mint, AmmError::InvalidVaultMint);
+
+        pool.paused = true;
+        stack_info.launch = Some(BondingCurve {
+            kind,
+            pool: pool.key(),
+            quote_vault: ctx.accounts.launch_vault.key(),
+            vault_authority_bump: launch_authority_bump,
+            start_price_wad,
+            curve_scale,
+            graduation_market_cap,
+            sold: 0,
+            reserve_quote: 0,
+            graduated: false,
+        });
+        emit!(LaunchStarted {
+            stack_mint: stack_info.stack_mint,
+            pool: pool.key(),
+            kind,
+            start_price_wad,
+            curve_scale,
+            graduation_market_cap,
+        });
+        Ok(())
+    }
+
+    /// Buy stack from the launch curve with `quote_in` (fee-on-transfer safe); graduates the launch into
+    /// its pool when this buy lifts the market cap to the threshold
+    pub fn buy_launch<'info>(
+        ctx: Context<'_, '_, '_, 'info, BuyLaunch<'info>>,
+        quote_in: u64,
+        min_stack_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        require!(!ctx.accounts.global.is_paused(PAUSE_MINT), AmmError::ProtocolPaused);
+        // `pool.paused` is the launch's own hold, so only the flags set by `pause_pool` count here
+        require!(ctx.accounts.pool.pause_flags & PAUSE_MINT == 0, AmmError::PoolPaused);
+        let mut curve = ctx.accounts.stack_info.launch.ok_or(AmmError::LaunchNotActive)?;
+        require!(!curve.graduated, AmmError::LaunchNotActive);
+        require_keys_eq!(curve.quote_vault, ctx.accounts.launch_vault.key(), AmmError::InvalidVaultOwner);
+        require_keys_eq!(curve.pool, ctx.accounts.pool.key(), AmmError::InvalidBondingCurve);
+
+        let vault_before = ctx.accounts.launch_vault.amount;
+        transfer_from_user(
+            &ctx.accounts.token_program,
+            &ctx.accounts.user_quote_account,
+            &ctx.accounts.launch_vault,
+            &ctx.accounts.quote_mint,
+            &[],
+            &ctx.accounts.user,
+            quote_in,
+        )?;
+        ctx.accounts.launch_vault.reload()?;
+        let actual_in = ctx.accounts.launch_vault.amount.checked_sub(vault_before).ok_or(AmmError::MathOverflow)?;
+
+        // the whole input stays in the reserve; stack out is the most the curve sells for it (rounded down)
+        let stack_out: u64 = launch_amount_out(&curve, actual_in as u128)?.try_into().map_err(|_| AmmError::MathOverflow)?;
+        require!(stack_out > 0 && stack_out >= min_stack_out, AmmError::SlippageExceeded);
+        ctx.accounts.mint_stack(ctx.accounts.user_stack_account.to_account_info(), stack_out)?;
+        curve.sold = curve.sold.checked_add(stack_out).ok_or(AmmError::MathOverflow)?;
+        curve.reserve_quote = curve.reserve_quote.checked_add(actual_in).ok_or(AmmError::MathOverflow)?;
+
+        emit!(LaunchTraded {
+            stack_mint: ctx.accounts.stack_mint.key(),
+            trader: ctx.accounts.user.key(),
+            is_buy: true,
+            quote_amount: actual_in,
+            stack_amount: stack_out,
+            sold: curve.sold,
+        });
+
+        // market cap = curve price x stack sold on the curve, in quote native units; supply minted before
+        // the launch (e.g. to the creator) doesn't count towards graduation
+        let market_cap = mul_div_wide(launch_price_wad(&curve, curve.sold as u128)?, curve.sold as u128, WAD)?;
+        if market_cap >= curve.graduation_market_cap as u128 {
+            ctx.accounts.graduate(&curve)?;
+            curve.graduated = true;
+            curve.reserve_quote = 0;
+        }
+        ctx.accounts.stack_info.launch = Some(curve);
+        Ok(())
+    }
+
+    /// Sell stack back to the launch curve before graduation; the stack is burned and paid from the reserve
+    pub fn sell_launch<'info>(
+        ctx: Context<'_, '_, '_, 'info, SellLaunch<'info>>,
+        stack_in: u64,
+        min_quote_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        require!(!ctx.accounts.global.is_paused(PAUSE_REDEEM), AmmError::ProtocolPaused);
+        require!(ctx.accounts.pool.pause_flags & PAUSE_REDEEM == 0, AmmError::PoolPaused);
+        let stack_info_key = ctx.accounts.stack_info.key();
+        let mut curve = ctx.accounts.stack_info.launch.ok_or(AmmError::LaunchNotActive)?;
+        require!(!curve.graduated, AmmError::LaunchNotActive);
+        require_keys_eq!(curve.quote_vault, ctx.accounts.launch_vault.key(), AmmError::InvalidVaultOwner);
+        require_keys_eq!(curve.pool, ctx.accounts.pool.key(), AmmError::InvalidBondingCurve);
+        require!(stack_in > 0 && stack_in <= curve.sold, AmmError::InsufficientLiquidity);
+
+        let sold_after = curve.sold - stack_in;
+        let quote_out: u64 = launch_cost(&curve, sold_after as u128, curve.sold as u128, false)?
+            .try_into().map_err(|_| AmmError::MathOverflow)?;
+        require!(amount_after_transfer_fee(&ctx.accounts.quote_mint, quote_out)? >= min_quote_out, AmmError::SlippageExceeded);
+
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.stack_token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.stack_mint.to_account_info().clone(),
+                    from: ctx.accounts.user_stack_account.to_account_info().clone(),
+                    authority: ctx.accounts.user.to_account_info().clone(),
+                },
+            ),
+            stack_in,
+        )?;
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.launch_vault,
+            &ctx.accounts.user_quote_account,
+            &ctx.accounts.quote_mint,
+            &[],
+            &ctx.accounts.launch_authority,
+            stack_info_key,
+            curve.vault_authority_bump,
+            quote_out,
+        )?;
+        curve.sold = sold_after;
+        curve.reserve_quote = curve.reserve_quote.checked_sub(quote_out).ok_or(AmmError::MathOverflow)?;
+
+        emit!(LaunchTraded {
+            stack_mint: ctx.accounts.stack_mint.key(),
+            trader: ctx.accounts.user.key(),
+            is_buy: false,
+            quote_amount: quote_out,
+            stack_amount: stack_in,
+            sold: curve.sold,
+        });
+        ctx.accounts.stack_info.launch = Some(curve);
+        Ok(())
+    }
+
     /// Create pool. Added: explicit vault_authority PDA validation & fee cap checking against global.
     #[allow(clippy::too_many_arguments)]
     pub fn create_pool(
         ctx: Context<CreatePool>,
         fee_bps: u16,
         k: u128,
-        fee_on_transfer: bool,
         decimal_normalize_to: u8,
+        oracle_source: OracleSource,
+        curve_type: CurveType,
+        amp: u64,
+        stack_weight_bps: u16,
     ) -> Result<()> {
         // Basic parameter checks
         require!(decimal_normalize_to <= 18, AmmError::InvalidDecimals);
+        match curve_type {
+            CurveType::ConstantProduct | CurveType::Weighted => require!(amp == 0, AmmError::InvalidAmp),
+            CurveType::StableSwap => require!((MIN_AMP..=MAX_AMP).contains(&amp), AmmError::InvalidAmp),
+        }
+        match curve_type {
+            CurveType::Weighted => require!((MIN_WEIGHT_BPS..=MAX_WEIGHT_BPS).contains(&stack_weight_bps), AmmError::InvalidWeight),
+            _ => require!(stack_weight_bps == 0, AmmError::InvalidWeight),
+        }
+        // only the stack mint may carry a transfer hook, and only one on the global allowlist
+        validate_mint_extensions(&ctx.accounts.stack_mint, Some(&ctx.accounts.global.transfer_hook_programs))?;
+        validate_mint_extensions(&ctx.accounts.quote_mint, None)?;
+        validate_mint_extensions(&ctx.accounts.lp_mint, None)?;
+
+        // make sure the oracle account actually decodes as the declared source before binding it to the pool
+        if oracle_source != OracleSource::None {
+            load_oracle_price(oracle_source, &ctx.accounts.oracle.to_account_info())?;
+        }
 
         let g = &ctx.accounts.global;
         // enforce fee cap per global config
@@ -110,7 +672,7 @@ pub mod stackmint_amm {
         let pool = &mut ctx.accounts.pool;
 
         // derive & validate the vault_authority PDA server-side so the client cannot pass a random account
-        let (expected_vault_auth, _vault_auth_bump) = Pubkey::find_program_address(
+        let (expected_vault_auth, vault_auth_bump) = Pubkey::find_program_address(
             &[b"vault_authority", pool_key.as_ref()],
             &crate::ID,
         );
@@ -125,12 +687,6 @@ pub mod stackmint_amm {
         require_keys_eq!(ctx.accounts.stack_vault.mint, ctx.accounts.stack_mint.key(), AmmError::InvalidVaultMint);
         require_keys_eq!(ctx.accounts.quote_vault.mint, ctx.accounts.quote_mint.key(), AmmError::InvalidVaultMint);
 
-        // fee vaults must be ATAs owned by vault authority and quote-mint denominated
-        require_keys_eq!(ctx.accounts.protocol_fee_vault.owner, vault_authority_key, AmmError::InvalidVaultOwner);
-        require_keys_eq!(ctx.accounts.creator_fee_vault.owner, vault_authority_key, AmmError::InvalidVaultOwner);
-        require_keys_eq!(ctx.accounts.protocol_fee_vault.mint, ctx.accounts.quote_mint.key(), AmmError::InvalidVaultMint);
-        require_keys_eq!(ctx.accounts.creator_fee_vault.mint, ctx.accounts.quote_mint.key(), AmmError::InvalidVaultMint);
-
         // Initialize pool
         pool.version = 1;
         pool.stack_mint = ctx.accounts.stack_mint.key();
@@ -138,6 +694,8 @@ pub mod stackmint_amm {
         pool.fee_bps = fee_bps;
         pool.k = k;
         pool.lp_mint = ctx.accounts.lp_mint.key();
+        pool.stack_vault = ctx.accounts.stack_vault.key();
+        pool.quote_vault = ctx.accounts.quote_vault.key();
 
         // compute bump for pool PDA and store it
         let (_expected_pool_pda, bump_pool) = Pubkey::find_program_address(
@@ -145,20 +703,44 @@ pub mod stackmint_amm {
             &crate::ID,
         );
         pool.bump = bump_pool;
+        pool.vault_authority_bump = vault_auth_bump;
 
         pool.paused = false;
         pool.total_lp_supply = 0u128;
         pool.locked = false;
         pool.decimal_normalize_to = decimal_normalize_to;
-        pool.fee_on_transfer = fee_on_transfer;
-        pool.protocol_fee_vault = ctx.accounts.protocol_fee_vault.key();
-        pool.creator_fee_vault = ctx.accounts.creator_fee_vault.key();
         pool.treasury = ctx.accounts.global.treasury;
         pool.oracle = ctx.accounts.oracle.key();
-        pool.creator_claimable = 0u128;
+        pool.oracle_source = oracle_source;
+        pool.curve_type = curve_type;
+        pool.amp_initial = amp;
+        pool.amp_target = amp;
+        pool.amp_ramp_start_ts = 0;
+        pool.amp_ramp_end_ts = 0;
+        // only weighted pools take a weight; the other curves are balanced by construction
+        pool.pause_flags = 0;
+        pool.stack_weight_bps = if curve_type == CurveType::Weighted { stack_weight_bps } else { (BPS_DENOM / 2) as u16 };
+        pool.oracle_max_staleness_secs = DEFAULT_ORACLE_MAX_STALENESS_SECS;
+        pool.oracle_max_conf_bps = DEFAULT_ORACLE_MAX_CONF_BPS;
+        pool.protocol_fees_stack = 0u64;
+        pool.protocol_fees_quote = 0u64;
+        pool.creator_fees_stack = 0u64;
+        pool.creator_fees_quote = 0u64;
         pool.creator_last_claim_ts = 0i64;
-        pool.max_price_deviation_bps = 2000; // default 20% allowed deviation vs provided oracle price
+        pool.max_price_deviation_bps = 2000; // default 20% allowed deviation vs on-chain oracle price
         pool.last_governance_nonce = 0u128; // initialize governance replay protection
+        pool.price_cumulative_quote_per_stack = 0u128;
+        pool.price_cumulative_stack_per_quote = 0u128;
+        pool.last_price_update_ts = Clock::get()?.unix_timestamp;
+        pool.flash_loan_stack = 0u64;
+        pool.flash_loan_quote = 0u64;
+        pool.flash_reserve_stack = 0u64;
+        pool.flash_reserve_quote = 0u64;
+
+        let observations = &mut ctx.accounts.observations;
+        observations.pool = pool_key;
+        observations.index = 0;
+        observations.observations = Vec::with_capacity(OBSERVATION_CAPACITY);
         emit!(PoolCreated {
             pool: pool_key,
             stack_mint: pool.stack_mint,
@@ -169,20 +751,27 @@ pub mod stackmint_amm {
     }
 
     /// Provide liquidity: normalized to internal precision, mint canonical LP shares using sqrt(total)
-    /// Uses consolidated helpers and checks.
-    pub fn provide_liquidity(
-        ctx: Context<ProvideLiquidity>,
+    /// Uses consolidated helpers and checks. `amount_stack` / `amount_quote` are maximums: once the pool
+    /// has liquidity only the reserve-proportional pair is pulled, the excess side never leaves the user.
+    pub fn provide_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, ProvideLiquidity<'info>>,
         amount_stack: u64,
         amount_quote: u64,
+        min_lp_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
     ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+
         // Capture pool key & bump BEFORE taking a mutable borrow to avoid borrow conflicts
         let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+        let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
 
         // Reentrancy & pause checks
         let pool = &mut ctx.accounts.pool;
         let global = &ctx.accounts.global;
-        require!(!global.paused && !pool.paused, AmmError::ProtocolPaused);
+        require!(!global.is_paused(PAUSE_DEPOSITS), AmmError::ProtocolPaused);
+        require!(!pool.is_paused(PAUSE_DEPOSITS), AmmError::PoolPaused);
         require!(!pool.locked, AmmError::Reentrancy);
         pool.locked = true;
 
@@ -193,66 +782,82 @@ pub mod stackmint_amm {
         let quote_decimals = ctx.accounts.quote_mint.decimals;
         let target_decimals = pool.decimal_normalize_to;
 
-        // For fee-on-transfer tokens, measure actual vault delta after transfer
-        let reserve_stack_before = ctx.accounts.stack_vault.amount;
-        let reserve_quote_before = ctx.accounts.quote_vault.amount;
+        // For fee-on-transfer tokens, measure actual vault delta after transfer;
+        // trading reserves exclude protocol/creator fees accrued in the vaults
+        let stack_vault_before = ctx.accounts.stack_vault.amount;
+        let quote_vault_before = ctx.accounts.quote_vault.amount;
+        let reserve_stack_before = vault_reserve(stack_vault_before, pool.accrued_fees_stack()?)?;
+        let reserve_quote_before = vault_reserve(quote_vault_before, pool.accrued_fees_quote()?)?;
+        let reserve_stack_norm_before = normalize_amount_u128(reserve_stack_before, stack_decimals, target_decimals)?;
+        let reserve_quote_norm_before = normalize_amount_u128(reserve_quote_before, quote_decimals, target_decimals)?;
+
+        // fold the pre-deposit price into the TWAP accumulators before reserves change
+        update_price_accumulators(pool, &mut ctx.accounts.observations, reserve_stack_norm_before, reserve_quote_norm_before)?;
+
+        // only deposit at the current reserve ratio (the first deposit sets it)
+        let total_lp = ctx.accounts.lp_mint.supply as u128;
+        let (amount_stack, amount_quote) = if total_lp == 0 {
+            (amount_stack, amount_quote)
+        } else {
+            optimal_deposit(amount_stack, amount_quote, reserve_stack_before, reserve_quote_before)?
+        };
 
         // Transfer tokens from user to vault
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_stack_account.to_account_info().clone(),
-                    to: ctx.accounts.stack_vault.to_account_info().clone(),
-                    authority: ctx.accounts.user.to_account_info().clone(),
-                },
-            ),
+        transfer_from_user(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.user_stack_account,
+            &ctx.accounts.stack_vault,
+            &ctx.accounts.stack_mint,
+            ctx.remaining_accounts,
+            &ctx.accounts.user,
             amount_stack,
         )?;
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_quote_account.to_account_info().clone(),
-                    to: ctx.accounts.quote_vault.to_account_info().clone(),
-                    authority: ctx.accounts.user.to_account_info().clone(),
-                },
-            ),
+        transfer_from_user(
+            &ctx.accounts.token_program,
+            &ctx.accounts.user_quote_account,
+            &ctx.accounts.quote_vault,
+            &ctx.accounts.quote_mint,
+            &[],
+            &ctx.accounts.user,
             amount_quote,
         )?;
 
         // detect actual received amounts (handles fee-on-transfer)
-        let reserve_stack_after = ctx.accounts.stack_vault.amount;
-        let reserve_quote_after = ctx.accounts.quote_vault.amount;
-        let actual_in_stack = reserve_stack_after.checked_sub(reserve_stack_before).ok_or(AmmError::MathOverflow)?;
-        let actual_in_quote = reserve_quote_after.checked_sub(reserve_quote_before).ok_or(AmmError::MathOverflow)?;
+        ctx.accounts.stack_vault.reload()?;
+        ctx.accounts.quote_vault.reload()?;
+        let actual_in_stack = ctx.accounts.stack_vault.amount.checked_sub(stack_vault_before).ok_or(AmmError::MathOverflow)?;
+        let actual_in_quote = ctx.accounts.quote_vault.amount.checked_sub(quote_vault_before).ok_or(AmmError::MathOverflow)?;
 
         // Normalize amounts to common precision (u128)
         let norm_stack = normalize_amount_u128(actual_in_stack, stack_decimals, target_decimals)?;
         let norm_quote = normalize_amount_u128(actual_in_quote, quote_decimals, target_decimals)?;
 
-        // Calculate LP to mint
-        let lp_to_mint_u128: u128;
-        if ctx.accounts.lp_mint.supply == 0 {
-            // initial supply = floor(sqrt(norm_stack * norm_quote))
-            let a = norm_stack;
-            let b = norm_quote;
-            lp_to_mint_u128 = integer_sqrt(a.checked_mul(b).ok_or(AmmError::MathOverflow)?);
-        } else {
-            // minted = norm_stack * total_lp / reserve_stack_norm_before
-            let total_lp = ctx.accounts.lp_mint.supply as u128;
-            let reserve_stack_norm_before = normalize_amount_u128(reserve_stack_before, stack_decimals, target_decimals)?;
-            require!(reserve_stack_norm_before > 0, AmmError::NoLiquidity);
-            lp_to_mint_u128 = norm_stack
-                .checked_mul(total_lp).ok_or(AmmError::MathOverflow)?
-                .checked_div(reserve_stack_norm_before).ok_or(AmmError::MathOverflow)?;
-        }
+        // Calculate LP to mint from what actually arrived (fee-on-transfer safe)
+        let lp_to_mint_u128 = lp_for_deposit(pool, norm_stack, norm_quote, reserve_stack_norm_before, reserve_quote_norm_before, total_lp)?;
 
-        require!(lp_to_mint_u128 > 0, AmmError::ZeroLpMint);
-        let lp_to_mint = lp_to_mint_u128.try_into().map_err(|_| AmmError::MathOverflow)?;
+        // bootstrap: a slice of the first mint is locked forever so the share price can't be inflated or zeroed
+        let locked_lp = if total_lp == 0 { minimum_liquidity(&ctx.accounts.global) } else { 0 };
+        require!(lp_to_mint_u128 > locked_lp as u128, AmmError::ZeroLpMint);
+        let lp_to_mint: u64 = (lp_to_mint_u128 - locked_lp as u128).try_into().map_err(|_| AmmError::MathOverflow)?;
+        require!(lp_to_mint >= min_lp_out, AmmError::SlippageExceeded);
+
+        if locked_lp > 0 {
+            token_interface::mint_to(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    MintTo {
+                        mint: ctx.accounts.lp_mint.to_account_info().clone(),
+                        to: ctx.accounts.locked_lp.to_account_info().clone(),
+                        authority: ctx.accounts.vault_authority.to_account_info().clone(),
+                    },
+                )
+                .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[vault_authority_bump]]]),
+                locked_lp,
+            )?;
+        }
 
         // mint lp to user (vault_authority signs)
-        token::mint_to(
+        token_interface::mint_to(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 MintTo {
@@ -261,7 +866,7 @@ pub mod stackmint_amm {
                     authority: ctx.accounts.vault_authority.to_account_info().clone(),
                 },
             )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
+            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[vault_authority_bump]]]),
             lp_to_mint,
         )?;
 
@@ -269,45 +874,37 @@ pub mod stackmint_amm {
 
         // handle dust: use global config (fallback to const if zero)
         let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
-        if ctx.accounts.stack_vault.amount <= dust_threshold {
-            let amt = ctx.accounts.stack_vault.amount;
-            if amt > 0 {
-                token::transfer(
-                    CpiContext::new(
-                        ctx.accounts.token_program.to_account_info(),
-                        Transfer {
-                            from: ctx.accounts.stack_vault.to_account_info().clone(),
-                            to: ctx.accounts.treasury_token_account.to_account_info().clone(),
-                            authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                        },
-                    )
-                    .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                    amt,
-                )?;
-            }
-        }
-        if ctx.accounts.quote_vault.amount <= dust_threshold {
-            let amt = ctx.accounts.quote_vault.amount;
-            if amt > 0 {
-                token::transfer(
-                    CpiContext::new(
-                        ctx.accounts.token_program.to_account_info(),
-                        Transfer {
-                            from: ctx.accounts.quote_vault.to_account_info().clone(),
-                            to: ctx.accounts.treasury_token_account.to_account_info().clone(),
-                            authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                        },
-                    )
-                    .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                    amt,
-                )?;
-            }
-        }
+        sweep_vault_dust(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.stack_mint,
+            ctx.remaining_accounts,
+            &mut ctx.accounts.stack_vault,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            pool.accrued_fees_stack()?,
+            dust_threshold,
+        )?;
+        sweep_vault_dust(
+            &ctx.accounts.token_program,
+            &ctx.accounts.quote_mint,
+            &[],
+            &mut ctx.accounts.quote_vault,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            pool.accrued_fees_quote()?,
+            dust_threshold,
+        )?;
 
         emit!(LiquidityProvided {
             pool: pool_key,
             provider: ctx.accounts.user.key(),
             lp_minted: lp_to_mint,
+            amount_stack: actual_in_stack,
+            amount_quote: actual_in_quote,
         });
 
         pool.locked = false;
@@ -315,14 +912,24 @@ pub mod stackmint_amm {
     }
 
     /// Remove liquidity — burn LP and withdraw pro rata in normalized units
-    pub fn remove_liquidity(ctx: Context<RemoveLiquidity>, lp_amount: u64) -> Result<()> {
+    pub fn remove_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, RemoveLiquidity<'info>>,
+        lp_amount: u64,
+        min_stack_out: u64,
+        min_quote_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+
         // Capture pool key & bump BEFORE taking mutable borrow
         let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+        let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
 
         let pool = &mut ctx.accounts.pool;
         let global = &ctx.accounts.global;
-        require!(!global.paused && !pool.paused, AmmError::ProtocolPaused);
+        require!(!global.is_paused(PAUSE_WITHDRAWALS), AmmError::ProtocolPaused);
+        require!(!pool.is_paused(PAUSE_WITHDRAWALS), AmmError::PoolPaused);
         require!(!pool.locked, AmmError::Reentrancy);
         pool.locked = true;
 
@@ -330,7 +937,7 @@ pub mod stackmint_amm {
         require!(total_lp > 0, AmmError::NoLiquidity);
 
         // Burn LP from user
-        token::burn(
+        token_interface::burn(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 Burn {
@@ -349,11 +956,12 @@ pub mod stackmint_amm {
         let quote_decimals = ctx.accounts.quote_mint.decimals;
         let target_decimals = pool.decimal_normalize_to;
 
-        let reserve_stack = ctx.accounts.stack_vault.amount;
-        let reserve_quote = ctx.accounts.quote_vault.amount;
+        let reserve_stack = vault_reserve(ctx.accounts.stack_vault.amount, pool.accrued_fees_stack()?)?;
+        let reserve_quote = vault_reserve(ctx.accounts.quote_vault.amount, pool.accrued_fees_quote()?)?;
 
         let reserve_stack_norm = normalize_amount_u128(reserve_stack, stack_decimals, target_decimals)?;
         let reserve_quote_norm = normalize_amount_u128(reserve_quote, quote_decimals, target_decimals)?;
+        update_price_accumulators(pool, &mut ctx.accounts.observations, reserve_stack_norm, reserve_quote_norm)?;
 
         // out_norm = reserve_norm * lp_amount / total_lp
         let out_stack_norm = reserve_stack_norm
@@ -366,69 +974,63 @@ pub mod stackmint_amm {
         // denormalize back to native decimals
         let out_stack = denormalize_amount_u64(out_stack_norm, stack_decimals, target_decimals)?;
         let out_quote = denormalize_amount_u64(out_quote_norm, quote_decimals, target_decimals)?;
+        // minimums apply to what the user receives after any Token-2022 transfer fee
+        require!(
+            amount_after_transfer_fee(&ctx.accounts.stack_mint, out_stack)? >= min_stack_out
+                && amount_after_transfer_fee(&ctx.accounts.quote_mint, out_quote)? >= min_quote_out,
+            AmmError::SlippageExceeded
+        );
 
         // Transfer out tokens from vault to user (vault PDA signs)
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.stack_vault.to_account_info().clone(),
-                    to: ctx.accounts.user_stack_account.to_account_info().clone(),
-                    authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
+        transfer_from_vault(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.stack_vault,
+            &ctx.accounts.user_stack_account,
+            &ctx.accounts.stack_mint,
+            ctx.remaining_accounts,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
             out_stack,
         )?;
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.quote_vault.to_account_info().clone(),
-                    to: ctx.accounts.user_quote_account.to_account_info().clone(),
-                    authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.quote_vault,
+            &ctx.accounts.user_quote_account,
+            &ctx.accounts.quote_mint,
+            &[],
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
             out_quote,
         )?;
 
         // sweep dust if needed (use global config)
         let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
-        if ctx.accounts.stack_vault.amount <= dust_threshold {
-            let amt = ctx.accounts.stack_vault.amount;
-            if amt > 0 {
-                token::transfer(
-                    CpiContext::new(
-                        ctx.accounts.token_program.to_account_info(),
-                        Transfer {
-                            from: ctx.accounts.stack_vault.to_account_info().clone(),
-                            to: ctx.accounts.treasury_token_account.to_account_info().clone(),
-                            authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                        },
-                    )
-                    .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                    amt,
-                )?;
-            }
-        }
-        if ctx.accounts.quote_vault.amount <= dust_threshold {
-            let amt = ctx.accounts.quote_vault.amount;
-            if amt > 0 {
-                token::transfer(
-                    CpiContext::new(
-                        ctx.accounts.token_program.to_account_info(),
-                        Transfer {
-                            from: ctx.accounts.quote_vault.to_account_info().clone(),
-                            to: ctx.accounts.treasury_token_account.to_account_info().clone(),
-                            authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                        },
-                    )
-                    .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                    amt,
-                )?;
-            }
-        }
+        sweep_vault_dust(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.stack_mint,
+            ctx.remaining_accounts,
+            &mut ctx.accounts.stack_vault,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            pool.accrued_fees_stack()?,
+            dust_threshold,
+        )?;
+        sweep_vault_dust(
+            &ctx.accounts.token_program,
+            &ctx.accounts.quote_mint,
+            &[],
+            &mut ctx.accounts.quote_vault,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            pool.accrued_fees_quote()?,
+            dust_threshold,
+        )?;
 
         pool.total_lp_supply = pool.total_lp_supply.checked_sub(lp_amount_u128).ok_or(AmmError::MathOverflow)?;
 
@@ -436,569 +1038,741 @@ pub mod stackmint_amm {
             pool: pool_key,
             provider: ctx.accounts.user.key(),
             lp_burned: lp_amount,
+            amount_stack: out_stack,
+            amount_quote: out_quote,
         });
 
         pool.locked = false;
         Ok(())
     }
 
-    /// Swap: stack -> quote with consolidated fee helper and governance nonce check
-    pub fn swap_stack_to_quote(
-        ctx: Context<SwapStackToQuote>,
+    /// Single-sided ("zap") deposit: `side` names the internal swap, so `StackToQuote` deposits stack only.
+    /// The optimal fraction of the input is swapped through the pool's own curve (fees booked like a swap),
+    /// the remainder and the swap output both stay in the vaults, and LP is minted against the post-swap reserves.
+    pub fn provide_liquidity_single_sided<'info>(
+        ctx: Context<'_, '_, '_, 'info, ZapLiquidity<'info>>,
+        side: SwapDirection,
         amount_in: u64,
-        min_out: u64,
-        oracle_price: Option<u128>,
-        use_governance_approval: bool,
+        min_lp_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
     ) -> Result<()> {
-        // capture pool key & bump before mutable borrow
+        check_deadline(deadline_unix_ts, max_slot)?;
+
+        // Capture pool key & bump BEFORE taking a mutable borrow
         let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+        let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
+
         let pool = &mut ctx.accounts.pool;
         let global = &ctx.accounts.global;
-
-        if use_governance_approval {
-            // this will check approved && expiry && target and nonce > last_governance_nonce and update pool.last_governance_nonce
-            validate_governance_approval(&ctx.accounts.governance_approval, pool_key, pool)?;
-        }
-
-        require!(!global.paused && !pool.paused, AmmError::ProtocolPaused);
+        // a zap swaps half the input, so it also needs swaps open
+        require!(!global.is_paused(PAUSE_DEPOSITS) && !global.is_paused(PAUSE_SWAPS), AmmError::ProtocolPaused);
+        require!(!pool.is_paused(PAUSE_DEPOSITS) && !pool.is_paused(PAUSE_SWAPS), AmmError::PoolPaused);
         require!(!pool.locked, AmmError::Reentrancy);
         pool.locked = true;
 
-        // validate decimals & accounts
         validate_token_account_matches_mint(&ctx.accounts.user_stack_account, &ctx.accounts.stack_mint)?;
         validate_token_account_matches_mint(&ctx.accounts.user_quote_account, &ctx.accounts.quote_mint)?;
+        let stack_decimals = ctx.accounts.stack_mint.decimals;
+        let quote_decimals = ctx.accounts.quote_mint.decimals;
+        let target_decimals = pool.decimal_normalize_to;
+        let stack_in = side == SwapDirection::StackToQuote;
 
-        // Capture reserves before transfer
-        let reserve_stack_before = ctx.accounts.stack_vault.amount;
-        let reserve_quote_before = ctx.accounts.quote_vault.amount;
-
-        // Transfer stack from user to vault
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_stack_account.to_account_info().clone(),
-                    to: ctx.accounts.stack_vault.to_account_info().clone(),
-                    authority: ctx.accounts.user.to_account_info().clone(),
-                },
-            ),
-            amount_in,
-        )?;
+        let max_fee_bps = if global.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { global.max_fee_bps };
+        require!(pool.fee_bps <= max_fee_bps, AmmError::InvalidFee);
 
-        let reserve_stack_after = ctx.accounts.stack_vault.amount;
-        let actual_in_stack = reserve_stack_after.checked_sub(reserve_stack_before).ok_or(AmmError::MathOverflow)?;
+        // a zap needs an existing price to swap against
+        let total_lp = ctx.accounts.lp_mint.supply as u128;
+        require!(total_lp > 0, AmmError::NoLiquidity);
 
-        // normalize
-        let stack_norm = normalize_amount_u128(actual_in_stack, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
-        let reserve_stack_norm = normalize_amount_u128(reserve_stack_before, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
-        let reserve_quote_norm = normalize_amount_u128(reserve_quote_before, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
+        // trading reserves exclude protocol/creator fees accrued in the vaults
+        let stack_vault_before = ctx.accounts.stack_vault.amount;
+        let quote_vault_before = ctx.accounts.quote_vault.amount;
+        let reserve_stack = vault_reserve(stack_vault_before, pool.accrued_fees_stack()?)?;
+        let reserve_quote = vault_reserve(quote_vault_before, pool.accrued_fees_quote()?)?;
+        let reserve_stack_norm = normalize_amount_u128(reserve_stack, stack_decimals, target_decimals)?;
+        let reserve_quote_norm = normalize_amount_u128(reserve_quote, quote_decimals, target_decimals)?;
+        update_price_accumulators(pool, &mut ctx.accounts.observations, reserve_stack_norm, reserve_quote_norm)?;
 
-        // compute fees via centralized helper
-        let (_gross_fee, protocol_fee, creator_fee, net_in) =
-            compute_fees(stack_norm, pool.fee_bps, ctx.accounts.global.protocol_fee_bps, ctx.accounts.stack_info.creator_fee_bps)?;
+        // Transfer the single input token from user to its vault
+        let (program, from, to, mint, hook_accounts, vault_before) = if stack_in {
+            (&ctx.accounts.stack_token_program, &ctx.accounts.user_stack_account, &ctx.accounts.stack_vault, &ctx.accounts.stack_mint, ctx.remaining_accounts, stack_vault_before)
+        } else {
+            (&ctx.accounts.token_program, &ctx.accounts.user_quote_account, &ctx.accounts.quote_vault, &ctx.accounts.quote_mint, &[][..], quote_vault_before)
+        };
+        transfer_from_user(program, from, to, mint, hook_accounts, &ctx.accounts.user, amount_in)?;
+
+        // detect actual received amount (handles fee-on-transfer)
+        let vault_after = if stack_in {
+            ctx.accounts.stack_vault.reload()?;
+            ctx.accounts.stack_vault.amount
+        } else {
+            ctx.accounts.quote_vault.reload()?;
+            ctx.accounts.quote_vault.amount
+        };
+        let actual_in = vault_after.checked_sub(vault_before).ok_or(AmmError::MathOverflow)?;
 
-        // circuit breaker: ensure fees don't exceed some absolute safety threshold (global.max_fee_bps)
-        let max_fee_bps = if ctx.accounts.global.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { ctx.accounts.global.max_fee_bps };
-        require!(pool.fee_bps <= max_fee_bps, AmmError::InvalidFee);
+        let (in_decimals, out_decimals, reserve_in, reserve_out, reserve_in_norm, reserve_out_norm) = if stack_in {
+            (stack_decimals, quote_decimals, reserve_stack, reserve_quote, reserve_stack_norm, reserve_quote_norm)
+        } else {
+            (quote_decimals, stack_decimals, reserve_quote, reserve_stack, reserve_quote_norm, reserve_stack_norm)
+        };
+
+        // swap leg: optimal fraction for the pool's curve, priced exactly like an exact-in swap
+        let swap_in = zap_swap_in(pool, stack_in, actual_in, reserve_in, reserve_out, in_decimals, out_decimals)?;
+        let (gross_fee, protocol_fee, creator_fee, net_in_native) =
+            compute_fees(swap_in, pool.fee_bps, global.protocol_fee_bps, ctx.accounts.stack_info.creator_fee_bps)?;
+        let net_in = normalize_amount_u128(net_in_native as u64, in_decimals, target_decimals)?;
+        let swap_out_norm = curve_amount_out(pool, stack_in, net_in, reserve_in_norm, reserve_out_norm)?;
+        let swap_out = denormalize_amount_u64(swap_out_norm, out_decimals, target_decimals)?;
+        if net_in > 0 {
+            let (quote_norm, stack_norm) = if stack_in { (swap_out_norm, net_in) } else { (net_in, swap_out_norm) };
+            enforce_oracle_guardrail(pool, &ctx.accounts.oracle.to_account_info(), quote_norm, stack_norm)?;
+        }
+        book_swap_fees(pool, pool_key, stack_in, gross_fee, protocol_fee, creator_fee)?;
+
+        // deposit leg: the unswapped remainder plus the swap output (which never leaves its vault),
+        // priced against the reserves as they stand after the swap
+        let deposit_in: u64 = (actual_in as u128).checked_sub(swap_in).ok_or(AmmError::MathOverflow)?
+            .try_into().map_err(|_| AmmError::MathOverflow)?;
+        let reserve_in_after: u64 = (reserve_in as u128)
+            .checked_add(swap_in).ok_or(AmmError::MathOverflow)?
+            .checked_sub(protocol_fee).ok_or(AmmError::MathOverflow)?
+            .checked_sub(creator_fee).ok_or(AmmError::MathOverflow)?
+            .try_into().map_err(|_| AmmError::MathOverflow)?;
+        let reserve_out_after = reserve_out.checked_sub(swap_out).ok_or(AmmError::MathOverflow)?;
+        let (deposit_stack, deposit_quote, reserve_stack_after, reserve_quote_after) = if stack_in {
+            (deposit_in, swap_out, reserve_in_after, reserve_out_after)
+        } else {
+            (swap_out, deposit_in, reserve_out_after, reserve_in_after)
+        };
 
-        // constant product out calculation in normalized units:
-        let amount_out_norm = get_amount_out(net_in, reserve_stack_norm, reserve_quote_norm)?;
+        let lp_to_mint_u128 = lp_for_deposit(
+            pool,
+            normalize_amount_u128(deposit_stack, stack_decimals, target_decimals)?,
+            normalize_amount_u128(deposit_quote, quote_decimals, target_decimals)?,
+            normalize_amount_u128(reserve_stack_after, stack_decimals, target_decimals)?,
+            normalize_amount_u128(reserve_quote_after, quote_decimals, target_decimals)?,
+            total_lp,
+        )?;
+        require!(lp_to_mint_u128 > 0, AmmError::ZeroLpMint);
+        let lp_to_mint: u64 = lp_to_mint_u128.try_into().map_err(|_| AmmError::MathOverflow)?;
+        require!(lp_to_mint >= min_lp_out, AmmError::SlippageExceeded);
 
-        // Price protection via provided oracle (if given)
-        if let Some(op) = oracle_price {
-            if net_in == 0 {
-                return Err(AmmError::SlippageExceeded.into());
-            }
-            let implied_price_x = amount_out_norm
-                .checked_mul(10u128.pow(pool.decimal_normalize_to as u32)).ok_or(AmmError::MathOverflow)?
-                .checked_div(net_in).ok_or(AmmError::MathOverflow)?;
-            // Compare op and implied_price_x (both in same scaling expected from caller)
-            let allowed = pool.max_price_deviation_bps as u128;
-            let diff = if op > implied_price_x { op - implied_price_x } else { implied_price_x - op };
-            let pct = diff.checked_mul(BPS_DENOM).ok_or(AmmError::MathOverflow)?.checked_div(op).ok_or(AmmError::MathOverflow)?;
-            require!(pct <= allowed, AmmError::OraclePriceMismatch);
-        }
-
-        // convert amount_out_norm -> native quote units
-        let amount_out = denormalize_amount_u64(amount_out_norm, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
-        require!(amount_out >= min_out, AmmError::SlippageExceeded);
-
-        // route fees using helper (convert normalized fee -> quote native and transfer)
-        let (protocol_fee_quote_norm, creator_fee_quote_norm) = route_fee_to_quote_vault(
-            &ctx.accounts.token_program,
-            &ctx.accounts.quote_vault,
-            &ctx.accounts.protocol_fee_vault,
-            &ctx.accounts.creator_fee_vault,
-            &ctx.accounts.vault_authority,
-            pool_key,
-            pool_bump,
-            protocol_fee,
-            creator_fee,
-            reserve_stack_norm,
-            reserve_quote_norm,
-            ctx.accounts.quote_mint.decimals,
-            pool.decimal_normalize_to,
-        )?;
-
-        // update pool creator claimable with normalized quote-equivalent
-        if creator_fee_quote_norm > 0 {
-            pool.creator_claimable = pool.creator_claimable.checked_add(creator_fee_quote_norm).ok_or(AmmError::MathOverflow)?;
-            pool.creator_last_claim_ts = Clock::get()?.unix_timestamp;
-        }
-
-        // Transfer amount_out from quote_vault to user (vault PDA signs)
-        token::transfer(
+        // mint lp to user (vault_authority signs)
+        token_interface::mint_to(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.quote_vault.to_account_info().clone(),
-                    to: ctx.accounts.user_quote_account.to_account_info().clone(),
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info().clone(),
+                    to: ctx.accounts.user_lp_account.to_account_info().clone(),
                     authority: ctx.accounts.vault_authority.to_account_info().clone(),
                 },
             )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-            amount_out,
+            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[vault_authority_bump]]]),
+            lp_to_mint,
         )?;
 
-        // sweep dust if tiny leftover (configurable)
+        pool.total_lp_supply = pool.total_lp_supply.checked_add(lp_to_mint_u128).ok_or(AmmError::MathOverflow)?;
+
+        // handle dust: use global config (fallback to const if zero)
         let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
-        if ctx.accounts.quote_vault.amount <= dust_threshold {
-            let amt = ctx.accounts.quote_vault.amount;
-            if amt > 0 {
-                token::transfer(
-                    CpiContext::new(
-                        ctx.accounts.token_program.to_account_info(),
-                        Transfer {
-                            from: ctx.accounts.quote_vault.to_account_info().clone(),
-                            to: ctx.accounts.treasury_token_account.to_account_info().clone(),
-                            authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                        },
-                    )
-                    .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                    amt,
-                )?;
-            }
-        }
+        sweep_vault_dust(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.stack_mint,
+            ctx.remaining_accounts,
+            &mut ctx.accounts.stack_vault,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            pool.accrued_fees_stack()?,
+            dust_threshold,
+        )?;
+        sweep_vault_dust(
+            &ctx.accounts.token_program,
+            &ctx.accounts.quote_mint,
+            &[],
+            &mut ctx.accounts.quote_vault,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            pool.accrued_fees_quote()?,
+            dust_threshold,
+        )?;
 
         emit!(Swap {
             pool: pool_key,
             trader: ctx.accounts.user.key(),
-            side: SwapDirection::StackToQuote,
-            amount_in,
-            amount_out,
+            side,
+            amount_in: swap_in as u64,
+            amount_out: swap_out,
+        });
+        emit!(LiquidityProvided {
+            pool: pool_key,
+            provider: ctx.accounts.user.key(),
+            lp_minted: lp_to_mint,
+            amount_stack: deposit_stack,
+            amount_quote: deposit_quote,
         });
 
+        request_rebalance_hook(
+            &mut ctx.accounts.stack_info,
+            global,
+            pool,
+            &mut ctx.accounts.stack_mint,
+            &mut ctx.accounts.stack_vault,
+            &mut ctx.accounts.quote_vault,
+            ctx.accounts.user.key(),
+            if stack_in { RebalanceTrade::StackToQuote } else { RebalanceTrade::QuoteToStack },
+            swap_in as u64,
+            swap_out,
+            reserve_in,
+        )?;
+
         pool.locked = false;
         Ok(())
     }
 
-    /// Swap: quote -> stack (uses same fee helper & governance nonce check)
-    pub fn swap_quote_to_stack(
-        ctx: Context<SwapQuoteToStack>,
-        amount_in: u64,
-        min_out: u64,
-        oracle_price: Option<u128>,
-        use_governance_approval: bool,
+    /// Single-sided ("zap") withdrawal: burns LP for its pro-rata share, then sells the `side` input leg
+    /// back into the pool so only one asset is paid out (`StackToQuote` pays quote only), guarded by `min_amount_out`.
+    pub fn remove_liquidity_single_sided<'info>(
+        ctx: Context<'_, '_, '_, 'info, ZapLiquidity<'info>>,
+        lp_amount: u64,
+        side: SwapDirection,
+        min_amount_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
     ) -> Result<()> {
-        // capture pool key & bump before mutable borrow
+        check_deadline(deadline_unix_ts, max_slot)?;
+
+        // Capture pool key & bump BEFORE taking mutable borrow
         let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+        let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
+
         let pool = &mut ctx.accounts.pool;
         let global = &ctx.accounts.global;
-
-        if use_governance_approval {
-            validate_governance_approval(&ctx.accounts.governance_approval, pool_key, pool)?;
-        }
-
-        require!(!global.paused && !pool.paused, AmmError::ProtocolPaused);
+        // the unwanted side is swapped back, so swaps must be open as well
+        require!(!global.is_paused(PAUSE_WITHDRAWALS) && !global.is_paused(PAUSE_SWAPS), AmmError::ProtocolPaused);
+        require!(!pool.is_paused(PAUSE_WITHDRAWALS) && !pool.is_paused(PAUSE_SWAPS), AmmError::PoolPaused);
         require!(!pool.locked, AmmError::Reentrancy);
         pool.locked = true;
 
-        // Validate accounts
-        validate_token_account_matches_mint(&ctx.accounts.user_quote_account, &ctx.accounts.quote_mint)?;
         validate_token_account_matches_mint(&ctx.accounts.user_stack_account, &ctx.accounts.stack_mint)?;
+        validate_token_account_matches_mint(&ctx.accounts.user_quote_account, &ctx.accounts.quote_mint)?;
+        let stack_decimals = ctx.accounts.stack_mint.decimals;
+        let quote_decimals = ctx.accounts.quote_mint.decimals;
+        let target_decimals = pool.decimal_normalize_to;
+        let stack_in = side == SwapDirection::StackToQuote;
+
+        let max_fee_bps = if global.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { global.max_fee_bps };
+        require!(pool.fee_bps <= max_fee_bps, AmmError::InvalidFee);
 
-        // reserves before
-        let reserve_stack_before = ctx.accounts.stack_vault.amount;
-        let reserve_quote_before = ctx.accounts.quote_vault.amount;
+        let total_lp = ctx.accounts.lp_mint.supply as u128;
+        require!(total_lp > 0, AmmError::NoLiquidity);
 
-        // Transfer quote from user to quote_vault
-        token::transfer(
+        // Burn LP from user
+        token_interface::burn(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_quote_account.to_account_info().clone(),
-                    to: ctx.accounts.quote_vault.to_account_info().clone(),
+                Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info().clone(),
+                    from: ctx.accounts.user_lp_account.to_account_info().clone(),
                     authority: ctx.accounts.user.to_account_info().clone(),
                 },
             ),
-            amount_in,
+            lp_amount,
         )?;
 
-        let reserve_quote_after = ctx.accounts.quote_vault.amount;
-        let actual_in_quote = reserve_quote_after.checked_sub(reserve_quote_before).ok_or(AmmError::MathOverflow)?;
-
-        // normalize
-        let quote_norm = normalize_amount_u128(actual_in_quote, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
-        let reserve_quote_norm = normalize_amount_u128(reserve_quote_before, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
-        let reserve_stack_norm = normalize_amount_u128(reserve_stack_before, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
-
-        // compute fees via centralized helper
-        let (_gross_fee, protocol_fee, creator_fee, net_in) =
-            compute_fees(quote_norm, pool.fee_bps, ctx.accounts.global.protocol_fee_bps, ctx.accounts.stack_info.creator_fee_bps)?;
+        let reserve_stack = vault_reserve(ctx.accounts.stack_vault.amount, pool.accrued_fees_stack()?)?;
+        let reserve_quote = vault_reserve(ctx.accounts.quote_vault.amount, pool.accrued_fees_quote()?)?;
+        let reserve_stack_norm = normalize_amount_u128(reserve_stack, stack_decimals, target_decimals)?;
+        let reserve_quote_norm = normalize_amount_u128(reserve_quote, quote_decimals, target_decimals)?;
+        update_price_accumulators(pool, &mut ctx.accounts.observations, reserve_stack_norm, reserve_quote_norm)?;
 
-        // constant product out calculation in normalized units:
-        let amount_out_norm = get_amount_out(net_in, reserve_quote_norm, reserve_stack_norm)?;
+        // pro-rata share in normalized units, denormalized back to native decimals
+        let out_stack = denormalize_amount_u64(checked_mul_div(reserve_stack_norm, lp_amount as u128, total_lp)?, stack_decimals, target_decimals)?;
+        let out_quote = denormalize_amount_u64(checked_mul_div(reserve_quote_norm, lp_amount as u128, total_lp)?, quote_decimals, target_decimals)?;
 
-        // price protection if oracle provided
-        if let Some(op) = oracle_price {
-            if net_in == 0 { return Err(AmmError::SlippageExceeded.into()); }
-            let implied_price_x = amount_out_norm
-                .checked_mul(10u128.pow(pool.decimal_normalize_to as u32)).ok_or(AmmError::MathOverflow)?
-                .checked_div(net_in).ok_or(AmmError::MathOverflow)?;
-            let allowed = pool.max_price_deviation_bps as u128;
-            let diff = if op > implied_price_x { op - implied_price_x } else { implied_price_x - op };
-            let pct = diff.checked_mul(BPS_DENOM).ok_or(AmmError::MathOverflow)?.checked_div(op).ok_or(AmmError::MathOverflow)?;
-            require!(pct <= allowed, AmmError::OraclePriceMismatch);
+        let (in_decimals, out_decimals, share_in, share_out, reserve_in, reserve_out) = if stack_in {
+            (stack_decimals, quote_decimals, out_stack, out_quote, reserve_stack, reserve_quote)
+        } else {
+            (quote_decimals, stack_decimals, out_quote, out_stack, reserve_quote, reserve_stack)
+        };
+
+        // swap leg: the input-side share never leaves its vault; it is sold against the post-withdrawal reserves
+        let reserve_in_after = reserve_in.checked_sub(share_in).ok_or(AmmError::MathOverflow)?;
+        let reserve_out_after = reserve_out.checked_sub(share_out).ok_or(AmmError::MathOverflow)?;
+        let (gross_fee, protocol_fee, creator_fee, net_in_native) =
+            compute_fees(share_in as u128, pool.fee_bps, global.protocol_fee_bps, ctx.accounts.stack_info.creator_fee_bps)?;
+        let net_in = normalize_amount_u128(net_in_native as u64, in_decimals, target_decimals)?;
+        let swap_out_norm = curve_amount_out(
+            pool,
+            stack_in,
+            net_in,
+            normalize_amount_u128(reserve_in_after, in_decimals, target_decimals)?,
+            normalize_amount_u128(reserve_out_after, out_decimals, target_decimals)?,
+        )?;
+        let swap_out = denormalize_amount_u64(swap_out_norm, out_decimals, target_decimals)?;
+        if net_in > 0 {
+            let (quote_norm, stack_norm) = if stack_in { (swap_out_norm, net_in) } else { (net_in, swap_out_norm) };
+            enforce_oracle_guardrail(pool, &ctx.accounts.oracle.to_account_info(), quote_norm, stack_norm)?;
         }
+        book_swap_fees(pool, pool_key, stack_in, gross_fee, protocol_fee, creator_fee)?;
 
-        // denormalize amount_out to native stack units
-        let amount_out = denormalize_amount_u64(amount_out_norm, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
-        require!(amount_out >= min_out, AmmError::SlippageExceeded);
+        let amount_out = share_out.checked_add(swap_out).ok_or(AmmError::MathOverflow)?;
 
-        // route fees: compute equivalent stack amount for protocol and creator fees (approx) and transfer
-        route_fee_to_stack_vault(
-            &ctx.accounts.token_program,
-            &ctx.accounts.stack_vault,
-            &ctx.accounts.protocol_fee_vault,
-            &ctx.accounts.creator_fee_vault,
+        // Transfer the single output token from its vault to user (vault PDA signs)
+        let (program, from, to, mint, hook_accounts) = if stack_in {
+            (&ctx.accounts.token_program, &ctx.accounts.quote_vault, &ctx.accounts.user_quote_account, &ctx.accounts.quote_mint, &[][..])
+        } else {
+            (&ctx.accounts.stack_token_program, &ctx.accounts.stack_vault, &ctx.accounts.user_stack_account, &ctx.accounts.stack_mint, ctx.remaining_accounts)
+        };
+        // slippage is checked on what the user receives after any Token-2022 transfer fee
+        require!(amount_after_transfer_fee(mint, amount_out)? >= min_amount_out, AmmError::SlippageExceeded);
+        transfer_from_vault(program, from, to, mint, hook_accounts, &ctx.accounts.vault_authority, pool_key, vault_authority_bump, amount_out)?;
+
+        // sweep dust if needed (use global config)
+        let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
+        sweep_vault_dust(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.stack_mint,
+            ctx.remaining_accounts,
+            &mut ctx.accounts.stack_vault,
+            &ctx.accounts.treasury_token_account,
             &ctx.accounts.vault_authority,
             pool_key,
-            pool_bump,
-            protocol_fee,
-            creator_fee,
-            reserve_quote_norm,
-            reserve_stack_norm,
-            ctx.accounts.stack_mint.decimals,
-            pool.decimal_normalize_to,
-            pool,
+            vault_authority_bump,
+            pool.accrued_fees_stack()?,
+            dust_threshold,
         )?;
-
-        // transfer stacks to user
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.stack_vault.to_account_info().clone(),
-                    to: ctx.accounts.user_stack_account.to_account_info().clone(),
-                    authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-            amount_out,
+        sweep_vault_dust(
+            &ctx.accounts.token_program,
+            &ctx.accounts.quote_mint,
+            &[],
+            &mut ctx.accounts.quote_vault,
+            &ctx.accounts.treasury_token_account,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            pool.accrued_fees_quote()?,
+            dust_threshold,
         )?;
 
-        // sweep dust if tiny leftover
-        let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
-        if ctx.accounts.stack_vault.amount <= dust_threshold {
-            let amt = ctx.accounts.stack_vault.amount;
-            if amt > 0 {
-                token::transfer(
-                    CpiContext::new(
-                        ctx.accounts.token_program.to_account_info(),
-                        Transfer {
-                            from: ctx.accounts.stack_vault.to_account_info().clone(),
-                            to: ctx.accounts.treasury_token_account.to_account_info().clone(),
-                            authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                        },
-                    )
-                    .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                    amt,
-                )?;
-            }
-        }
+        pool.total_lp_supply = pool.total_lp_supply.checked_sub(lp_amount as u128).ok_or(AmmError::MathOverflow)?;
 
+        emit!(LiquidityRemoved {
+            pool: pool_key,
+            provider: ctx.accounts.user.key(),
+            lp_burned: lp_amount,
+            amount_stack: out_stack,
+            amount_quote: out_quote,
+        });
         emit!(Swap {
             pool: pool_key,
             trader: ctx.accounts.user.key(),
-            side: SwapDirection::QuoteToStack,
-            amount_in,
-            amount_out,
+            side,
+            amount_in: share_in,
+            amount_out: swap_out,
         });
 
+        request_rebalance_hook(
+            &mut ctx.accounts.stack_info,
+            global,
+            pool,
+            &mut ctx.accounts.stack_mint,
+            &mut ctx.accounts.stack_vault,
+            &mut ctx.accounts.quote_vault,
+            ctx.accounts.user.key(),
+            if stack_in { RebalanceTrade::StackToQuote } else { RebalanceTrade::QuoteToStack },
+            share_in,
+            swap_out,
+            reserve_in_after,
+        )?;
+
         pool.locked = false;
         Ok(())
     }
 
-    /// Mint stack via pool: quote -> stack then mint stack tokens (stack mint authority PDA must sign)
-    pub fn mint_stack_via_pool(
-        ctx: Context<MintStackViaPool>,
-        quote_in: u64,
-        min_stack_out: u64,
-        oracle_price: Option<u128>,
+    /// Multi-hop exact-in swap across StackMint pools (e.g. Stack A -> quote -> Stack B in one transaction).
+    /// `remaining_accounts` carries ROUTE_ACCOUNTS_PER_HOP accounts per hop, in route order:
+    /// [pool, stack_mint, quote_mint, stack_vault, quote_vault, vault_authority, stack_info, observations, oracle,
+    /// stack_token_program, quote_token_program]. Pools, vaults and observations must be writable, as must stack info
+    /// when the stack has a rebalance hook. Accounts after the last hop are passed to Token-2022 transfer hooks.
+    /// Intermediate amounts move vault-to-vault; only the final output (net of any transfer fee) is checked against
+    /// `min_out`.
+    pub fn route_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RouteSwap<'info>>,
+        amount_in: u64,
+        min_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
     ) -> Result<()> {
-        // capture pool key & bump first
-        let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+        check_deadline(deadline_unix_ts, max_slot)?;
+        process_route_swap(ctx, amount_in, min_out)
+    }
 
+    /// Flash loan: lend from the vaults for the rest of the transaction. A `flash_repay` for the same pool
+    /// must appear later in the transaction (checked via the instructions sysvar); until it runs the pool
+    /// stays `locked`, so every other pool instruction (including a second borrow) fails with `Reentrancy`.
+    pub fn flash_borrow<'info>(ctx: Context<'_, '_, '_, 'info, FlashBorrow<'info>>, amount_stack: u64, amount_quote: u64) -> Result<()> {
+        let pool_key = ctx.accounts.pool.key();
+        let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
         let pool = &mut ctx.accounts.pool;
         let global = &ctx.accounts.global;
-        require!(!global.paused && !pool.paused, AmmError::ProtocolPaused);
+        require!(!global.is_paused(PAUSE_SWAPS), AmmError::ProtocolPaused);
+        require!(!pool.is_paused(PAUSE_SWAPS), AmmError::PoolPaused);
         require!(!pool.locked, AmmError::Reentrancy);
-        pool.locked = true;
-
-        // Perform a quote->stack swap (reuse logic simplified)
-        // reserves before
-        let reserve_stack_before = ctx.accounts.stack_vault.amount;
-        let reserve_quote_before = ctx.accounts.quote_vault.amount;
-
-        // transfer quote in
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_quote_account.to_account_info().clone(),
-                    to: ctx.accounts.quote_vault.to_account_info().clone(),
-                    authority: ctx.accounts.user.to_account_info().clone(),
-                },
-            ),
-            quote_in,
-        )?;
-        let reserve_quote_after = ctx.accounts.quote_vault.amount;
-        let actual_in_quote = reserve_quote_after.checked_sub(reserve_quote_before).ok_or(AmmError::MathOverflow)?;
+        require!(amount_stack > 0 || amount_quote > 0, AmmError::NoLiquidity);
 
-        // normalize
-        let quote_norm = normalize_amount_u128(actual_in_quote, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
-        let reserve_quote_norm = normalize_amount_u128(reserve_quote_before, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
-        let reserve_stack_norm = normalize_amount_u128(reserve_stack_before, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
+        // the loan only goes out if this transaction also repays it
+        require_flash_repay(&ctx.accounts.instructions.to_account_info(), pool_key)?;
 
-        // fees
-        let (_gross_fee, protocol_fee, creator_fee, net_in) =
-            compute_fees(quote_norm, pool.fee_bps, ctx.accounts.global.protocol_fee_bps, ctx.accounts.stack_info.creator_fee_bps)?;
+        // remember the pre-loan trading reserves; flash_repay checks the invariant against them
+        pool.flash_reserve_stack = vault_reserve(ctx.accounts.stack_vault.amount, pool.accrued_fees_stack()?)?;
+        pool.flash_reserve_quote = vault_reserve(ctx.accounts.quote_vault.amount, pool.accrued_fees_quote()?)?;
+        require!(amount_stack < pool.flash_reserve_stack && amount_quote < pool.flash_reserve_quote, AmmError::InsufficientLiquidity);
+        pool.flash_loan_stack = amount_stack;
+        pool.flash_loan_quote = amount_quote;
+        pool.locked = true;
 
-        // compute amount_out normalized
-        let amount_out_norm = get_amount_out(net_in, reserve_quote_norm, reserve_stack_norm)?;
-        let amount_out_native = denormalize_amount_u64(amount_out_norm, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
-        require!(amount_out_native >= min_stack_out, AmmError::SlippageExceeded);
+        if amount_stack > 0 {
+            transfer_from_vault(
+                &ctx.accounts.stack_token_program,
+                &ctx.accounts.stack_vault,
+                &ctx.accounts.user_stack_account,
+                &ctx.accounts.stack_mint,
+                ctx.remaining_accounts,
+                &ctx.accounts.vault_authority,
+                pool_key,
+                vault_authority_bump,
+                amount_stack,
+            )?;
+        }
+        if amount_quote > 0 {
+            transfer_from_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.quote_vault,
+                &ctx.accounts.user_quote_account,
+                &ctx.accounts.quote_mint,
+                &[],
+                &ctx.accounts.vault_authority,
+                pool_key,
+                vault_authority_bump,
+                amount_quote,
+            )?;
+        }
 
-        // route fees to fee vaults (approximations) and update pool.creator_claimable
-        route_fee_to_stack_vault(
-            &ctx.accounts.token_program,
-            &ctx.accounts.stack_vault,
-            &ctx.accounts.protocol_fee_vault,
-            &ctx.accounts.creator_fee_vault,
-            &ctx.accounts.vault_authority,
-            pool_key,
-            pool_bump,
-            protocol_fee,
-            creator_fee,
-            reserve_quote_norm,
-            reserve_stack_norm,
-            ctx.accounts.stack_mint.decimals,
-            pool.decimal_normalize_to,
-            pool,
-        )?;
+        emit!(FlashLoan {
+            pool: pool_key,
+            borrower: ctx.accounts.user.key(),
+            amount_stack,
+            amount_quote,
+        });
+        Ok(())
+    }
 
-        // Mint stack tokens to user (stack_mint_auth PDA signs)
-        let stack_info_bump = ctx.accounts.stack_info.bump;
-        token::mint_to(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                MintTo {
-                    mint: ctx.accounts.stack_mint.to_account_info().clone(),
-                    to: ctx.accounts.user_stack_account.to_account_info().clone(),
-                    authority: ctx.accounts.stack_mint_auth.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"stack_mint_auth", ctx.accounts.stack_mint.to_account_info().key.as_ref(), &[stack_info_bump]]]),
-            amount_out_native,
-        )?;
+    /// Close a flash loan: pull principal + flash fee (`pool.fee_bps`, split like a swap fee) from the
+    /// repayer, then require the reserves to cover the pre-loan reserves plus the LP fee share on each side
+    /// and the constant product not to have shrunk. Unlocks the pool.
+    pub fn flash_repay<'info>(ctx: Context<'_, '_, '_, 'info, FlashRepay<'info>>) -> Result<()> {
+        let pool_key = ctx.accounts.pool.key();
+        let pool = &mut ctx.accounts.pool;
+        require!(pool.locked && (pool.flash_loan_stack > 0 || pool.flash_loan_quote > 0), AmmError::NoFlashLoan);
+
+        let protocol_fee_bps = ctx.accounts.global.protocol_fee_bps;
+        let creator_fee_bps = ctx.accounts.stack_info.creator_fee_bps;
+        let (fee_stack, protocol_fee_stack, creator_fee_stack, _) =
+            compute_fees(pool.flash_loan_stack as u128, pool.fee_bps, protocol_fee_bps, creator_fee_bps)?;
+        let (fee_quote, protocol_fee_quote, creator_fee_quote, _) =
+            compute_fees(pool.flash_loan_quote as u128, pool.fee_bps, protocol_fee_bps, creator_fee_bps)?;
+        let repay_stack: u64 = (pool.flash_loan_stack as u128).checked_add(fee_stack).ok_or(AmmError::MathOverflow)?
+            .try_into().map_err(|_| AmmError::MathOverflow)?;
+        let repay_quote: u64 = (pool.flash_loan_quote as u128).checked_add(fee_quote).ok_or(AmmError::MathOverflow)?
+            .try_into().map_err(|_| AmmError::MathOverflow)?;
+
+        // the vaults must receive principal + fee in full, so any Token-2022 transfer fee is on the repayer
+        if repay_stack > 0 {
+            transfer_from_user(
+                &ctx.accounts.stack_token_program,
+                &ctx.accounts.user_stack_account,
+                &ctx.accounts.stack_vault,
+                &ctx.accounts.stack_mint,
+                ctx.remaining_accounts,
+                &ctx.accounts.user,
+                transfer_amount_for(&ctx.accounts.stack_mint, repay_stack)?,
+            )?;
+        }
+        if repay_quote > 0 {
+            transfer_from_user(
+                &ctx.accounts.token_program,
+                &ctx.accounts.user_quote_account,
+                &ctx.accounts.quote_vault,
+                &ctx.accounts.quote_mint,
+                &[],
+                &ctx.accounts.user,
+                transfer_amount_for(&ctx.accounts.quote_mint, repay_quote)?,
+            )?;
+        }
 
-        // sweep tiny dust from quote vault if needed
-        let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
-        if ctx.accounts.quote_vault.amount <= dust_threshold {
-            let amt = ctx.accounts.quote_vault.amount;
-            if amt > 0 {
-                token::transfer(
-                    CpiContext::new(
-                        ctx.accounts.token_program.to_account_info(),
-                        Transfer {
-                            from: ctx.accounts.quote_vault.to_account_info().clone(),
-                            to: ctx.accounts.treasury_token_account.to_account_info().clone(),
-                            authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                        },
-                    )
-                    .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                    amt,
-                )?;
-            }
+        // protocol & creator shares accrue like swap fees; the LP share must stay in the reserves
+        if fee_stack > 0 {
+            book_swap_fees(pool, pool_key, true, fee_stack, protocol_fee_stack, creator_fee_stack)?;
+        }
+        if fee_quote > 0 {
+            book_swap_fees(pool, pool_key, false, fee_quote, protocol_fee_quote, creator_fee_quote)?;
         }
+        let lp_fee_stack = fee_stack - protocol_fee_stack - creator_fee_stack;
+        let lp_fee_quote = fee_quote - protocol_fee_quote - creator_fee_quote;
+
+        // measured after transfer so fee-on-transfer shortfalls are caught
+        ctx.accounts.stack_vault.reload()?;
+        ctx.accounts.quote_vault.reload()?;
+        let reserve_stack = vault_reserve(ctx.accounts.stack_vault.amount, pool.accrued_fees_stack()?)? as u128;
+        let reserve_quote = vault_reserve(ctx.accounts.quote_vault.amount, pool.accrued_fees_quote()?)? as u128;
+        let reserve_stack_before = pool.flash_reserve_stack as u128;
+        let reserve_quote_before = pool.flash_reserve_quote as u128;
+        require!(
+            reserve_stack >= reserve_stack_before.checked_add(lp_fee_stack).ok_or(AmmError::MathOverflow)?
+                && reserve_quote >= reserve_quote_before.checked_add(lp_fee_quote).ok_or(AmmError::MathOverflow)?,
+            AmmError::FlashLoanInvariant
+        );
+        let k_before = reserve_stack_before.checked_mul(reserve_quote_before).ok_or(AmmError::MathOverflow)?;
+        let k_after = reserve_stack.checked_mul(reserve_quote).ok_or(AmmError::MathOverflow)?;
+        require!(k_after >= k_before, AmmError::FlashLoanInvariant);
 
-        emit!(MintedStackViaPool {
+        emit!(FlashLoanRepaid {
             pool: pool_key,
-            user: ctx.accounts.user.key(),
-            quote_in,
-            stack_out: amount_out_native,
+            repayer: ctx.accounts.user.key(),
+            amount_stack: pool.flash_loan_stack,
+            amount_quote: pool.flash_loan_quote,
+            fee_stack: fee_stack as u64,
+            fee_quote: fee_quote as u64,
         });
 
+        pool.flash_loan_stack = 0;
+        pool.flash_loan_quote = 0;
+        pool.flash_reserve_stack = 0;
+        pool.flash_reserve_quote = 0;
         pool.locked = false;
         Ok(())
     }
 
-    /// Redeem stack via pool: burn stacks and pay quote (approx via AMM)
-    pub fn redeem_stack_via_pool(
-        ctx: Context<RedeemStackViaPool>,
-        stack_in: u64,
-        min_quote_out: u64,
+    /// Swap: stack -> quote with consolidated fee helper and governance nonce check
+    pub fn swap_stack_to_quote<'info>(
+        ctx: Context<'_, '_, '_, 'info, SwapStackToQuote<'info>>,
+        amount_in: u64,
+        min_out: u64,
+        use_governance_approval: bool,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
     ) -> Result<()> {
-        // capture pool key & bump first
-        let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+        check_deadline(deadline_unix_ts, max_slot)?;
+        process_swap_stack_to_quote(ctx, SwapAmount::ExactIn { amount_in, min_out }, use_governance_approval)
+    }
 
-        let pool = &mut ctx.accounts.pool;
-        let global = &ctx.accounts.global;
-        require!(!global.paused && !pool.paused, AmmError::ProtocolPaused);
-        require!(!pool.locked, AmmError::Reentrancy);
-        pool.locked = true;
+    /// Swap: stack -> quote for exactly `amount_out` quote, spending at most `max_in` stack
+    pub fn swap_stack_to_quote_exact_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, SwapStackToQuote<'info>>,
+        amount_out: u64,
+        max_in: u64,
+        use_governance_approval: bool,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        process_swap_stack_to_quote(ctx, SwapAmount::ExactOut { amount_out, max_in }, use_governance_approval)
+    }
+
+    /// Swap: quote -> stack (uses same fee helper & governance nonce check)
+    pub fn swap_quote_to_stack<'info>(
+        ctx: Context<'_, '_, '_, 'info, SwapQuoteToStack<'info>>,
+        amount_in: u64,
+        min_out: u64,
+        use_governance_approval: bool,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        process_swap_quote_to_stack(ctx, SwapAmount::ExactIn { amount_in, min_out }, use_governance_approval)
+    }
+
+    /// Swap: quote -> stack for exactly `amount_out` stack, spending at most `max_in` quote
+    pub fn swap_quote_to_stack_exact_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, SwapQuoteToStack<'info>>,
+        amount_out: u64,
+        max_in: u64,
+        use_governance_approval: bool,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        process_swap_quote_to_stack(ctx, SwapAmount::ExactOut { amount_out, max_in }, use_governance_approval)
+    }
+
+    /// Mint stack at NAV: `quote_in` goes into the stack's backing reserve and stack is minted at
+    /// `reserve / backed_supply` (or `backing.initial_nav_wad` while nothing is backed), less `backing.fee_bps`
+    pub fn mint_stack_via_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, MintStackViaPool<'info>>,
+        quote_in: u64,
+        min_stack_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        process_mint_stack_via_pool(ctx, SwapAmount::ExactIn { amount_in: quote_in, min_out: min_stack_out })
+    }
+
+    /// Mint exactly `stack_out` stack at NAV, spending at most `max_quote_in` quote
+    pub fn mint_stack_via_pool_exact_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, MintStackViaPool<'info>>,
+        stack_out: u64,
+        max_quote_in: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        process_mint_stack_via_pool(ctx, SwapAmount::ExactOut { amount_out: stack_out, max_in: max_quote_in })
+    }
 
-        // Burn stack from user (we expect user transferred into their own account)
-        token::burn(
+    /// Redeem stack at NAV: burn `stack_in` and pay its share of the stack's backing reserve
+    /// (`reserve · stack_in / backed_supply`, less `backing.fee_bps` which stays in the reserve)
+    pub fn redeem_stack_via_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, RedeemStackViaPool<'info>>,
+        stack_in: u64,
+        min_quote_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        require!(!ctx.accounts.global.is_paused(PAUSE_REDEEM), AmmError::ProtocolPaused);
+        // only the flag: redeems pay from the backing reserve, so a launch holding the pool paused doesn't stop them
+        require!(ctx.accounts.pool.pause_flags & PAUSE_REDEEM == 0, AmmError::PoolPaused);
+
+        let stack_info_key = ctx.accounts.stack_info.key();
+        let mut backing = ctx.accounts.stack_info.backing.ok_or(AmmError::BackingNotInitialized)?;
+        require_keys_eq!(ctx.accounts.backing_vault.key(), backing.vault, AmmError::InvalidVaultOwner);
+        require_keys_eq!(ctx.accounts.quote_mint.key(), backing.quote_mint, AmmError::InvalidVaultMint);
+        require!(stack_in <= backing.backed_supply, AmmError::InsufficientBacking);
+
+        // per-stack redeem limits (per transaction and sliding window)
+        ctx.accounts.stack_info.record_redeem(stack_in, Clock::get()?.unix_timestamp)?;
+
+        // NAV share rounded down, fee rounded down; both remainders stay in the reserve
+        let gross_out = backing_redeem_quote(&backing, stack_in)?;
+        let fee = (gross_out as u128).checked_mul(backing.fee_bps as u128).ok_or(AmmError::MathOverflow)? / BPS_DENOM;
+        let quote_out = gross_out - fee as u64;
+        require!(amount_after_transfer_fee(&ctx.accounts.quote_mint, quote_out)? >= min_quote_out, AmmError::SlippageExceeded);
+        let backed_supply_before = backing.backed_supply;
+
+        token_interface::burn(
             CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.stack_token_program.to_account_info(),
                 Burn {
-                    mint: ctx.accounts.stack_mint.to_account_info().clone(),
-                    from: ctx.accounts.user_stack_account.to_account_info().clone(),
-                    authority: ctx.accounts.user.to_account_info().clone(),
+                    mint: ctx.accounts.stack_mint.to_account_info(),
+                    from: ctx.accounts.user_stack_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
                 },
             ),
             stack_in,
         )?;
-
-        // compute amounts using constant product
-        let reserve_stack_before = ctx.accounts.stack_vault.amount;
-        let reserve_quote_before = ctx.accounts.quote_vault.amount;
-        let amount_in = stack_in as u128;
-
-        // compute fees (on stack input)
-        let (_gross_fee, protocol_fee, creator_fee, net_in) =
-            compute_fees(amount_in, pool.fee_bps, ctx.accounts.global.protocol_fee_bps, ctx.accounts.stack_info.creator_fee_bps)?;
-
-        // normalize reserves
-        let reserve_stack_norm = normalize_amount_u128(reserve_stack_before, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
-        let reserve_quote_norm = normalize_amount_u128(reserve_quote_before, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
-
-        let amount_out_norm = get_amount_out(net_in, reserve_stack_norm, reserve_quote_norm)?;
-        let amount_out_native = denormalize_amount_u64(amount_out_norm, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
-        require!(amount_out_native >= min_quote_out, AmmError::SlippageExceeded);
-
-        // route fees: convert protocol & creator fee (denom: stack) to quote by simulation and transfer
-        let (protocol_fee_quote_norm, creator_fee_quote_norm) = route_fee_to_quote_vault(
+        transfer_from_vault(
             &ctx.accounts.token_program,
-            &ctx.accounts.quote_vault,
-            &ctx.accounts.protocol_fee_vault,
-            &ctx.accounts.creator_fee_vault,
-            &ctx.accounts.vault_authority,
-            pool_key,
-            pool_bump,
-            protocol_fee,
-            creator_fee,
-            reserve_stack_norm,
-            reserve_quote_norm,
-            ctx.accounts.quote_mint.decimals,
-            pool.decimal_normalize_to,
+            &ctx.accounts.backing_vault,
+            &ctx.accounts.user_quote_account,
+            &ctx.accounts.quote_mint,
+            &[],
+            &ctx.accounts.backing_authority,
+            stack_info_key,
+            backing.vault_authority_bump,
+            quote_out,
         )?;
 
-        // update pool creator claimable
-        if creator_fee_quote_norm > 0 {
-            pool.creator_claimable = pool.creator_claimable.checked_add(creator_fee_quote_norm).ok_or(AmmError::MathOverflow)?;
-            pool.creator_last_claim_ts = Clock::get()?.unix_timestamp;
-        }
-
-        // transfer quote_out to user
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.quote_vault.to_account_info().clone(),
-                    to: ctx.accounts.user_quote_account.to_account_info().clone(),
-                    authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-            amount_out_native,
-        )?;
+        backing.reserve = backing.reserve.checked_sub(quote_out).ok_or(AmmError::MathOverflow)?;
+        backing.backed_supply -= stack_in;
+        ctx.accounts.stack_info.backing = Some(backing);
 
         emit!(RedeemedStackViaPool {
-            pool: pool_key,
+            pool: ctx.accounts.pool.key(),
             user: ctx.accounts.user.key(),
             stack_in,
-            quote_out: amount_out_native,
+            quote_out,
+            backing_reserve: backing.reserve,
+            backed_supply: backing.backed_supply,
         });
 
-        // sweep dust if tiny leftover
-        let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
-        if ctx.accounts.quote_vault.amount <= dust_threshold {
-            let amt = ctx.accounts.quote_vault.amount;
-            if amt > 0 {
-                token::transfer(
-                    CpiContext::new(
-                        ctx.accounts.token_program.to_account_info(),
-                        Transfer {
-                            from: ctx.accounts.quote_vault.to_account_info().clone(),
-                            to: ctx.accounts.treasury_token_account.to_account_info().clone(),
-                            authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                        },
-                    )
-                    .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                    amt,
-                )?;
-            }
-        }
-
-        pool.locked = false;
+        request_rebalance_hook(
+            &mut ctx.accounts.stack_info,
+            &ctx.accounts.global,
+            &ctx.accounts.pool,
+            &mut ctx.accounts.stack_mint,
+            &mut ctx.accounts.stack_vault,
+            &mut ctx.accounts.quote_vault,
+            ctx.accounts.user.key(),
+            RebalanceTrade::RedeemViaPool,
+            stack_in,
+            quote_out,
+            backed_supply_before,
+        )?;
         Ok(())
     }
 
-    /// Claim creator fees (timelocked) — uses global-configurable timelock
-    pub fn claim_creator_fees(ctx: Context<ClaimCreatorFees>) -> Result<()> {
+    /// Claim creator fees (timelocked) — uses global-configurable timelock.
+    /// Creator fees accrue in the input token of each swap, so both sides are paid out of the trading vaults.
+    pub fn claim_creator_fees<'info>(ctx: Context<'_, '_, '_, 'info, ClaimCreatorFees<'info>>) -> Result<()> {
         // capture pool key & bump before mutable borrow
         let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+        let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
 
         let pool = &mut ctx.accounts.pool;
         let info = &ctx.accounts.stack_info;
         require!(ctx.accounts.creator.key() == info.creator, AmmError::Unauthorized);
+        require!(!ctx.accounts.global.is_paused(PAUSE_CREATOR_CLAIMS), AmmError::ProtocolPaused);
+        require!(!pool.is_paused(PAUSE_CREATOR_CLAIMS), AmmError::PoolPaused);
 
         let now = Clock::get()?.unix_timestamp;
-        require!(pool.creator_claimable > 0, AmmError::NoFees);
+        let amount_stack = pool.creator_fees_stack;
+        let amount_quote = pool.creator_fees_quote;
+        require!(amount_stack > 0 || amount_quote > 0, AmmError::NoFees);
 
         // read lock seconds from global (ClaimCreatorFees now includes global)
         let lock_secs = if ctx.accounts.global.creator_claim_lock_secs == 0 { FALLBACK_CREATOR_CLAIM_LOCK_SECS } else { ctx.accounts.global.creator_claim_lock_secs };
         require!(now >= pool.creator_last_claim_ts.checked_add(lock_secs).ok_or(AmmError::MathOverflow)?, AmmError::ClaimLocked);
 
-        // convert normalized claimable to native quote tokens
-        let claim_norm = pool.creator_claimable;
-        let amount_native = denormalize_amount_u64(claim_norm, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
-        if amount_native > 0 {
-            token::transfer(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.creator_fee_vault.to_account_info().clone(),
-                        to: ctx.accounts.creator_receiver.to_account_info().clone(),
-                        authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                    },
-                )
-                .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                amount_native,
+        if amount_stack > 0 {
+            transfer_from_vault(
+                &ctx.accounts.stack_token_program,
+                &ctx.accounts.stack_vault,
+                &ctx.accounts.creator_stack_receiver,
+                &ctx.accounts.stack_mint,
+                ctx.remaining_accounts,
+                &ctx.accounts.vault_authority,
+                pool_key,
+                vault_authority_bump,
+                amount_stack,
+            )?;
+        }
+        if amount_quote > 0 {
+            transfer_from_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.quote_vault,
+                &ctx.accounts.creator_quote_receiver,
+                &ctx.accounts.quote_mint,
+                &[],
+                &ctx.accounts.vault_authority,
+                pool_key,
+                vault_authority_bump,
+                amount_quote,
             )?;
         }
-        pool.creator_claimable = 0u128;
+        pool.creator_fees_stack = 0u64;
+        pool.creator_fees_quote = 0u64;
         emit!(CreatorClaimed {
             pool: pool_key,
             creator: ctx.accounts.creator.key(),
-            amount: amount_native,
+            amount_stack,
+            amount_quote,
         });
         Ok(())
     }
@@ -1021,25 +1795,93 @@ pub mod stackmint_amm {
         Ok(())
     }
 
-    /// Emergency withdraw (LPs can withdraw pro rata even if pool paused).
-    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>, lp_amount: u64) -> Result<()> {
-        // capture pool key & bump first
-        let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+    /// Pause individual operations protocol-wide (admin/pauser/governance); `flags` are added to the set
+    /// already paused. Unlike `emergency_pause` this leaves every other operation running.
+    pub fn pause_operations(ctx: Context<PauseResume>, flags: u8) -> Result<()> {
+        let g = &mut ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        g.check_pause_update(caller, flags, PAUSE_ALL)?;
+        g.pause_flags |= flags;
+        emit!(ProtocolPauseFlagsUpdated { pause_flags: g.pause_flags, by: caller });
+        Ok(())
+    }
 
-        // allow LP to burn and withdraw ignoring some checks, but still ensure math & non-negative
+    /// Resume protocol-wide operations paused with `pause_operations`
+    pub fn resume_operations(ctx: Context<PauseResume>, flags: u8) -> Result<()> {
+        let g = &mut ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        g.check_pause_update(caller, flags, PAUSE_ALL)?;
+        g.pause_flags &= !flags;
+        emit!(ProtocolPauseFlagsUpdated { pause_flags: g.pause_flags, by: caller });
+        Ok(())
+    }
+
+    /// Pause individual operations on one pool (admin/pauser/governance), e.g. to stop a single risky stack
+    /// without touching the rest of the protocol. `emergency_withdraw` stays open, so LPs can always exit.
+    pub fn pause_pool(ctx: Context<PausePool>, flags: u8) -> Result<()> {
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        g.check_pause_update(caller, flags, PAUSE_ALL)?;
         let pool = &mut ctx.accounts.pool;
-        require!(!pool.locked, AmmError::Reentrancy);
-        pool.locked = true;
+        pool.pause_flags |= flags;
+        emit!(PoolPauseFlagsUpdated { pool: pool.key(), pause_flags: pool.pause_flags, by: caller });
+        Ok(())
+    }
 
-        // Burn LP & compute share
-        token::burn(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Burn {
-                    mint: ctx.accounts.lp_mint.to_account_info().clone(),
-                    from: ctx.accounts.user_lp_account.to_account_info().clone(),
-                    authority: ctx.accounts.user.to_account_info().clone(),
+    /// Resume pool operations paused with `pause_pool`. A pool held paused by a running bonding-curve launch
+    /// (`pool.paused`) stays paused until the launch graduates.
+    pub fn resume_pool(ctx: Context<PausePool>, flags: u8) -> Result<()> {
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        g.check_pause_update(caller, flags, PAUSE_ALL)?;
+        let pool = &mut ctx.accounts.pool;
+        pool.pause_flags &= !flags;
+        emit!(PoolPauseFlagsUpdated { pool: pool.key(), pause_flags: pool.pause_flags, by: caller });
+        Ok(())
+    }
+
+    /// `pause_pool` for a concentrated-liquidity pool: stops swaps, deposits or creator claims on it (any
+    /// other flag is rejected). Decreasing and collecting positions are never paused.
+    pub fn pause_cl_pool(ctx: Context<PauseClPool>, flags: u8) -> Result<()> {
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        g.check_pause_update(caller, flags, PAUSE_CL_ALL)?;
+        let pool = &mut ctx.accounts.cl_pool;
+        pool.pause_flags |= flags;
+        emit!(PoolPauseFlagsUpdated { pool: pool.key(), pause_flags: pool.pause_flags, by: caller });
+        Ok(())
+    }
+
+    /// Resume concentrated-liquidity pool operations paused with `pause_cl_pool`
+    pub fn resume_cl_pool(ctx: Context<PauseClPool>, flags: u8) -> Result<()> {
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        g.check_pause_update(caller, flags, PAUSE_CL_ALL)?;
+        let pool = &mut ctx.accounts.cl_pool;
+        pool.pause_flags &= !flags;
+        emit!(PoolPauseFlagsUpdated { pool: pool.key(), pause_flags: pool.pause_flags, by: caller });
+        Ok(())
+    }
+
+    /// Emergency withdraw (LPs can withdraw pro rata even if pool paused).
+    pub fn emergency_withdraw<'info>(ctx: Context<'_, '_, '_, 'info, EmergencyWithdraw<'info>>, lp_amount: u64) -> Result<()> {
+        // capture pool key & bump first
+        let pool_key = ctx.accounts.pool.key();
+        let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
+
+        // allow LP to burn and withdraw ignoring some checks, but still ensure math & non-negative
+        let pool = &mut ctx.accounts.pool;
+        require!(!pool.locked, AmmError::Reentrancy);
+        pool.locked = true;
+
+        // Burn LP & compute share
+        token_interface::burn(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.lp_mint.to_account_info().clone(),
+                    from: ctx.accounts.user_lp_account.to_account_info().clone(),
+                    authority: ctx.accounts.user.to_account_info().clone(),
                 },
             ),
             lp_amount,
@@ -1048,35 +1890,36 @@ pub mod stackmint_amm {
         let total_lp = ctx.accounts.lp_mint.supply as u128;
         require!(total_lp > 0, AmmError::NoLiquidity);
 
-        let out_stack = (ctx.accounts.stack_vault.amount as u128)
+        // pro rata share of the trading reserves (accrued protocol/creator fees are not LP property)
+        let reserve_stack = vault_reserve(ctx.accounts.stack_vault.amount, pool.accrued_fees_stack()?)?;
+        let reserve_quote = vault_reserve(ctx.accounts.quote_vault.amount, pool.accrued_fees_quote()?)?;
+        let out_stack = (reserve_stack as u128)
             .checked_mul(lp_amount_u128).ok_or(AmmError::MathOverflow)?
             .checked_div(total_lp).ok_or(AmmError::MathOverflow)?;
-        let out_quote = (ctx.accounts.quote_vault.amount as u128)
+        let out_quote = (reserve_quote as u128)
             .checked_mul(lp_amount_u128).ok_or(AmmError::MathOverflow)?
             .checked_div(total_lp).ok_or(AmmError::MathOverflow)?;
 
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.stack_vault.to_account_info().clone(),
-                    to: ctx.accounts.user_stack_account.to_account_info().clone(),
-                    authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
+        transfer_from_vault(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.stack_vault,
+            &ctx.accounts.user_stack_account,
+            &ctx.accounts.stack_mint,
+            ctx.remaining_accounts,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
             out_stack.try_into().map_err(|_| AmmError::MathOverflow)?,
         )?;
-        token::transfer(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.quote_vault.to_account_info().clone(),
-                    to: ctx.accounts.user_quote_account.to_account_info().clone(),
-                    authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.quote_vault,
+            &ctx.accounts.user_quote_account,
+            &ctx.accounts.quote_mint,
+            &[],
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
             out_quote.try_into().map_err(|_| AmmError::MathOverflow)?,
         )?;
 
@@ -1092,10 +1935,10 @@ pub mod stackmint_amm {
     }
 
     /// Admin: withdraw accumulated protocol fees to admin receiver
-    pub fn withdraw_protocol_fees(ctx: Context<WithdrawProtocolFees>, use_governance_approval: bool) -> Result<()> {
+    pub fn withdraw_protocol_fees<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawProtocolFees<'info>>, use_governance_approval: bool) -> Result<()> {
         // capture pool key & bump first
         let pool_key = ctx.accounts.pool.key();
-        let pool_bump = ctx.accounts.pool.bump;
+        let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
 
         let pool = &mut ctx.accounts.pool;
         let g = &ctx.accounts.global;
@@ -1103,47 +1946,266 @@ pub mod stackmint_amm {
         require!(caller == g.admin || caller == g.fee_manager || caller == g.governance, AmmError::Unauthorized);
 
         if use_governance_approval {
-            validate_governance_approval(&ctx.accounts.governance_approval, pool_key, pool)?;
+            let payload = (ctx.accounts.admin_stack_receiver.key(), ctx.accounts.admin_quote_receiver.key());
+            consume_governance_approval(
+                &ctx.accounts.governance_approval,
+                &ctx.accounts.approval_rent_receiver,
+                pool_key,
+                GovernanceAction::WithdrawProtocolFees,
+                governance_payload_hash(&payload)?,
+                pool,
+            )?;
         }
 
-        // move all tokens in protocol_fee_vault to admin_receiver
-        let vault_balance = ctx.accounts.protocol_fee_vault.amount;
-        if vault_balance > 0 {
-            token::transfer(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.protocol_fee_vault.to_account_info().clone(),
-                        to: ctx.accounts.admin_receiver.to_account_info().clone(),
-                        authority: ctx.accounts.vault_authority.to_account_info().clone(),
-                    },
-                )
-                .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-                vault_balance,
+        // protocol fees accrue in the input token of each swap and sit in the trading vaults until withdrawn
+        let amount_stack = pool.protocol_fees_stack;
+        let amount_quote = pool.protocol_fees_quote;
+        if amount_stack > 0 {
+            transfer_from_vault(
+                &ctx.accounts.stack_token_program,
+                &ctx.accounts.stack_vault,
+                &ctx.accounts.admin_stack_receiver,
+                &ctx.accounts.stack_mint,
+                ctx.remaining_accounts,
+                &ctx.accounts.vault_authority,
+                pool_key,
+                vault_authority_bump,
+                amount_stack,
+            )?;
+        }
+        if amount_quote > 0 {
+            transfer_from_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.quote_vault,
+                &ctx.accounts.admin_quote_receiver,
+                &ctx.accounts.quote_mint,
+                &[],
+                &ctx.accounts.vault_authority,
+                pool_key,
+                vault_authority_bump,
+                amount_quote,
             )?;
         }
-        emit!(ProtocolFeesWithdrawn { pool: pool_key, to: ctx.accounts.admin_receiver.key(), amount: vault_balance });
+        pool.protocol_fees_stack = 0u64;
+        pool.protocol_fees_quote = 0u64;
+        emit!(ProtocolFeesWithdrawn {
+            pool: pool_key,
+            to_stack: ctx.accounts.admin_stack_receiver.key(),
+            to_quote: ctx.accounts.admin_quote_receiver.key(),
+            amount_stack,
+            amount_quote,
+        });
         Ok(())
     }
 
-    /// View helper (read-only): compute mid-price (quote per stack) from reserves
-    pub fn view_mid_price(ctx: Context<ViewMidPrice>) -> Result<()> {
+    /// View helper (read-only): compute mid-price (quote per stack) from reserves; also returned via return data
+    pub fn view_mid_price(ctx: Context<ViewMidPrice>) -> Result<u128> {
         let pool_key = ctx.accounts.pool.key();
         let pool = &ctx.accounts.pool;
 
-        let stack = ctx.accounts.stack_vault.amount as u128;
-        let quote = ctx.accounts.quote_vault.amount as u128;
+        let stack = vault_reserve(ctx.accounts.stack_vault.amount, pool.accrued_fees_stack()?)? as u128;
+        let quote = vault_reserve(ctx.accounts.quote_vault.amount, pool.accrued_fees_quote()?)? as u128;
         require!(stack > 0 && quote > 0, AmmError::NoLiquidity);
-        // price = quote / stack scaled to internal precision (10^decimal)
+        // price = (quote / w_quote) / (stack / w_stack) scaled to internal precision (10^decimal);
+        // the weights are equal unless the pool is weighted
+        let (stack_weight, quote_weight) = pool.weights_bps(true);
         let price_x = quote
             .checked_mul(10u128.pow(pool.decimal_normalize_to as u32)).ok_or(AmmError::MathOverflow)?
-            .checked_div(stack).ok_or(AmmError::MathOverflow)?;
+            .checked_mul(stack_weight).ok_or(AmmError::MathOverflow)?
+            .checked_div(stack.checked_mul(quote_weight).ok_or(AmmError::MathOverflow)?).ok_or(AmmError::MathOverflow)?;
         emit!(MidPrice { pool: pool_key, price_x });
-        Ok(())
+        Ok(price_x)
+    }
+
+    /// Quote (read-only): output of an exact-input swap, with the fee split; returned via return data
+    pub fn quote_exact_in(ctx: Context<QuotePool>, amount_in: u64, side: SwapDirection) -> Result<SwapQuote> {
+        let q = &ctx.accounts;
+        let target_decimals = q.pool.decimal_normalize_to;
+        let (in_decimals, out_decimals, reserve_in, reserve_out) = q.oriented(&side)?;
+        let (mint_in, mint_out) = q.mints(&side);
+        let stack_in = side == SwapDirection::StackToQuote;
+
+        let reserve_in_norm = normalize_amount_u128(reserve_in, in_decimals, target_decimals)?;
+        let reserve_out_norm = normalize_amount_u128(reserve_out, out_decimals, target_decimals)?;
+
+        // same native-unit fee split as the swap instructions, on what reaches the vault
+        let received_in = amount_after_transfer_fee(mint_in, amount_in)?;
+        let (gross_fee, protocol_fee, creator_fee, net_in_native) =
+            compute_fees(received_in as u128, q.pool.fee_bps, q.global.protocol_fee_bps, q.stack_info.creator_fee_bps)?;
+        let net_in = normalize_amount_u128(net_in_native as u64, in_decimals, target_decimals)?;
+        let amount_out_norm = curve_amount_out(&q.pool, stack_in, net_in, reserve_in_norm, reserve_out_norm)?;
+        let curve_out = denormalize_amount_u64(amount_out_norm, out_decimals, target_decimals)?;
+
+        Ok(SwapQuote {
+            side,
+            amount_in,
+            amount_out: amount_after_transfer_fee(mint_out, curve_out)?,
+            fee: gross_fee as u64,
+            protocol_fee: protocol_fee as u64,
+            creator_fee: creator_fee as u64,
+            price_impact_bps: price_impact_bps(&q.pool, stack_in, net_in, amount_out_norm, reserve_in_norm, reserve_out_norm)?,
+        })
+    }
+
+    /// Quote (read-only): input required for an exact-output swap (rounded up, in the pool's favor)
+    pub fn quote_exact_out(ctx: Context<QuotePool>, amount_out: u64, side: SwapDirection) -> Result<SwapQuote> {
+        let q = &ctx.accounts;
+        let target_decimals = q.pool.decimal_normalize_to;
+        let (in_decimals, out_decimals, reserve_in, reserve_out) = q.oriented(&side)?;
+        let (mint_in, mint_out) = q.mints(&side);
+        let stack_in = side == SwapDirection::StackToQuote;
+
+        // the vault sends enough to deliver `amount_out` after any Token-2022 transfer fee
+        let sent_out = transfer_amount_for(mint_out, amount_out)?;
+        let amount_out_norm = normalize_amount_u128_ceil(sent_out, out_decimals, target_decimals)?;
+        let reserve_in_norm = normalize_amount_u128(reserve_in, in_decimals, target_decimals)?;
+        let reserve_out_norm = normalize_amount_u128(reserve_out, out_decimals, target_decimals)?;
+
+        let net_in = curve_amount_in(&q.pool, stack_in, amount_out_norm, reserve_in_norm, reserve_out_norm)?;
+        let gross_in_norm = gross_up_for_fee(net_in, q.pool.fee_bps)?;
+        let vault_in = denormalize_amount_u64_ceil(gross_in_norm, in_decimals, target_decimals)?;
+
+        // fee split of what reaches the vault; the trader also covers the input transfer fee
+        let (gross_fee, protocol_fee, creator_fee, _net_in) =
+            compute_fees(vault_in as u128, q.pool.fee_bps, q.global.protocol_fee_bps, q.stack_info.creator_fee_bps)?;
+        let amount_in = transfer_amount_for(mint_in, vault_in)?;
+
+        Ok(SwapQuote {
+            side,
+            amount_in,
+            amount_out,
+            fee: gross_fee as u64,
+            protocol_fee: protocol_fee as u64,
+            creator_fee: creator_fee as u64,
+            price_impact_bps: price_impact_bps(&q.pool, stack_in, net_in, amount_out_norm, reserve_in_norm, reserve_out_norm)?,
+        })
+    }
+
+    /// Quote (read-only): LP minted by `provide_liquidity` for the given maximum deposit, and the amounts it pulls
+    pub fn quote_add_liquidity(ctx: Context<QuotePool>, amount_stack: u64, amount_quote: u64) -> Result<LiquidityQuote> {
+        let q = &ctx.accounts;
+        let target_decimals = q.pool.decimal_normalize_to;
+        let total_lp = q.lp_mint.supply as u128;
+        let (reserve_stack, reserve_quote) = q.reserves()?;
+
+        let (amount_stack, amount_quote) = if total_lp == 0 {
+            (amount_stack, amount_quote)
+        } else {
+            optimal_deposit(amount_stack, amount_quote, reserve_stack, reserve_quote)?
+        };
+        let lp_out_u128 = lp_for_deposit(
+            &q.pool,
+            normalize_amount_u128(amount_stack, q.stack_mint.decimals, target_decimals)?,
+            normalize_amount_u128(amount_quote, q.quote_mint.decimals, target_decimals)?,
+            normalize_amount_u128(reserve_stack, q.stack_mint.decimals, target_decimals)?,
+            normalize_amount_u128(reserve_quote, q.quote_mint.decimals, target_decimals)?,
+            total_lp,
+        )?;
+        let locked_lp = if total_lp == 0 { minimum_liquidity(&q.global) } else { 0 };
+        require!(lp_out_u128 > locked_lp as u128, AmmError::ZeroLpMint);
+
+        Ok(LiquidityQuote {
+            lp_amount: (lp_out_u128 - locked_lp as u128).try_into().map_err(|_| AmmError::MathOverflow)?,
+            amount_stack,
+            amount_quote,
+        })
+    }
+
+    /// Quote (read-only): underlying returned by `remove_liquidity` for `lp_amount`
+    pub fn quote_remove_liquidity(ctx: Context<QuotePool>, lp_amount: u64) -> Result<LiquidityQuote> {
+        let q = &ctx.accounts;
+        let target_decimals = q.pool.decimal_normalize_to;
+        let total_lp = q.lp_mint.supply as u128;
+        require!(total_lp > 0, AmmError::NoLiquidity);
+
+        let (reserve_stack, reserve_quote) = q.reserves()?;
+        let reserve_stack_norm = normalize_amount_u128(reserve_stack, q.stack_mint.decimals, target_decimals)?;
+        let reserve_quote_norm = normalize_amount_u128(reserve_quote, q.quote_mint.decimals, target_decimals)?;
+        let out_stack_norm = checked_mul_div(reserve_stack_norm, lp_amount as u128, total_lp)?;
+        let out_quote_norm = checked_mul_div(reserve_quote_norm, lp_amount as u128, total_lp)?;
+
+        Ok(LiquidityQuote {
+            lp_amount,
+            amount_stack: denormalize_amount_u64(out_stack_norm, q.stack_mint.decimals, target_decimals)?,
+            amount_quote: denormalize_amount_u64(out_quote_norm, q.quote_mint.decimals, target_decimals)?,
+        })
+    }
+
+    /// View (read-only): snapshot of reserves, fees and price state for front ends & composing programs
+    pub fn get_pool_state(ctx: Context<QuotePool>) -> Result<PoolStateView> {
+        let q = &ctx.accounts;
+        let pool = &q.pool;
+        let (reserve_stack, reserve_quote) = q.reserves()?;
+        let reserve_stack_norm = normalize_amount_u128(reserve_stack, q.stack_mint.decimals, pool.decimal_normalize_to)?;
+        let reserve_quote_norm = normalize_amount_u128(reserve_quote, q.quote_mint.decimals, pool.decimal_normalize_to)?;
+        let mid_price_x = if reserve_stack_norm > 0 && reserve_quote_norm > 0 {
+            spot_price_x(pool, true, reserve_stack_norm, reserve_quote_norm, Clock::get()?.unix_timestamp)?
+        } else {
+            0
+        };
+
+        Ok(PoolStateView {
+            reserve_stack,
+            reserve_quote,
+            lp_supply: q.lp_mint.supply,
+            fee_bps: pool.fee_bps,
+            protocol_fee_bps: q.global.protocol_fee_bps,
+            creator_fee_bps: q.stack_info.creator_fee_bps,
+            mid_price_x,
+            paused: q.global.paused || pool.paused,
+            pause_flags: q.global.pause_flags | pool.pause_flags,
+            oracle_source: pool.oracle_source,
+            price_cumulative_quote_per_stack: pool.price_cumulative_quote_per_stack,
+            price_cumulative_stack_per_quote: pool.price_cumulative_stack_per_quote,
+            last_price_update_ts: pool.last_price_update_ts,
+            protocol_fees_stack: pool.protocol_fees_stack,
+            protocol_fees_quote: pool.protocol_fees_quote,
+            creator_fees_stack: pool.creator_fees_stack,
+            creator_fees_quote: pool.creator_fees_quote,
+            curve_type: pool.curve_type,
+            amp: current_amp(pool, Clock::get()?.unix_timestamp)?,
+            stack_weight_bps: pool.stack_weight_bps,
+        })
+    }
+
+    /// View helper (read-only): time-weighted average price over the last `window_secs`, both directions.
+    /// Uses the observation ring buffer plus the pool's live accumulators, so the window may end "now".
+    pub fn get_twap(ctx: Context<GetTwap>, window_secs: u32) -> Result<TwapPrice> {
+        let pool_key = ctx.accounts.pool.key();
+        let pool = &ctx.accounts.pool;
+        require!(window_secs > 0, AmmError::InvalidTwapWindow);
+
+        let reserve_stack = vault_reserve(ctx.accounts.stack_vault.amount, pool.accrued_fees_stack()?)?;
+        let reserve_quote = vault_reserve(ctx.accounts.quote_vault.amount, pool.accrued_fees_quote()?)?;
+        let reserve_stack_norm = normalize_amount_u128(reserve_stack, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
+        let reserve_quote_norm = normalize_amount_u128(reserve_quote, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
+
+        // counterfactual accumulators at `now` (current reserves have been in effect since the last update)
+        let now = Clock::get()?.unix_timestamp;
+        let current = current_cumulative_prices(pool, reserve_stack_norm, reserve_quote_norm, now)?;
+
+        let target_ts = now.checked_sub(window_secs as i64).ok_or(AmmError::MathOverflow)?;
+        let past = cumulative_prices_at(&ctx.accounts.observations, current, target_ts)?;
+
+        let quote_per_stack_x = current.cumulative_quote_per_stack
+            .wrapping_sub(past.cumulative_quote_per_stack)
+            .checked_div(window_secs as u128).ok_or(AmmError::MathOverflow)?;
+        let stack_per_quote_x = current.cumulative_stack_per_quote
+            .wrapping_sub(past.cumulative_stack_per_quote)
+            .checked_div(window_secs as u128).ok_or(AmmError::MathOverflow)?;
+
+        emit!(TwapRead { pool: pool_key, window_secs, quote_per_stack_x, stack_per_quote_x });
+        Ok(TwapPrice { window_secs, quote_per_stack_x, stack_per_quote_x })
     }
 
-    /// Set pool parameters (admin/fee_manager/governance) with optional governance approval & safety checks
-    pub fn set_pool_params(ctx: Context<SetParams>, new_fee_bps: Option<u16>, new_k: Option<u128>, max_price_deviation_bps: Option<u16>, use_governance_approval: bool) -> Result<()> {
+    /// Set a pool's oracle limits (admin/governance) with optional governance approval & safety checks.
+    /// Fee, k and price deviation cap go through `queue_pool_params` instead.
+    pub fn set_pool_params(
+        ctx: Context<SetParams>,
+        oracle_max_staleness_secs: Option<i64>,
+        oracle_max_conf_bps: Option<u16>,
+        use_governance_approval: bool,
+    ) -> Result<()> {
         // capture pool key & bump first
         let pool_key = ctx.accounts.pool.key();
         let pool = &mut ctx.accounts.pool;
@@ -1152,675 +2214,4795 @@ pub mod stackmint_amm {
         require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
 
         if use_governance_approval {
-            validate_governance_approval(&ctx.accounts.governance_approval, pool_key, pool)?;
+            consume_governance_approval(
+                &ctx.accounts.governance_approval,
+                &ctx.accounts.approval_rent_receiver,
+                pool_key,
+                GovernanceAction::SetPoolParams,
+                governance_payload_hash(&(oracle_max_staleness_secs, oracle_max_conf_bps))?,
+                pool,
+            )?;
+        }
+
+        if let Some(secs) = oracle_max_staleness_secs {
+            require!(secs > 0, AmmError::InvalidOracle);
+            pool.oracle_max_staleness_secs = secs;
+        }
+        if let Some(c) = oracle_max_conf_bps {
+            require!(c as u128 <= BPS_DENOM, AmmError::InvalidOracle);
+            pool.oracle_max_conf_bps = c;
+        }
+
+        emit!(PoolParamsUpdated { pool: pool_key, by: caller });
+        Ok(())
+    }
+
+    /// Queue a change of fee, k and/or price deviation cap (admin/governance, optionally under a governance
+    /// approval). It applies via `execute_pool_params` once `Global.params_timelock_secs` have passed; only one
+    /// change per pool can be pending, and governance or the pauser can veto it with `cancel_pool_params`.
+    pub fn queue_pool_params(
+        ctx: Context<QueuePoolParams>,
+        new_fee_bps: Option<u16>,
+        new_k: Option<u128>,
+        max_price_deviation_bps: Option<u16>,
+        use_governance_approval: bool,
+    ) -> Result<()> {
+        let pool_key = ctx.accounts.pool.key();
+        let pool = &mut ctx.accounts.pool;
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
+
+        if use_governance_approval {
+            consume_governance_approval(
+                &ctx.accounts.governance_approval,
+                &ctx.accounts.approval_rent_receiver,
+                pool_key,
+                GovernanceAction::QueuePoolParams,
+                governance_payload_hash(&(new_fee_bps, new_k, max_price_deviation_bps))?,
+                pool,
+            )?;
         }
 
         if let Some(f) = new_fee_bps {
-            // enforce global max fee cap
+            // enforce global max fee cap (checked again on execution)
+            let max_fee = if g.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { g.max_fee_bps };
+            require!(f <= max_fee, AmmError::InvalidFee);
+        }
+        if let Some(m) = max_price_deviation_bps {
+            require!(m as u128 <= BPS_DENOM, AmmError::InvalidFee);
+        }
+
+        let delay = if g.params_timelock_secs == 0 { FALLBACK_PARAMS_TIMELOCK_SECS } else { g.params_timelock_secs };
+        let eta = Clock::get()?.unix_timestamp.checked_add(delay).ok_or(AmmError::MathOverflow)?;
+        let (_, bump) = Pubkey::find_program_address(&[b"pending_pool_params", pool_key.as_ref()], &crate::ID);
+        let pending = &mut ctx.accounts.pending_params;
+        pending.pool = pool_key;
+        pending.new_fee_bps = new_fee_bps;
+        pending.new_k = new_k;
+        pending.max_price_deviation_bps = max_price_deviation_bps;
+        pending.eta = eta;
+        pending.queued_by = caller;
+        pending.bump = bump;
+
+        emit!(PoolParamsQueued { pool: pool_key, new_fee_bps, new_k, max_price_deviation_bps, eta, by: caller });
+        Ok(())
+    }
+
+    /// Apply a queued pool-params change once its ETA has passed (anyone); rent goes back to whoever queued it
+    pub fn execute_pool_params(ctx: Context<ExecutePoolParams>) -> Result<()> {
+        let pool_key = ctx.accounts.pool.key();
+        let pending = &ctx.accounts.pending_params;
+        require!(Clock::get()?.unix_timestamp >= pending.eta, AmmError::PoolParamsTimelocked);
+
+        let pool = &mut ctx.accounts.pool;
+        let g = &ctx.accounts.global;
+        if let Some(f) = pending.new_fee_bps {
+            // the cap may have been lowered while the change was queued
             let max_fee = if g.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { g.max_fee_bps };
             require!(f <= max_fee, AmmError::InvalidFee);
             pool.fee_bps = f;
         }
-        if let Some(kv) = new_k {
+        if let Some(kv) = pending.new_k {
             pool.k = kv;
         }
-        if let Some(m) = max_price_deviation_bps {
+        if let Some(m) = pending.max_price_deviation_bps {
             pool.max_price_deviation_bps = m;
         }
 
-        emit!(PoolParamsUpdated { pool: pool_key, by: caller });
+        emit!(PoolParamsExecuted {
+            pool: pool_key,
+            new_fee_bps: pending.new_fee_bps,
+            new_k: pending.new_k,
+            max_price_deviation_bps: pending.max_price_deviation_bps,
+            by: ctx.accounts.caller.key(),
+        });
         Ok(())
     }
-}
 
-/* ---------------------------------------------------
-   ACCOUNTS, CONTEXTS, HELPERS, EVENTS & ERRORS
-   --------------------------------------------------- */
+    /// Veto a queued pool-params change (governance or pauser); rent goes back to whoever queued it
+    pub fn cancel_pool_params(ctx: Context<CancelPoolParams>) -> Result<()> {
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.caller.key();
+        require!(caller == g.governance || caller == g.pauser, AmmError::Unauthorized);
+        emit!(PoolParamsCancelled { pool: ctx.accounts.pending_params.pool, by: caller });
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct InitGlobal<'info> {
-    #[account(init, payer = admin, space = 8 + Global::LEN, seeds = [b"global"], bump)]
-    pub global: Account<'info, Global>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    /// Treasury: ATA where dust and miscellaneous small balances are forwarded (denominated in quote mint for pools)
-    #[account(mut)]
-    pub treasury: Account<'info, TokenAccount>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    /// Ramp a StableSwap pool's amplification linearly to `target_amp`, reached at `ramp_end_ts`
+    /// (admin/governance). A ramp lasts at least MIN_AMP_RAMP_SECS and moves amp by at most MAX_AMP_CHANGE x,
+    /// so the curve can't be yanked under LPs; it starts from the current (possibly mid-ramp) amp.
+    pub fn ramp_amp(ctx: Context<SetParams>, target_amp: u64, ramp_end_ts: i64, use_governance_approval: bool) -> Result<()> {
+        let pool_key = ctx.accounts.pool.key();
+        let pool = &mut ctx.accounts.pool;
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
 
-#[account]
-pub struct Global {
-    pub version: u8,
-    pub admin: Pubkey,
-    pub pauser: Pubkey,
-    pub fee_manager: Pubkey,
-    pub governance: Pubkey,
-    pub protocol_fee_bps: u16,
-    pub paused: bool,
-    pub treasury: Pubkey,
-    // configurable safety params
-    pub max_fee_bps: u16,         // maximum allowed fee for pools
-    pub dust_threshold: u64,      // tiny balance sweep threshold
-    pub creator_claim_lock_secs: i64, // timelock
-}
-impl Global { const LEN: usize = 1 + 32*4 + 2 + 1 + 32 + 2 + 8 + 8; }
+        if use_governance_approval {
+            consume_governance_approval(
+                &ctx.accounts.governance_approval,
+                &ctx.accounts.approval_rent_receiver,
+                pool_key,
+                GovernanceAction::RampAmp,
+                governance_payload_hash(&(target_amp, ramp_end_ts))?,
+                pool,
+            )?;
+        }
 
-#[derive(Accounts)]
-pub struct RegisterStack<'info> {
-    #[account(mut)]
-    pub creator: Signer<'info>,
-    #[account(mut)]
-    pub stack_mint: Account<'info, Mint>,
-    /// Mint authority PDA for the stack mint
-    #[account(seeds = [b"stack_mint_auth", stack_mint.key().as_ref()], bump)]
-    pub stack_mint_auth: UncheckedAccount<'info>,
-    #[account(init, payer = creator, space = 8 + StackInfo::LEN, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
-    pub stack_info: Account<'info, StackInfo>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        require!(pool.curve_type == CurveType::StableSwap, AmmError::InvalidAmp);
+        require!((MIN_AMP..=MAX_AMP).contains(&target_amp), AmmError::InvalidAmp);
+        let now = Clock::get()?.unix_timestamp;
+        require!(ramp_end_ts >= now.checked_add(MIN_AMP_RAMP_SECS).ok_or(AmmError::MathOverflow)?, AmmError::InvalidAmp);
+        let amp = current_amp(pool, now)?;
+        require!(
+            target_amp <= amp.saturating_mul(MAX_AMP_CHANGE) && amp <= target_amp.saturating_mul(MAX_AMP_CHANGE),
+            AmmError::InvalidAmp
+        );
 
-#[account]
-pub struct StackInfo {
-    pub version: u8,
-    pub creator: Pubkey,
-    pub stack_mint: Pubkey,
-    pub creator_fee_bps: u16,
-    pub rebalance_hook: Option<Pubkey>,
-    pub bump: u8,
-}
-impl StackInfo { const LEN: usize = 1 + 32 + 32 + 2 + (1+32) + 1; }
+        pool.amp_initial = amp;
+        pool.amp_target = target_amp;
+        pool.amp_ramp_start_ts = now;
+        pool.amp_ramp_end_ts = ramp_end_ts;
+        emit!(AmpRampStarted { pool: pool_key, amp_initial: amp, amp_target: target_amp, start_ts: now, end_ts: ramp_end_ts });
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct CreatePool<'info> {
-    #[account(mut)]
-    pub creator: Signer<'info>,
+    /// Freeze a StableSwap pool's amplification at its current value (admin/governance)
+    pub fn stop_ramp_amp(ctx: Context<SetParams>, use_governance_approval: bool) -> Result<()> {
+        let pool_key = ctx.accounts.pool.key();
+        let pool = &mut ctx.accounts.pool;
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.admin.key();
+        require!(caller == g.admin || caller == g.governance, AmmError::Unauthorized);
 
-    #[account(mut)]
-    pub stack_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub quote_mint: Account<'info, Mint>,
+        if use_governance_approval {
+            consume_governance_approval(
+                &ctx.accounts.governance_approval,
+                &ctx.accounts.approval_rent_receiver,
+                pool_key,
+                GovernanceAction::StopRampAmp,
+                governance_payload_hash(&())?,
+                pool,
+            )?;
+        }
 
-    /// LP mint
-    #[account(mut)]
-    pub lp_mint: Account<'info, Mint>,
+        require!(pool.curve_type == CurveType::StableSwap, AmmError::InvalidAmp);
+        let now = Clock::get()?.unix_timestamp;
+        let amp = current_amp(pool, now)?;
+        pool.amp_initial = amp;
+        pool.amp_target = amp;
+        pool.amp_ramp_start_ts = now;
+        pool.amp_ramp_end_ts = now;
+        emit!(AmpRampStopped { pool: pool_key, amp });
+        Ok(())
+    }
 
-    #[account(init, payer = creator, space = 8 + Pool::LEN, seeds=[b"pool", stack_mint.key().as_ref(), quote_mint.key().as_ref()], bump)]
-    pub pool: Account<'info, Pool>,
+    /// Create a program-owned test price feed (local/devnet testing only; pools opt in via `OracleSource::TestFeed`).
+    /// Fails unless the program is built with the `test-feed` feature.
+    pub fn init_test_price_feed(ctx: Context<InitTestPriceFeed>, price: i64, expo: i32, conf: u64) -> Result<()> {
+        require!(cfg!(feature = "test-feed"), AmmError::TestFeedDisabled);
+        require!(price > 0, AmmError::InvalidOraclePrice);
+        let feed = &mut ctx.accounts.price_feed;
+        feed.authority = ctx.accounts.authority.key();
+        feed.price = price;
+        feed.expo = expo;
+        feed.conf = conf;
+        feed.publish_ts = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
+    /// Push a new price into a test price feed (feed authority only); publish time is taken from the Clock
+    pub fn set_test_price_feed(ctx: Context<SetTestPriceFeed>, price: i64, conf: u64) -> Result<()> {
+        require!(cfg!(feature = "test-feed"), AmmError::TestFeedDisabled);
+        require!(price > 0, AmmError::InvalidOraclePrice);
+        let feed = &mut ctx.accounts.price_feed;
+        feed.price = price;
+        feed.conf = conf;
+        feed.publish_ts = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
 
-    /// Fee vaults (ATAs owned by vault_authority)
-    #[account(mut)]
-    pub protocol_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub creator_fee_vault: Account<'info, TokenAccount>,
+    /* -----------------------
+       GOVERNANCE
+       M-of-N multisig over `GovernanceConfig.signers`. A proposal names the account it targets (a pool, or the
+       config itself), the instruction it authorizes and the sha256 of that instruction's borsh-encoded payload
+       (see `GovernanceAction`); once `threshold` current signers have approved, `execute_proposal` writes the
+       `GovernanceApproval` PDA that `use_governance_approval` instructions consume (and close).
+       ----------------------- */
+
+    /// Create the governance multisig (admin only, once)
+    pub fn init_governance(ctx: Context<InitGovernance>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.global.admin, AmmError::Unauthorized);
+        validate_governance_signers(&signers, threshold)?;
+
+        let config = &mut ctx.accounts.governance_config;
+        config.signers = signers.clone();
+        config.threshold = threshold;
+        config.proposal_count = 0;
+        config.last_governance_nonce = 0;
+        emit!(GovernanceConfigUpdated { signers, threshold });
+        Ok(())
+    }
 
-    /// vault authority PDA
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+    /// Replace the multisig's signers and threshold; authorized only by an approval the current multisig
+    /// executed with the config as its target (closed here)
+    pub fn set_governance_config(ctx: Context<SetGovernanceConfig>, signers: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        validate_governance_signers(&signers, threshold)?;
+        let config_key = ctx.accounts.governance_config.key();
+        let payload_hash = governance_payload_hash(&(signers.clone(), threshold))?;
+        let config = &mut ctx.accounts.governance_config;
+        check_governance_approval(
+            &ctx.accounts.governance_approval,
+            config_key,
+            GovernanceAction::SetGovernanceConfig,
+            payload_hash,
+            &mut config.last_governance_nonce,
+        )?;
 
-    /// optional oracle account (unchecked; integration with Pyth left as NOTE)
-    #[account(mut)]
-    pub oracle: UncheckedAccount<'info>,
+        config.signers = signers.clone();
+        config.threshold = threshold;
+        emit!(GovernanceConfigUpdated { signers, threshold });
+        Ok(())
+    }
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
+    /// Propose `action` on `target` with the given payload hash (multisig signers only). Both are carried onto
+    /// the approval; `expiry_ts` bounds both execution and the approval's validity.
+    pub fn create_proposal(
+        ctx: Context<CreateProposal>,
+        target: Pubkey,
+        action: GovernanceAction,
+        payload_hash: [u8; 32],
+        expiry_ts: i64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.governance_config;
+        let proposer = ctx.accounts.proposer.key();
+        require!(config.signers.contains(&proposer), AmmError::NotGovernanceSigner);
+        require!(expiry_ts > Clock::get()?.unix_timestamp, AmmError::GovernanceApprovalExpired);
+
+        let (_, bump) = Pubkey::find_program_address(&[b"proposal", config.proposal_count.to_le_bytes().as_ref()], &crate::ID);
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.id = config.proposal_count;
+        proposal.proposer = proposer;
+        proposal.target = target;
+        proposal.action = action;
+        proposal.payload_hash = payload_hash;
+        proposal.expiry_ts = expiry_ts;
+        proposal.approvers = Vec::new();
+        proposal.executed = false;
+        proposal.bump = bump;
+        config.proposal_count = config.proposal_count.checked_add(1).ok_or(AmmError::MathOverflow)?;
+
+        emit!(ProposalCreated { proposal: proposal.key(), id: proposal.id, proposer, target, action, payload_hash, expiry_ts });
+        Ok(())
+    }
 
-    #[account(seeds=[b"global"], bump)]
-    pub global: Account<'info, Global>,
-}
+    /// Approve a pending proposal (multisig signers only, once each)
+    pub fn approve_proposal(ctx: Context<ApproveProposal>) -> Result<()> {
+        let signer = ctx.accounts.signer.key();
+        require!(ctx.accounts.governance_config.signers.contains(&signer), AmmError::NotGovernanceSigner);
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, AmmError::ProposalAlreadyExecuted);
+        require!(proposal.expiry_ts >= Clock::get()?.unix_timestamp, AmmError::GovernanceApprovalExpired);
+        require!(!proposal.approvers.contains(&signer), AmmError::AlreadyApproved);
+
+        proposal.approvers.push(signer);
+        emit!(ProposalApproved { proposal: proposal.key(), signer, approvals: proposal.approvers.len() as u8 });
+        Ok(())
+    }
 
-#[account]
-pub struct Pool {
-    pub version: u8,
-    pub stack_mint: Pubkey,
-    pub quote_mint: Pubkey,
+    /// Execute a proposal approved by at least `threshold` current signers (anyone may pay): writes its
+    /// `GovernanceApproval` with nonce `id + 1`, whose rent the payer gets back when it is consumed. Approvals
+    /// for the same pool must be consumed in nonce order.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let config = &ctx.accounts.governance_config;
+        let proposal = &mut ctx.accounts.proposal;
+        require!(!proposal.executed, AmmError::ProposalAlreadyExecuted);
+        require!(proposal.expiry_ts >= Clock::get()?.unix_timestamp, AmmError::GovernanceApprovalExpired);
+        // approvals from signers removed since they approved don't count
+        let approvals = proposal.approvers.iter().filter(|a| config.signers.contains(a)).count();
+        require!(approvals >= config.threshold as usize, AmmError::GovernanceNotApproved);
+
+        let approval = &mut ctx.accounts.governance_approval;
+        approval.target = proposal.target;
+        approval.approved = true;
+        approval.expiry_ts = proposal.expiry_ts;
+        approval.nonce = proposal.id as u128 + 1;
+        approval.action = proposal.action;
+        approval.payload_hash = proposal.payload_hash;
+        approval.rent_payer = ctx.accounts.payer.key();
+        proposal.executed = true;
+
+        emit!(ProposalExecuted { proposal: proposal.key(), approval: approval.key(), nonce: approval.nonce });
+        Ok(())
+    }
+
+    /// Cancel a proposal that hasn't been executed: by its proposer at any time, or by anyone once expired.
+    /// Rent goes back to the proposer.
+    pub fn cancel_proposal(ctx: Context<CancelProposal>) -> Result<()> {
+        let proposal = &ctx.accounts.proposal;
+        require!(!proposal.executed, AmmError::ProposalAlreadyExecuted);
+        let caller = ctx.accounts.caller.key();
+        require!(
+            caller == proposal.proposer || proposal.expiry_ts < Clock::get()?.unix_timestamp,
+            AmmError::Unauthorized
+        );
+        emit!(ProposalCancelled { proposal: proposal.key(), by: caller });
+        Ok(())
+    }
+
+    /* -----------------------
+       CONCENTRATED LIQUIDITY
+       Separate `ClPool` accounts under the same Global / StackInfo registry. Liquidity is provided over
+       tick ranges through per-owner `ClPosition` PDAs; ticks live in `TickArray` PDAs of TICK_ARRAY_SIZE.
+       Prices are sqrt(quote per stack) in Q64.64 over native amounts (no decimal normalization).
+       ----------------------- */
+
+    /// Create a concentrated-liquidity pool at `initial_sqrt_price_x64`; one pool per mint pair and tick spacing
+    pub fn create_cl_pool(ctx: Context<CreateClPool>, fee_bps: u16, tick_spacing: u16, initial_sqrt_price_x64: u128) -> Result<()> {
+        require!((1..=CL_MAX_TICK_SPACING).contains(&tick_spacing), AmmError::InvalidTickSpacing);
+        let g = &ctx.accounts.global;
+        let max_fee = if g.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { g.max_fee_bps };
+        require!(fee_bps <= max_fee, AmmError::InvalidFee);
+        validate_mint_extensions(&ctx.accounts.stack_mint, Some(&g.transfer_hook_programs))?;
+        validate_mint_extensions(&ctx.accounts.quote_mint, None)?;
+        let tick_current = tick_at_sqrt_price(initial_sqrt_price_x64)?;
+
+        let pool_key = ctx.accounts.cl_pool.key();
+        let (expected_vault_auth, vault_auth_bump) = Pubkey::find_program_address(
+            &[b"vault_authority", pool_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(expected_vault_auth, ctx.accounts.vault_authority.key(), AmmError::InvalidVaultOwner);
+        require_keys_eq!(ctx.accounts.stack_vault.owner, expected_vault_auth, AmmError::InvalidVaultOwner);
+        require_keys_eq!(ctx.accounts.quote_vault.owner, expected_vault_auth, AmmError::InvalidVaultOwner);
+        require_keys_eq!(ctx.accounts.stack_vault.mint, ctx.accounts.stack_mint.key(), AmmError::InvalidVaultMint);
+        require_keys_eq!(ctx.accounts.quote_vault.mint, ctx.accounts.quote_mint.key(), AmmError::InvalidVaultMint);
+
+        let pool = &mut ctx.accounts.cl_pool;
+        pool.version = 1;
+        pool.stack_mint = ctx.accounts.stack_mint.key();
+        pool.quote_mint = ctx.accounts.quote_mint.key();
+        pool.stack_vault = ctx.accounts.stack_vault.key();
+        pool.quote_vault = ctx.accounts.quote_vault.key();
+        pool.fee_bps = fee_bps;
+        pool.tick_spacing = tick_spacing;
+        let (_expected_pool_pda, bump_pool) = Pubkey::find_program_address(
+            &[b"cl_pool", pool.stack_mint.as_ref(), pool.quote_mint.as_ref(), &tick_spacing.to_le_bytes()],
+            &crate::ID,
+        );
+        pool.bump = bump_pool;
+        pool.vault_authority_bump = vault_auth_bump;
+        pool.locked = false;
+        pool.sqrt_price_x64 = initial_sqrt_price_x64;
+        pool.tick_current = tick_current;
+        pool.liquidity = 0;
+        pool.fee_growth_global_stack_x64 = 0;
+        pool.fee_growth_global_quote_x64 = 0;
+        pool.protocol_fees_stack = 0;
+        pool.protocol_fees_quote = 0;
+        pool.creator_fees_stack = 0;
+        pool.creator_fees_quote = 0;
+        pool.creator_last_claim_ts = 0;
+        pool.pause_flags = 0;
+
+        emit!(ClPoolCreated {
+            cl_pool: pool_key,
+            stack_mint: pool.stack_mint,
+            quote_mint: pool.quote_mint,
+            fee_bps,
+            tick_spacing,
+            sqrt_price_x64: initial_sqrt_price_x64,
+        });
+        Ok(())
+    }
+
+    /// Create the tick array starting at `start_tick_index` (permissionless; the payer funds the rent)
+    pub fn init_tick_array(ctx: Context<InitTickArray>, start_tick_index: i32) -> Result<()> {
+        let tick_spacing = ctx.accounts.cl_pool.tick_spacing;
+        require!(tick_array_start(start_tick_index, tick_spacing) == start_tick_index, AmmError::TickArrayMismatch);
+        require!(
+            start_tick_index >= tick_array_start(CL_MIN_TICK, tick_spacing) && start_tick_index <= CL_MAX_TICK,
+            AmmError::InvalidTickRange
+        );
+        let tick_array = &mut ctx.accounts.tick_array;
+        tick_array.cl_pool = ctx.accounts.cl_pool.key();
+        tick_array.start_tick_index = start_tick_index;
+        tick_array.ticks = [Tick::default(); TICK_ARRAY_SIZE];
+        Ok(())
+    }
+
+    /// Open an empty position over [tick_lower, tick_upper); both ticks must sit on the pool's tick spacing
+    pub fn open_cl_position(ctx: Context<OpenClPosition>, tick_lower: i32, tick_upper: i32) -> Result<()> {
+        let tick_spacing = ctx.accounts.cl_pool.tick_spacing as i32;
+        require!(
+            tick_lower < tick_upper
+                && tick_lower >= CL_MIN_TICK
+                && tick_upper <= CL_MAX_TICK
+                && tick_lower % tick_spacing == 0
+                && tick_upper % tick_spacing == 0,
+            AmmError::InvalidTickRange
+        );
+        let cl_pool_key = ctx.accounts.cl_pool.key();
+        let owner_key = ctx.accounts.owner.key();
+        let (_expected_position_pda, bump_position) = Pubkey::find_program_address(
+            &[b"cl_position", cl_pool_key.as_ref(), owner_key.as_ref(), &tick_lower.to_le_bytes(), &tick_upper.to_le_bytes()],
+            &crate::ID,
+        );
+        let position = &mut ctx.accounts.position;
+        position.cl_pool = cl_pool_key;
+        position.owner = owner_key;
+        position.tick_lower = tick_lower;
+        position.tick_upper = tick_upper;
+        position.liquidity = 0;
+        position.fee_growth_inside_stack_last_x64 = 0;
+        position.fee_growth_inside_quote_last_x64 = 0;
+        position.tokens_owed_stack = 0;
+        position.tokens_owed_quote = 0;
+        position.bump = bump_position;
+        Ok(())
+    }
+
+    /// Add `liquidity` to a position, pulling at most `max_stack` / `max_quote` (amounts rounded up)
+    pub fn increase_cl_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, ModifyClLiquidity<'info>>,
+        liquidity: u128,
+        max_stack: u64,
+        max_quote: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        require!(!ctx.accounts.global.is_paused(PAUSE_DEPOSITS), AmmError::ProtocolPaused);
+        require!(!ctx.accounts.cl_pool.is_paused(PAUSE_DEPOSITS), AmmError::PoolPaused);
+        require!(!ctx.accounts.cl_pool.locked, AmmError::Reentrancy);
+        require!(liquidity > 0, AmmError::ZeroLpMint);
+        let liquidity_delta = i128::try_from(liquidity).map_err(|_| AmmError::MathOverflow)?;
+        let (amount_stack, amount_quote) = ctx.accounts.modify_position(liquidity_delta)?;
+
+        // the vaults must receive the full amounts, so Token-2022 transfer fees are grossed up on top
+        let send_stack = transfer_amount_for(&ctx.accounts.stack_mint, amount_stack)?;
+        let send_quote = transfer_amount_for(&ctx.accounts.quote_mint, amount_quote)?;
+        require!(send_stack <= max_stack && send_quote <= max_quote, AmmError::SlippageExceeded);
+        if send_stack > 0 {
+            transfer_from_user(
+                &ctx.accounts.stack_token_program,
+                &ctx.accounts.owner_stack_account,
+                &ctx.accounts.stack_vault,
+                &ctx.accounts.stack_mint,
+                ctx.remaining_accounts,
+                &ctx.accounts.owner,
+                send_stack,
+            )?;
+        }
+        if send_quote > 0 {
+            transfer_from_user(
+                &ctx.accounts.token_program,
+                &ctx.accounts.owner_quote_account,
+                &ctx.accounts.quote_vault,
+                &ctx.accounts.quote_mint,
+                &[],
+                &ctx.accounts.owner,
+                send_quote,
+            )?;
+        }
+
+        emit!(ClLiquidityChanged {
+            cl_pool: ctx.accounts.cl_pool.key(),
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            liquidity_delta,
+            amount_stack,
+            amount_quote,
+        });
+        Ok(())
+    }
+
+    /// Remove `liquidity` from a position and pay the principal out (amounts rounded down). Allowed while
+    /// the protocol is paused so LPs can always exit; accrued fees stay on the position for `collect_cl_fees`.
+    pub fn decrease_cl_liquidity<'info>(
+        ctx: Context<'_, '_, '_, 'info, ModifyClLiquidity<'info>>,
+        liquidity: u128,
+        min_stack_out: u64,
+        min_quote_out: u64,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        require!(!ctx.accounts.cl_pool.locked, AmmError::Reentrancy);
+        require!(liquidity > 0 && liquidity <= ctx.accounts.position.liquidity, AmmError::InsufficientLiquidity);
+        let liquidity_delta = -i128::try_from(liquidity).map_err(|_| AmmError::MathOverflow)?;
+        let (amount_stack, amount_quote) = ctx.accounts.modify_position(liquidity_delta)?;
+        require!(
+            amount_after_transfer_fee(&ctx.accounts.stack_mint, amount_stack)? >= min_stack_out
+                && amount_after_transfer_fee(&ctx.accounts.quote_mint, amount_quote)? >= min_quote_out,
+            AmmError::SlippageExceeded
+        );
+        ctx.accounts.pay_out(ctx.remaining_accounts, amount_stack, amount_quote)?;
+
+        emit!(ClLiquidityChanged {
+            cl_pool: ctx.accounts.cl_pool.key(),
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            liquidity_delta,
+            amount_stack,
+            amount_quote,
+        });
+        Ok(())
+    }
+
+    /// Settle the fees a position earned while in range and pay everything it is owed
+    pub fn collect_cl_fees<'info>(ctx: Context<'_, '_, '_, 'info, ModifyClLiquidity<'info>>) -> Result<()> {
+        require!(!ctx.accounts.cl_pool.locked, AmmError::Reentrancy);
+        ctx.accounts.modify_position(0)?;
+        let position = &mut ctx.accounts.position;
+        let (amount_stack, amount_quote) = (position.tokens_owed_stack, position.tokens_owed_quote);
+        require!(amount_stack > 0 || amount_quote > 0, AmmError::NoFees);
+        position.tokens_owed_stack = 0;
+        position.tokens_owed_quote = 0;
+        ctx.accounts.pay_out(ctx.remaining_accounts, amount_stack, amount_quote)?;
+
+        emit!(ClFeesCollected {
+            cl_pool: ctx.accounts.cl_pool.key(),
+            position: ctx.accounts.position.key(),
+            owner: ctx.accounts.owner.key(),
+            amount_stack,
+            amount_quote,
+        });
+        Ok(())
+    }
+
+    /// Close an empty position (no liquidity, nothing owed) and refund its rent to the owner
+    pub fn close_cl_position(ctx: Context<CloseClPosition>) -> Result<()> {
+        let position = &ctx.accounts.position;
+        require!(
+            position.liquidity == 0 && position.tokens_owed_stack == 0 && position.tokens_owed_quote == 0,
+            AmmError::PositionNotEmpty
+        );
+        Ok(())
+    }
+
+    /// Exact-in swap on a concentrated-liquidity pool. remaining_accounts: the tick arrays the price will
+    /// traverse (starting with the one holding the current tick, at most MAX_TICK_ARRAYS_PER_SWAP), then any
+    /// transfer-hook accounts.
+    #[allow(clippy::too_many_arguments)]
+    pub fn swap_cl<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapCl<'info>>,
+        amount_in: u64,
+        min_out: u64,
+        side: SwapDirection,
+        deadline_unix_ts: Option<i64>,
+        max_slot: Option<u64>,
+    ) -> Result<()> {
+        check_deadline(deadline_unix_ts, max_slot)?;
+        process_cl_swap(ctx, amount_in, min_out, side)
+    }
+
+    /// Creator claims the creator share of a concentrated-liquidity pool's swap fees (same timelock as pools)
+    pub fn claim_cl_creator_fees<'info>(ctx: Context<'_, '_, '_, 'info, CollectClPoolFees<'info>>) -> Result<()> {
+        require!(ctx.accounts.authority.key() == ctx.accounts.stack_info.creator, AmmError::Unauthorized);
+        let pool = &mut ctx.accounts.cl_pool;
+        let (amount_stack, amount_quote) = (pool.creator_fees_stack, pool.creator_fees_quote);
+        require!(amount_stack > 0 || amount_quote > 0, AmmError::NoFees);
+        let g = &ctx.accounts.global;
+        require!(!g.is_paused(PAUSE_CREATOR_CLAIMS), AmmError::ProtocolPaused);
+        require!(!pool.is_paused(PAUSE_CREATOR_CLAIMS), AmmError::PoolPaused);
+        let lock_secs = if g.creator_claim_lock_secs == 0 { FALLBACK_CREATOR_CLAIM_LOCK_SECS } else { g.creator_claim_lock_secs };
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= pool.creator_last_claim_ts.checked_add(lock_secs).ok_or(AmmError::MathOverflow)?, AmmError::ClaimLocked);
+        pool.creator_fees_stack = 0;
+        pool.creator_fees_quote = 0;
+        ctx.accounts.pay_out(ctx.remaining_accounts, amount_stack, amount_quote)?;
+
+        emit!(CreatorClaimed {
+            pool: ctx.accounts.cl_pool.key(),
+            creator: ctx.accounts.authority.key(),
+            amount_stack,
+            amount_quote,
+        });
+        Ok(())
+    }
+
+    /// Admin/fee manager/governance withdraws the protocol share of a concentrated-liquidity pool's swap fees
+    pub fn withdraw_cl_protocol_fees<'info>(ctx: Context<'_, '_, '_, 'info, CollectClPoolFees<'info>>) -> Result<()> {
+        let g = &ctx.accounts.global;
+        let caller = ctx.accounts.authority.key();
+        require!(caller == g.admin || caller == g.fee_manager || caller == g.governance, AmmError::Unauthorized);
+        let pool = &mut ctx.accounts.cl_pool;
+        let (amount_stack, amount_quote) = (pool.protocol_fees_stack, pool.protocol_fees_quote);
+        pool.protocol_fees_stack = 0;
+        pool.protocol_fees_quote = 0;
+        ctx.accounts.pay_out(ctx.remaining_accounts, amount_stack, amount_quote)?;
+
+        emit!(ProtocolFeesWithdrawn {
+            pool: ctx.accounts.cl_pool.key(),
+            to_stack: ctx.accounts.stack_receiver.key(),
+            to_quote: ctx.accounts.quote_receiver.key(),
+            amount_stack,
+            amount_quote,
+        });
+        Ok(())
+    }
+}
+
+/* -----------------------
+   REBALANCE HOOK
+   ----------------------- */
+
+/// After a trade on `pool`, record a rebalance-hook request if the stack has a hook installed, its program is
+/// still on `Global.rebalance_hook_programs`, and the trade's input is at least `rebalance_threshold_bps` of the
+/// input-side reserve before the trade (for NAV mints and redeems: the backing reserve and the backed supply).
+/// The hook itself runs later in `execute_rebalance_hook`: a failed CPI aborts the whole transaction on Solana,
+/// so calling it here would let a broken or hostile hook revert every qualifying trade. There is one pending
+/// slot per stack: a newer request replaces one that was never executed (flagged in `RebalanceHookRequested`),
+/// so the hook sees the latest post-trade state rather than every crossing. Returns whether a request was recorded.
+#[allow(clippy::too_many_arguments)]
+fn request_rebalance_hook<'info>(
+    stack_info: &mut Account<'info, StackInfo>,
+    global: &Global,
+    pool: &Account<'info, Pool>,
+    stack_mint: &mut InterfaceAccount<'info, Mint>,
+    stack_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    quote_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    trader: Pubkey,
+    trade: RebalanceTrade,
+    amount_in: u64,
+    amount_out: u64,
+    reserve_in_before: u64,
+) -> Result<bool> {
+    let hook = match stack_info.rebalance_hook {
+        Some(hook) if global.rebalance_hook_programs.contains(&hook) => hook,
+        _ => return Ok(false),
+    };
+    let threshold = (reserve_in_before as u128)
+        .checked_mul(stack_info.rebalance_threshold_bps as u128).ok_or(AmmError::MathOverflow)?;
+    if (amount_in as u128).checked_mul(BPS_DENOM).ok_or(AmmError::MathOverflow)? < threshold {
+        return Ok(false);
+    }
+
+    // post-trade view: vault balances minus accrued fees, and the supply after any mint/burn
+    stack_vault.reload()?;
+    quote_vault.reload()?;
+    stack_mint.reload()?;
+    let replaced_pending = stack_info.pending_rebalance.is_some();
+    stack_info.pending_rebalance = Some(RebalanceHookArgs {
+        pool: pool.key(),
+        stack_mint: stack_mint.key(),
+        trader,
+        trade,
+        amount_in,
+        amount_out,
+        reserve_stack: vault_reserve(stack_vault.amount, pool.accrued_fees_stack()?)?,
+        reserve_quote: vault_reserve(quote_vault.amount, pool.accrued_fees_quote()?)?,
+        stack_supply: stack_mint.supply,
+        backing_reserve: stack_info.backing.map_or(0, |b| b.reserve),
+        backed_supply: stack_info.backing.map_or(0, |b| b.backed_supply),
+    });
+    emit!(RebalanceHookRequested { stack_mint: stack_mint.key(), pool: pool.key(), hook, trade, amount_in, replaced_pending });
+    Ok(true)
+}
+
+/* -----------------------
+   SWAP PROCESSORS (shared by exact-in / exact-out instructions)
+   ----------------------- */
+
+/// Shared body of `swap_stack_to_quote` and `swap_stack_to_quote_exact_out`
+fn process_swap_stack_to_quote<'info>(
+    ctx: Context<'_, '_, '_, 'info, SwapStackToQuote<'info>>,
+    amount: SwapAmount,
+    use_governance_approval: bool,
+) -> Result<()> {
+    // capture pool key & bump before mutable borrow
+    let pool_key = ctx.accounts.pool.key();
+    let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
+    let pool = &mut ctx.accounts.pool;
+    let global = &ctx.accounts.global;
+
+    if use_governance_approval {
+        // checks target, action, payload, expiry and nonce > last_governance_nonce, then closes the approval
+        consume_governance_approval(
+            &ctx.accounts.governance_approval,
+            &ctx.accounts.approval_rent_receiver,
+            pool_key,
+            GovernanceAction::SwapStackToQuote,
+            governance_payload_hash(&(ctx.accounts.user.key(), amount))?,
+            pool,
+        )?;
+    }
+
+    require!(!global.is_paused(PAUSE_SWAPS), AmmError::ProtocolPaused);
+    require!(!pool.is_paused(PAUSE_SWAPS), AmmError::PoolPaused);
+    require!(!pool.locked, AmmError::Reentrancy);
+    pool.locked = true;
+
+    // validate decimals & accounts
+    validate_token_account_matches_mint(&ctx.accounts.user_stack_account, &ctx.accounts.stack_mint)?;
+    validate_token_account_matches_mint(&ctx.accounts.user_quote_account, &ctx.accounts.quote_mint)?;
+
+    // Capture vault balance & trading reserves (balance minus accrued protocol/creator fees) before transfer
+    let stack_vault_before = ctx.accounts.stack_vault.amount;
+    let reserve_stack_before = vault_reserve(stack_vault_before, pool.accrued_fees_stack()?)?;
+    let reserve_quote_before = vault_reserve(ctx.accounts.quote_vault.amount, pool.accrued_fees_quote()?)?;
+
+    // normalize reserves
+    let reserve_stack_norm = normalize_amount_u128(reserve_stack_before, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
+    let reserve_quote_norm = normalize_amount_u128(reserve_quote_before, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
+    update_price_accumulators(pool, &mut ctx.accounts.observations, reserve_stack_norm, reserve_quote_norm)?;
+
+    // exact-out: size the stack input from the requested quote output
+    let amount_in = match amount {
+        SwapAmount::ExactIn { amount_in, .. } => amount_in,
+        SwapAmount::ExactOut { amount_out, max_in } => {
+            // Token-2022 transfer fees are grossed up on both legs: the vault pays the output fee,
+            // the user pays the input fee so the vault still receives the full curve input
+            let amount_in = exact_out_amount_in(
+                transfer_amount_for(&ctx.accounts.quote_mint, amount_out)?,
+                reserve_stack_norm,
+                reserve_quote_norm,
+                ctx.accounts.stack_mint.decimals,
+                ctx.accounts.quote_mint.decimals,
+                pool.decimal_normalize_to,
+                pool,
+                true,
+            )?;
+            let amount_in = transfer_amount_for(&ctx.accounts.stack_mint, amount_in)?;
+            require!(amount_in <= max_in, AmmError::SlippageExceeded);
+            amount_in
+        }
+    };
+
+    // Transfer stack from user to vault
+    transfer_from_user(
+        &ctx.accounts.stack_token_program,
+        &ctx.accounts.user_stack_account,
+        &ctx.accounts.stack_vault,
+        &ctx.accounts.stack_mint,
+        ctx.remaining_accounts,
+        &ctx.accounts.user,
+        amount_in,
+    )?;
+
+    ctx.accounts.stack_vault.reload()?;
+    let actual_in_stack = ctx.accounts.stack_vault.amount.checked_sub(stack_vault_before).ok_or(AmmError::MathOverflow)?;
+
+    // fee split in native stack units; only the net input is priced on the curve
+    let (gross_fee, protocol_fee, creator_fee, net_in_native) =
+        compute_fees(actual_in_stack as u128, pool.fee_bps, ctx.accounts.global.protocol_fee_bps, ctx.accounts.stack_info.creator_fee_bps)?;
+    let net_in = normalize_amount_u128(net_in_native as u64, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
+
+    // circuit breaker: ensure fees don't exceed some absolute safety threshold (global.max_fee_bps)
+    let max_fee_bps = if ctx.accounts.global.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { ctx.accounts.global.max_fee_bps };
+    require!(pool.fee_bps <= max_fee_bps, AmmError::InvalidFee);
+
+    // curve out calculation in normalized units:
+    let amount_out_norm = curve_amount_out(pool, true, net_in, reserve_stack_norm, reserve_quote_norm)?;
+
+    // Price protection against the pool's on-chain oracle (quote received per stack sold)
+    enforce_oracle_guardrail(pool, &ctx.accounts.oracle.to_account_info(), amount_out_norm, net_in)?;
+
+    // convert amount_out_norm -> native quote units and apply the caller's limit
+    let amount_out = settle_amount_out(amount, amount_out_norm, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to, Some(&ctx.accounts.quote_mint))?;
+
+    // LP share stays in the stack vault (growing k); protocol & creator shares accrue in stack
+    book_swap_fees(pool, pool_key, true, gross_fee, protocol_fee, creator_fee)?;
+
+    // Transfer amount_out from quote_vault to user (vault PDA signs)
+    transfer_from_vault(
+        &ctx.accounts.token_program,
+        &ctx.accounts.quote_vault,
+        &ctx.accounts.user_quote_account,
+        &ctx.accounts.quote_mint,
+        &[],
+        &ctx.accounts.vault_authority,
+        pool_key,
+        vault_authority_bump,
+        amount_out,
+    )?;
+
+    // sweep dust if tiny leftover (configurable)
+    let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
+    sweep_vault_dust(
+        &ctx.accounts.token_program,
+        &ctx.accounts.quote_mint,
+        &[],
+        &mut ctx.accounts.quote_vault,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.vault_authority,
+        pool_key,
+        vault_authority_bump,
+        pool.accrued_fees_quote()?,
+        dust_threshold,
+    )?;
+
+    emit!(Swap {
+        pool: pool_key,
+        trader: ctx.accounts.user.key(),
+        side: SwapDirection::StackToQuote,
+        amount_in,
+        amount_out,
+    });
+
+    request_rebalance_hook(
+        &mut ctx.accounts.stack_info,
+        &ctx.accounts.global,
+        pool,
+        &mut ctx.accounts.stack_mint,
+        &mut ctx.accounts.stack_vault,
+        &mut ctx.accounts.quote_vault,
+        ctx.accounts.user.key(),
+        RebalanceTrade::StackToQuote,
+        actual_in_stack,
+        amount_out,
+        reserve_stack_before,
+    )?;
+
+    pool.locked = false;
+    Ok(())
+}
+
+/// Shared body of `swap_quote_to_stack` and `swap_quote_to_stack_exact_out`
+fn process_swap_quote_to_stack<'info>(
+    ctx: Context<'_, '_, '_, 'info, SwapQuoteToStack<'info>>,
+    amount: SwapAmount,
+    use_governance_approval: bool,
+) -> Result<()> {
+    // capture pool key & bump before mutable borrow
+    let pool_key = ctx.accounts.pool.key();
+    let vault_authority_bump = ctx.accounts.pool.vault_authority_bump;
+    let pool = &mut ctx.accounts.pool;
+    let global = &ctx.accounts.global;
+
+    if use_governance_approval {
+        consume_governance_approval(
+            &ctx.accounts.governance_approval,
+            &ctx.accounts.approval_rent_receiver,
+            pool_key,
+            GovernanceAction::SwapQuoteToStack,
+            governance_payload_hash(&(ctx.accounts.user.key(), amount))?,
+            pool,
+        )?;
+    }
+
+    require!(!global.is_paused(PAUSE_SWAPS), AmmError::ProtocolPaused);
+    require!(!pool.is_paused(PAUSE_SWAPS), AmmError::PoolPaused);
+    require!(!pool.locked, AmmError::Reentrancy);
+    pool.locked = true;
+
+    // Validate accounts
+    validate_token_account_matches_mint(&ctx.accounts.user_quote_account, &ctx.accounts.quote_mint)?;
+    validate_token_account_matches_mint(&ctx.accounts.user_stack_account, &ctx.accounts.stack_mint)?;
+
+    // vault balance & trading reserves (balance minus accrued protocol/creator fees) before transfer
+    let quote_vault_before = ctx.accounts.quote_vault.amount;
+    let reserve_stack_before = vault_reserve(ctx.accounts.stack_vault.amount, pool.accrued_fees_stack()?)?;
+    let reserve_quote_before = vault_reserve(quote_vault_before, pool.accrued_fees_quote()?)?;
+
+    // normalize reserves
+    let reserve_quote_norm = normalize_amount_u128(reserve_quote_before, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
+    let reserve_stack_norm = normalize_amount_u128(reserve_stack_before, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to)?;
+    update_price_accumulators(pool, &mut ctx.accounts.observations, reserve_stack_norm, reserve_quote_norm)?;
+
+    // exact-out: size the quote input from the requested stack output
+    let amount_in = match amount {
+        SwapAmount::ExactIn { amount_in, .. } => amount_in,
+        SwapAmount::ExactOut { amount_out, max_in } => {
+            // transfer fees grossed up on both legs, as in `process_swap_stack_to_quote`
+            let amount_in = exact_out_amount_in(
+                transfer_amount_for(&ctx.accounts.stack_mint, amount_out)?,
+                reserve_quote_norm,
+                reserve_stack_norm,
+                ctx.accounts.quote_mint.decimals,
+                ctx.accounts.stack_mint.decimals,
+                pool.decimal_normalize_to,
+                pool,
+                false,
+            )?;
+            let amount_in = transfer_amount_for(&ctx.accounts.quote_mint, amount_in)?;
+            require!(amount_in <= max_in, AmmError::SlippageExceeded);
+            amount_in
+        }
+    };
+
+    // Transfer quote from user to quote_vault
+    transfer_from_user(
+        &ctx.accounts.token_program,
+        &ctx.accounts.user_quote_account,
+        &ctx.accounts.quote_vault,
+        &ctx.accounts.quote_mint,
+        &[],
+        &ctx.accounts.user,
+        amount_in,
+    )?;
+
+    ctx.accounts.quote_vault.reload()?;
+    let actual_in_quote = ctx.accounts.quote_vault.amount.checked_sub(quote_vault_before).ok_or(AmmError::MathOverflow)?;
+
+    // fee split in native quote units; only the net input is priced on the curve
+    let (gross_fee, protocol_fee, creator_fee, net_in_native) =
+        compute_fees(actual_in_quote as u128, pool.fee_bps, ctx.accounts.global.protocol_fee_bps, ctx.accounts.stack_info.creator_fee_bps)?;
+    let net_in = normalize_amount_u128(net_in_native as u64, ctx.accounts.quote_mint.decimals, pool.decimal_normalize_to)?;
+
+    // curve out calculation in normalized units:
+    let amount_out_norm = curve_amount_out(pool, false, net_in, reserve_quote_norm, reserve_stack_norm)?;
+
+    // price protection against the pool's on-chain oracle (quote paid per stack received)
+    enforce_oracle_guardrail(pool, &ctx.accounts.oracle.to_account_info(), net_in, amount_out_norm)?;
+
+    // denormalize amount_out to native stack units and apply the caller's limit
+    let amount_out = settle_amount_out(amount, amount_out_norm, ctx.accounts.stack_mint.decimals, pool.decimal_normalize_to, Some(&ctx.accounts.stack_mint))?;
+
+    // LP share stays in the quote vault (growing k); protocol & creator shares accrue in quote
+    book_swap_fees(pool, pool_key, false, gross_fee, protocol_fee, creator_fee)?;
+
+    // transfer stacks to user
+    transfer_from_vault(
+        &ctx.accounts.stack_token_program,
+        &ctx.accounts.stack_vault,
+        &ctx.accounts.user_stack_account,
+        &ctx.accounts.stack_mint,
+        ctx.remaining_accounts,
+        &ctx.accounts.vault_authority,
+        pool_key,
+        vault_authority_bump,
+        amount_out,
+    )?;
+
+    // sweep dust if tiny leftover
+    let dust_threshold = if ctx.accounts.global.dust_threshold == 0 { FALLBACK_DUST_THRESHOLD } else { ctx.accounts.global.dust_threshold };
+    sweep_vault_dust(
+        &ctx.accounts.stack_token_program,
+        &ctx.accounts.stack_mint,
+        ctx.remaining_accounts,
+        &mut ctx.accounts.stack_vault,
+        &ctx.accounts.treasury_token_account,
+        &ctx.accounts.vault_authority,
+        pool_key,
+        vault_authority_bump,
+        pool.accrued_fees_stack()?,
+        dust_threshold,
+    )?;
+
+    emit!(Swap {
+        pool: pool_key,
+        trader: ctx.accounts.user.key(),
+        side: SwapDirection::QuoteToStack,
+        amount_in,
+        amount_out,
+    });
+
+    request_rebalance_hook(
+        &mut ctx.accounts.stack_info,
+        &ctx.accounts.global,
+        pool,
+        &mut ctx.accounts.stack_mint,
+        &mut ctx.accounts.stack_vault,
+        &mut ctx.accounts.quote_vault,
+        ctx.accounts.user.key(),
+        RebalanceTrade::QuoteToStack,
+        actual_in_quote,
+        amount_out,
+        reserve_quote_before,
+    )?;
+
+    pool.locked = false;
+    Ok(())
+}
+
+/// Shared body of `mint_stack_via_pool` and `mint_stack_via_pool_exact_out`: primary issuance against the
+/// stack's backing reserve. The pool's vaults are untouched; the pool is only reported to the rebalance hook.
+fn process_mint_stack_via_pool<'info>(ctx: Context<'_, '_, '_, 'info, MintStackViaPool<'info>>, amount: SwapAmount) -> Result<()> {
+    require!(!ctx.accounts.global.is_paused(PAUSE_MINT), AmmError::ProtocolPaused);
+    // only the flag: a pool held paused by a launch is covered by the launch check below
+    require!(ctx.accounts.pool.pause_flags & PAUSE_MINT == 0, AmmError::PoolPaused);
+    let mut backing = ctx.accounts.stack_info.backing.ok_or(AmmError::BackingNotInitialized)?;
+    require_keys_eq!(ctx.accounts.backing_vault.key(), backing.vault, AmmError::InvalidVaultOwner);
+    require_keys_eq!(ctx.accounts.quote_mint.key(), backing.quote_mint, AmmError::InvalidVaultMint);
+    // a running bonding-curve launch is the only way to mint until it graduates
+    if let Some(curve) = ctx.accounts.stack_info.launch {
+        require!(curve.graduated, AmmError::LaunchNotActive);
+    }
+
+    // exact-out: quote needed at NAV (rounded up), grossed up for the fee and any transfer fee
+    let quote_in = match amount {
+        SwapAmount::ExactIn { amount_in, .. } => amount_in,
+        SwapAmount::ExactOut { amount_out, max_in } => {
+            let net = backing_quote_for_stack(&backing, amount_out)? as u128;
+            let gross = checked_div_ceil(
+                net.checked_mul(BPS_DENOM).ok_or(AmmError::MathOverflow)?,
+                BPS_DENOM - backing.fee_bps as u128,
+            )?;
+            let quote_in = transfer_amount_for(&ctx.accounts.quote_mint, u64::try_from(gross).map_err(|_| AmmError::MathOverflow)?)?;
+            require!(quote_in <= max_in, AmmError::SlippageExceeded);
+            quote_in
+        }
+    };
+
+    let vault_before = ctx.accounts.backing_vault.amount;
+    transfer_from_user(
+        &ctx.accounts.token_program,
+        &ctx.accounts.user_quote_account,
+        &ctx.accounts.backing_vault,
+        &ctx.accounts.quote_mint,
+        &[],
+        &ctx.accounts.user,
+        quote_in,
+    )?;
+    ctx.accounts.backing_vault.reload()?;
+    let received = ctx.accounts.backing_vault.amount.checked_sub(vault_before).ok_or(AmmError::MathOverflow)?;
+
+    // the fee stays in the reserve, raising NAV for existing holders; stack out is rounded down
+    let fee = (received as u128).checked_mul(backing.fee_bps as u128).ok_or(AmmError::MathOverflow)? / BPS_DENOM;
+    let stack_for_quote = backing_stack_for_quote(&backing, received - fee as u64)?;
+    let stack_out = match amount {
+        SwapAmount::ExactIn { min_out, .. } => {
+            require!(stack_for_quote >= min_out, AmmError::SlippageExceeded);
+            stack_for_quote
+        }
+        SwapAmount::ExactOut { amount_out, .. } => {
+            require!(stack_for_quote >= amount_out, AmmError::SlippageExceeded);
+            amount_out
+        }
+    };
+    require!(stack_out > 0, AmmError::SlippageExceeded);
+
+    // per-stack supply cap and mint limits (per transaction and sliding window)
+    let supply = ctx.accounts.stack_mint.supply;
+    ctx.accounts.stack_info.record_mint(stack_out, supply, Clock::get()?.unix_timestamp)?;
+    let reserve_before = backing.reserve;
+    backing.reserve = backing.reserve.checked_add(received).ok_or(AmmError::MathOverflow)?;
+    backing.backed_supply = backing.backed_supply.checked_add(stack_out).ok_or(AmmError::MathOverflow)?;
+    ctx.accounts.stack_info.backing = Some(backing);
+
+    // Mint stack tokens to user (stack_mint_auth PDA signs)
+    let mint_auth_bump = ctx.accounts.stack_info.mint_auth_bump;
+    token_interface::mint_to(
+        CpiContext::new(
+            ctx.accounts.stack_token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.stack_mint.to_account_info().clone(),
+                to: ctx.accounts.user_stack_account.to_account_info().clone(),
+                authority: ctx.accounts.stack_mint_auth.to_account_info().clone(),
+            },
+        )
+        .with_signer(&[&[b"stack_mint_auth", ctx.accounts.stack_mint.to_account_info().key.as_ref(), &[mint_auth_bump]]]),
+        stack_out,
+    )?;
+
+    emit!(MintedStackViaPool {
+        pool: ctx.accounts.pool.key(),
+        user: ctx.accounts.user.key(),
+        quote_in: received,
+        stack_out,
+        backing_reserve: backing.reserve,
+        backed_supply: backing.backed_supply,
+    });
+
+    request_rebalance_hook(
+        &mut ctx.accounts.stack_info,
+        &ctx.accounts.global,
+        &ctx.accounts.pool,
+        &mut ctx.accounts.stack_mint,
+        &mut ctx.accounts.stack_vault,
+        &mut ctx.accounts.quote_vault,
+        ctx.accounts.user.key(),
+        RebalanceTrade::MintViaPool,
+        received,
+        stack_out,
+        reserve_before,
+    )?;
+    Ok(())
+}
+
+/// Body of `route_swap`: validate every hop up front, then chain exact-in swaps through them
+fn process_route_swap<'info>(ctx: Context<'_, '_, 'info, 'info, RouteSwap<'info>>, amount_in: u64, min_out: u64) -> Result<()> {
+    let global = &ctx.accounts.global;
+    require!(!global.is_paused(PAUSE_SWAPS), AmmError::ProtocolPaused);
+    let max_fee_bps = if global.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { global.max_fee_bps };
+
+    // hops come first (each starting with a pool account); whatever follows the last hop is
+    // forwarded to Token-2022 transfer hooks on the mints being moved
+    let remaining = ctx.remaining_accounts;
+    let hop_count = remaining
+        .chunks_exact(ROUTE_ACCOUNTS_PER_HOP)
+        .take_while(|accounts| is_pool_account(&accounts[0]))
+        .count();
+    require!(hop_count > 0 && hop_count <= MAX_ROUTE_HOPS, AmmError::InvalidRoute);
+    let (hop_accounts, hook_accounts) = remaining.split_at(hop_count * ROUTE_ACCOUNTS_PER_HOP);
+
+    // load & validate every hop before moving any tokens; each hop's output mint is the next hop's input
+    let input_mint = ctx.accounts.user_source.mint;
+    let mut hops: Vec<RouteHop<'info>> = Vec::with_capacity(hop_count);
+    let mut next_mint = input_mint;
+    for accounts in hop_accounts.chunks(ROUTE_ACCOUNTS_PER_HOP) {
+        let hop = RouteHop::load(accounts, next_mint)?;
+        // a pool may appear only once: each hop holds its own copy of the pool state
+        require!(hops.iter().all(|h| h.pool.key() != hop.pool.key()), AmmError::InvalidRoute);
+        require!(hop.pool.fee_bps <= max_fee_bps, AmmError::InvalidFee);
+        next_mint = hop.output_mint();
+        hops.push(hop);
+    }
+    require_keys_eq!(ctx.accounts.user_destination.mint, next_mint, AmmError::InvalidRoute);
+
+    // hop 0 is funded by the user; every later hop by the previous hop's output vault
+    let mut amount = amount_in;
+    // (amount in, amount out, input reserve before) per hop, for the rebalance hooks once every vault has settled
+    let mut trades: Vec<(u64, u64, u64)> = Vec::with_capacity(hops.len());
+    for i in 0..hops.len() {
+        let (done, rest) = hops.split_at_mut(i);
+        let hop = &mut rest[0];
+        let pool_key = hop.pool.key();
+        let stack_in = hop.side == SwapDirection::StackToQuote;
+
+        // trading reserves (balance minus accrued protocol/creator fees) before the transfer in
+        let reserve_stack = vault_reserve(hop.stack_vault.amount, hop.pool.accrued_fees_stack()?)?;
+        let reserve_quote = vault_reserve(hop.quote_vault.amount, hop.pool.accrued_fees_quote()?)?;
+        let reserve_stack_norm = normalize_amount_u128(reserve_stack, hop.stack_mint.decimals, hop.pool.decimal_normalize_to)?;
+        let reserve_quote_norm = normalize_amount_u128(reserve_quote, hop.quote_mint.decimals, hop.pool.decimal_normalize_to)?;
+        update_price_accumulators(&mut hop.pool, &mut hop.observations, reserve_stack_norm, reserve_quote_norm)?;
+
+        let vault_in_before = if stack_in { hop.stack_vault.amount } else { hop.quote_vault.amount };
+        let vault_in = if stack_in { &hop.stack_vault } else { &hop.quote_vault };
+        match done.last() {
+            None => {
+                let (program, mint) = hop.input_token();
+                transfer_from_user(program, &ctx.accounts.user_source, vault_in, mint, hook_accounts, &ctx.accounts.user, amount)?
+            }
+            Some(prev) => prev.pay_out(vault_in, hook_accounts, amount)?,
+        }
+
+        // measure what actually arrived (fee-on-transfer safe)
+        let vault_in_after = if stack_in {
+            hop.stack_vault.reload()?;
+            hop.stack_vault.amount
+        } else {
+            hop.quote_vault.reload()?;
+            hop.quote_vault.amount
+        };
+        let actual_in = vault_in_after.checked_sub(vault_in_before).ok_or(AmmError::MathOverflow)?;
+
+        let (in_decimals, out_decimals, reserve_in_norm, reserve_out_norm) = if stack_in {
+            (hop.stack_mint.decimals, hop.quote_mint.decimals, reserve_stack_norm, reserve_quote_norm)
+        } else {
+            (hop.quote_mint.decimals, hop.stack_mint.decimals, reserve_quote_norm, reserve_stack_norm)
+        };
+
+        // same pricing as a direct exact-in swap: fee split in native input units, net priced on the curve
+        let (gross_fee, protocol_fee, creator_fee, net_in_native) =
+            compute_fees(actual_in as u128, hop.pool.fee_bps, global.protocol_fee_bps, hop.stack_info.creator_fee_bps)?;
+        let net_in = normalize_amount_u128(net_in_native as u64, in_decimals, hop.pool.decimal_normalize_to)?;
+        let amount_out_norm = curve_amount_out(&hop.pool, stack_in, net_in, reserve_in_norm, reserve_out_norm)?;
+        let (quote_norm, stack_norm) = if stack_in { (amount_out_norm, net_in) } else { (net_in, amount_out_norm) };
+        enforce_oracle_guardrail(&hop.pool, &hop.oracle, quote_norm, stack_norm)?;
+        let amount_out = denormalize_amount_u64(amount_out_norm, out_decimals, hop.pool.decimal_normalize_to)?;
+
+        book_swap_fees(&mut hop.pool, pool_key, stack_in, gross_fee, protocol_fee, creator_fee)?;
+
+        emit!(Swap {
+            pool: pool_key,
+            trader: ctx.accounts.user.key(),
+            side: hop.side.clone(),
+            amount_in: actual_in,
+            amount_out,
+        });
+        trades.push((actual_in, amount_out, if stack_in { reserve_stack } else { reserve_quote }));
+        amount = amount_out;
+    }
+
+    // single slippage check on what the user finally receives (after any Token-2022 transfer fee)
+    let last = hops.last().ok_or(AmmError::InvalidRoute)?;
+    require!(last.output_after_transfer_fee(amount)? >= min_out, AmmError::SlippageExceeded);
+    last.pay_out(&ctx.accounts.user_destination, hook_accounts, amount)?;
+
+    // hop state was deserialized by hand, so persist it explicitly; a hop's stack info is only written
+    // when it recorded a rebalance request
+    for (hop, (hop_in, hop_out, reserve_in_before)) in hops.iter_mut().zip(trades) {
+        let trade = if hop.side == SwapDirection::StackToQuote { RebalanceTrade::StackToQuote } else { RebalanceTrade::QuoteToStack };
+        if request_rebalance_hook(
+            &mut hop.stack_info,
+            global,
+            &hop.pool,
+            &mut hop.stack_mint,
+            &mut hop.stack_vault,
+            &mut hop.quote_vault,
+            ctx.accounts.user.key(),
+            trade,
+            hop_in,
+            hop_out,
+            reserve_in_before,
+        )? {
+            hop.stack_info.exit(&crate::ID)?;
+        }
+        hop.pool.exit(&crate::ID)?;
+        hop.observations.exit(&crate::ID)?;
+    }
+
+    emit!(RouteSwapped {
+        trader: ctx.accounts.user.key(),
+        input_mint,
+        output_mint: next_mint,
+        hops: hops.len() as u8,
+        amount_in,
+        amount_out: amount,
+    });
+    Ok(())
+}
+
+/// Body of `swap_cl`: walk the price across initialized ticks until the measured input is consumed.
+/// Fees are charged per step in the input token; protocol & creator shares accrue on the pool, the LP
+/// share is credited to in-range liquidity through the global fee growth.
+fn process_cl_swap<'info>(ctx: Context<'_, '_, 'info, 'info, SwapCl<'info>>, amount_in: u64, min_out: u64, side: SwapDirection) -> Result<()> {
+    let pool_key = ctx.accounts.cl_pool.key();
+    let vault_authority_bump = ctx.accounts.cl_pool.vault_authority_bump;
+    let global = &ctx.accounts.global;
+    require!(!global.is_paused(PAUSE_SWAPS), AmmError::ProtocolPaused);
+    require!(!ctx.accounts.cl_pool.is_paused(PAUSE_SWAPS), AmmError::PoolPaused);
+    let max_fee_bps = if global.max_fee_bps == 0 { FALLBACK_MAX_FEE_BPS } else { global.max_fee_bps };
+    require!(ctx.accounts.cl_pool.fee_bps <= max_fee_bps, AmmError::InvalidFee);
+    require!(!ctx.accounts.cl_pool.locked, AmmError::Reentrancy);
+    ctx.accounts.cl_pool.locked = true;
+    let stack_in = side == SwapDirection::StackToQuote;
+
+    validate_token_account_matches_mint(&ctx.accounts.user_stack_account, &ctx.accounts.stack_mint)?;
+    validate_token_account_matches_mint(&ctx.accounts.user_quote_account, &ctx.accounts.quote_mint)?;
+
+    // tick arrays come first, in the order the price will cross them; whatever follows is forwarded
+    // to Token-2022 transfer hooks on the stack mint
+    let remaining = ctx.remaining_accounts;
+    let array_count = remaining
+        .iter()
+        .take(MAX_TICK_ARRAYS_PER_SWAP)
+        .take_while(|account| is_tick_array_account(account))
+        .count();
+    require!(array_count > 0, AmmError::TickArrayMismatch);
+    let (array_accounts, hook_accounts) = remaining.split_at(array_count);
+
+    // the first array must hold the current tick and each next one must be adjacent in the swap
+    // direction; tick arrays are PDAs per (pool, start), so this also rules out duplicates
+    let tick_spacing = ctx.accounts.cl_pool.tick_spacing;
+    let span = tick_array_span(tick_spacing);
+    let mut expected_start = tick_array_start(ctx.accounts.cl_pool.tick_current, tick_spacing);
+    let mut tick_arrays: Vec<Account<'info, TickArray>> = Vec::with_capacity(array_count);
+    for account in array_accounts {
+        let tick_array: Account<'info, TickArray> = Account::try_from(account)?;
+        require_keys_eq!(tick_array.cl_pool, pool_key, AmmError::TickArrayMismatch);
+        require!(tick_array.start_tick_index == expected_start, AmmError::TickArrayMismatch);
+        expected_start = if stack_in { expected_start - span } else { expected_start + span };
+        tick_arrays.push(tick_array);
+    }
+
+    // measure what actually arrived (fee-on-transfer safe)
+    let vault_in_before = if stack_in { ctx.accounts.stack_vault.amount } else { ctx.accounts.quote_vault.amount };
+    let actual_in = if stack_in {
+        transfer_from_user(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.user_stack_account,
+            &ctx.accounts.stack_vault,
+            &ctx.accounts.stack_mint,
+            hook_accounts,
+            &ctx.accounts.user,
+            amount_in,
+        )?;
+        ctx.accounts.stack_vault.reload()?;
+        ctx.accounts.stack_vault.amount
+    } else {
+        transfer_from_user(
+            &ctx.accounts.token_program,
+            &ctx.accounts.user_quote_account,
+            &ctx.accounts.quote_vault,
+            &ctx.accounts.quote_mint,
+            &[],
+            &ctx.accounts.user,
+            amount_in,
+        )?;
+        ctx.accounts.quote_vault.reload()?;
+        ctx.accounts.quote_vault.amount
+    }
+    .checked_sub(vault_in_before).ok_or(AmmError::MathOverflow)?;
+
+    let protocol_fee_bps = ctx.accounts.global.protocol_fee_bps as u128;
+    let creator_fee_bps = ctx.accounts.stack_info.creator_fee_bps as u128;
+    let pool = &mut ctx.accounts.cl_pool;
+    let mut sqrt_price = pool.sqrt_price_x64;
+    let mut tick_current = pool.tick_current;
+    let mut liquidity = pool.liquidity;
+    let mut fee_growth_global = (pool.fee_growth_global_stack_x64, pool.fee_growth_global_quote_x64);
+    let mut remaining_in = actual_in as u128;
+    let (mut amount_out, mut gross_fee, mut protocol_fee, mut creator_fee) = (0u128, 0u128, 0u128, 0u128);
+
+    while remaining_in > 0 {
+        let (next_tick, target_tick) = next_initialized_tick(&tick_arrays, tick_current, tick_spacing, stack_in)?;
+        let sqrt_target = sqrt_price_at_tick(target_tick)?;
+        let (sqrt_next, step_in, step_out, step_fee) =
+            cl_swap_step(sqrt_price, sqrt_target, liquidity, remaining_in, pool.fee_bps, stack_in)?;
+        remaining_in = remaining_in
+            .checked_sub(step_in).ok_or(AmmError::MathOverflow)?
+            .checked_sub(step_fee).ok_or(AmmError::MathOverflow)?;
+        amount_out = amount_out.checked_add(step_out).ok_or(AmmError::MathOverflow)?;
+
+        // protocol & creator take their shares of the step fee; the rest goes to in-range LPs
+        let step_protocol = step_fee.checked_mul(protocol_fee_bps).ok_or(AmmError::MathOverflow)? / BPS_DENOM;
+        let step_creator = step_fee.checked_mul(creator_fee_bps).ok_or(AmmError::MathOverflow)? / BPS_DENOM;
+        let step_lp = step_fee
+            .checked_sub(step_protocol).ok_or(AmmError::MathOverflow)?
+            .checked_sub(step_creator).ok_or(AmmError::MathOverflow)?;
+        if liquidity > 0 {
+            let growth = mul_div_wide(step_lp, CL_Q64, liquidity)?;
+            if stack_in {
+                fee_growth_global.0 = fee_growth_global.0.wrapping_add(growth);
+            } else {
+                fee_growth_global.1 = fee_growth_global.1.wrapping_add(growth);
+            }
+        }
+        gross_fee = gross_fee.checked_add(step_fee).ok_or(AmmError::MathOverflow)?;
+        protocol_fee = protocol_fee.checked_add(step_protocol).ok_or(AmmError::MathOverflow)?;
+        creator_fee = creator_fee.checked_add(step_creator).ok_or(AmmError::MathOverflow)?;
+
+        sqrt_price = sqrt_next;
+        if sqrt_next == sqrt_target {
+            let (array_index, tick_index) = match next_tick {
+                Some(found) => found,
+                // reached the edge of the supplied tick arrays (or of the price range) with input left
+                None if remaining_in > 0 => return err!(AmmError::MissingTickArray),
+                None => break,
+            };
+            let liquidity_net = tick_arrays[array_index].ticks[tick_index].cross(fee_growth_global);
+            // moving left crosses the tick downwards, so its net liquidity is removed
+            liquidity = add_liquidity_delta(liquidity, if stack_in { -liquidity_net } else { liquidity_net })?;
+            tick_current = if stack_in { target_tick - 1 } else { target_tick };
+        } else {
+            tick_current = tick_at_sqrt_price(sqrt_next)?;
+        }
+    }
+
+    let amount_out: u64 = amount_out.try_into().map_err(|_| AmmError::MathOverflow)?;
+    require!(amount_out > 0, AmmError::InsufficientLiquidity);
+    let mint_out = if stack_in { &ctx.accounts.quote_mint } else { &ctx.accounts.stack_mint };
+    require!(amount_after_transfer_fee(mint_out, amount_out)? >= min_out, AmmError::SlippageExceeded);
+
+    pool.sqrt_price_x64 = sqrt_price;
+    pool.tick_current = tick_current;
+    pool.liquidity = liquidity;
+    pool.fee_growth_global_stack_x64 = fee_growth_global.0;
+    pool.fee_growth_global_quote_x64 = fee_growth_global.1;
+    let lp_fee: u64 = gross_fee
+        .checked_sub(protocol_fee).ok_or(AmmError::MathOverflow)?
+        .checked_sub(creator_fee).ok_or(AmmError::MathOverflow)?
+        .try_into().map_err(|_| AmmError::MathOverflow)?;
+    let protocol_fee: u64 = protocol_fee.try_into().map_err(|_| AmmError::MathOverflow)?;
+    let creator_fee: u64 = creator_fee.try_into().map_err(|_| AmmError::MathOverflow)?;
+    let fee_mint = if stack_in {
+        pool.protocol_fees_stack = pool.protocol_fees_stack.checked_add(protocol_fee).ok_or(AmmError::MathOverflow)?;
+        pool.creator_fees_stack = pool.creator_fees_stack.checked_add(creator_fee).ok_or(AmmError::MathOverflow)?;
+        pool.stack_mint
+    } else {
+        pool.protocol_fees_quote = pool.protocol_fees_quote.checked_add(protocol_fee).ok_or(AmmError::MathOverflow)?;
+        pool.creator_fees_quote = pool.creator_fees_quote.checked_add(creator_fee).ok_or(AmmError::MathOverflow)?;
+        pool.quote_mint
+    };
+    if creator_fee > 0 {
+        pool.creator_last_claim_ts = Clock::get()?.unix_timestamp;
+    }
+    emit!(SwapFees { pool: pool_key, fee_mint, lp_fee, protocol_fee, creator_fee });
+
+    if stack_in {
+        transfer_from_vault(
+            &ctx.accounts.token_program,
+            &ctx.accounts.quote_vault,
+            &ctx.accounts.user_quote_account,
+            &ctx.accounts.quote_mint,
+            &[],
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            amount_out,
+        )?;
+    } else {
+        transfer_from_vault(
+            &ctx.accounts.stack_token_program,
+            &ctx.accounts.stack_vault,
+            &ctx.accounts.user_stack_account,
+            &ctx.accounts.stack_mint,
+            hook_accounts,
+            &ctx.accounts.vault_authority,
+            pool_key,
+            vault_authority_bump,
+            amount_out,
+        )?;
+    }
+
+    // tick arrays were deserialized by hand, so persist the crossed ticks explicitly
+    for tick_array in tick_arrays.iter() {
+        tick_array.exit(&crate::ID)?;
+    }
+
+    emit!(Swap {
+        pool: pool_key,
+        trader: ctx.accounts.user.key(),
+        side,
+        amount_in: actual_in,
+        amount_out,
+    });
+
+    ctx.accounts.cl_pool.locked = false;
+    Ok(())
+}
+
+/* ---------------------------------------------------
+   ACCOUNTS, CONTEXTS, HELPERS, EVENTS & ERRORS
+   --------------------------------------------------- */
+
+#[derive(Accounts)]
+pub struct InitGlobal<'info> {
+    #[account(init, payer = admin, space = 8 + Global::LEN, seeds = [b"global"], bump)]
+    pub global: Account<'info, Global>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// Treasury: ATA where dust and miscellaneous small balances are forwarded (denominated in quote mint for pools)
+    #[account(mut)]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[account]
+pub struct Global {
+    pub version: u8,
+    pub admin: Pubkey,
+    pub pauser: Pubkey,
+    pub fee_manager: Pubkey,
+    pub governance: Pubkey,
+    pub protocol_fee_bps: u16,
+    pub paused: bool,
+    pub treasury: Pubkey,
+    // configurable safety params
+    pub max_fee_bps: u16,         // maximum allowed fee for pools
+    pub dust_threshold: u64,      // tiny balance sweep threshold
+    pub creator_claim_lock_secs: i64, // timelock
+    pub minimum_liquidity: u64,   // LP locked forever on a pool's first deposit (0 = fallback)
+    pub transfer_hook_programs: Vec<Pubkey>, // Token-2022 transfer-hook programs stack mints may use
+    pub rebalance_hook_programs: Vec<Pubkey>, // programs a stack may install as its rebalance hook
+    pub params_timelock_secs: i64, // delay before queued pool params apply (0 = fallback)
+    // role transfers waiting for `accept_role`
+    pub pending_admin: Option<Pubkey>,
+    pub pending_pauser: Option<Pubkey>,
+    pub pending_fee_manager: Option<Pubkey>,
+    pub pending_governance: Option<Pubkey>,
+    pub pause_flags: u8, // `PAUSE_*` operations paused protocol-wide (on top of `paused`)
+}
+impl Global {
+    const LEN: usize = 1 + 32*4 + 2 + 1 + 32 + 2 + 8 + 8 + 8 + (4 + 32 * MAX_TRANSFER_HOOK_PROGRAMS) + (4 + 32 * MAX_REBALANCE_HOOK_PROGRAMS) + 8
+        + 4 * (1 + 32) + 1;
+
+    /// `op` (a `PAUSE_*` flag) is stopped protocol-wide
+    fn is_paused(&self, op: u8) -> bool {
+        self.paused || self.pause_flags & op != 0
+    }
+
+    /// `caller` may pause or resume, and `flags` is a non-empty set of the `allowed` `PAUSE_*` bits
+    fn check_pause_update(&self, caller: Pubkey, flags: u8, allowed: u8) -> Result<()> {
+        require!(caller == self.admin || caller == self.pauser || caller == self.governance, AmmError::Unauthorized);
+        require!(flags != 0 && flags & !allowed == 0, AmmError::InvalidPauseFlags);
+        Ok(())
+    }
+
+    fn role_holder(&mut self, role: GlobalRole) -> &mut Pubkey {
+        match role {
+            GlobalRole::Admin => &mut self.admin,
+            GlobalRole::Pauser => &mut self.pauser,
+            GlobalRole::FeeManager => &mut self.fee_manager,
+            GlobalRole::Governance => &mut self.governance,
+        }
+    }
+
+    fn pending_role_holder(&mut self, role: GlobalRole) -> &mut Option<Pubkey> {
+        match role {
+            GlobalRole::Admin => &mut self.pending_admin,
+            GlobalRole::Pauser => &mut self.pending_pauser,
+            GlobalRole::FeeManager => &mut self.pending_fee_manager,
+            GlobalRole::Governance => &mut self.pending_governance,
+        }
+    }
+}
+
+/// Privileged role on `Global`, rotated with `propose_role` / `accept_role`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GlobalRole {
+    Admin,
+    Pauser,
+    FeeManager,
+    Governance,
+}
+
+#[derive(Accounts)]
+pub struct RegisterStack<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(mut)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    /// Mint authority PDA for the stack mint
+    #[account(seeds = [b"stack_mint_auth", stack_mint.key().as_ref()], bump)]
+    pub stack_mint_auth: UncheckedAccount<'info>,
+    #[account(init, payer = creator, space = 8 + StackInfo::LEN, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+    /// read for the transfer-hook program allowlist
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+#[account]
+pub struct StackInfo {
+    pub version: u8,
+    pub creator: Pubkey,
+    pub stack_mint: Pubkey,
+    pub creator_fee_bps: u16,
+    pub rebalance_hook: Option<Pubkey>,
+    pub bump: u8,
+    pub mint_auth_bump: u8, // bump of the stack_mint_auth PDA that signs mint_to
+    pub launch: Option<BondingCurve>, // bonding-curve launch, if one was started
+    pub rebalance_threshold_bps: u16, // hook runs on trades whose input is at least this share of the input reserve
+    pub pending_rebalance_hook: Option<PendingRebalanceHook>,
+    pub supply_limits: SupplyLimits,
+    pub mint_window: RateWindow, // stack minted by `mint_stack_via_pool`, bucketed by `supply_limits.window_secs`
+    pub redeem_window: RateWindow, // stack burned by `redeem_stack_via_pool`
+    pub backing: Option<Backing>, // NAV issuance reserve, once `init_backing` ran
+    pub pending_rebalance: Option<RebalanceHookArgs>, // last qualifying trade, until `execute_rebalance_hook` runs
+}
+impl StackInfo {
+    const LEN: usize = 1 + 32 + 32 + 2 + (1+32) + 1 + 1 + (1 + BondingCurve::LEN) + 2 + (1 + PendingRebalanceHook::LEN)
+        + SupplyLimits::LEN + 2 * RateWindow::LEN + (1 + Backing::LEN) + (1 + RebalanceHookArgs::LEN);
+
+    /// Check a `mint_stack_via_pool` of `amount` on top of `supply` against the limits and count it in the window
+    fn record_mint(&mut self, amount: u64, supply: u64, now: i64) -> Result<()> {
+        let limits = self.supply_limits;
+        if limits.max_supply > 0 {
+            let new_supply = supply.checked_add(amount).ok_or(AmmError::MathOverflow)?;
+            require!(new_supply <= limits.max_supply, AmmError::SupplyCapExceeded);
+        }
+        require!(limits.max_mint_per_tx == 0 || amount <= limits.max_mint_per_tx, AmmError::MintLimitExceeded);
+        let used = self.mint_window.record(now, limits.window_secs, amount)?;
+        require!(limits.mint_per_window == 0 || used <= limits.mint_per_window, AmmError::MintRateLimited);
+        if limits.window_secs > 0 {
+            emit!(SupplyWindowUsage { stack_mint: self.stack_mint, is_mint: true, amount, window_used: used, window_limit: limits.mint_per_window });
+        }
+        Ok(())
+    }
+
+    /// Check a `redeem_stack_via_pool` of `amount` against the limits and count it in the window
+    fn record_redeem(&mut self, amount: u64, now: i64) -> Result<()> {
+        let limits = self.supply_limits;
+        require!(limits.max_redeem_per_tx == 0 || amount <= limits.max_redeem_per_tx, AmmError::RedeemLimitExceeded);
+        let used = self.redeem_window.record(now, limits.window_secs, amount)?;
+        require!(limits.redeem_per_window == 0 || used <= limits.redeem_per_window, AmmError::RedeemRateLimited);
+        if limits.window_secs > 0 {
+            emit!(SupplyWindowUsage { stack_mint: self.stack_mint, is_mint: false, amount, window_used: used, window_limit: limits.redeem_per_window });
+        }
+        Ok(())
+    }
+}
+
+/// Primary-issuance reserve held in `StackInfo.backing`. Invariant: `reserve` (quote) backs `backed_supply`
+/// (stack) at NAV = reserve / backed_supply; mints add both at NAV, redeems remove both at NAV, and every
+/// rounding and fee remainder stays in the reserve, so NAV never decreases.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct Backing {
+    pub quote_mint: Pubkey,
+    pub vault: Pubkey, // quote token account owned by the ["vault_authority", stack_info] PDA
+    pub vault_authority_bump: u8,
+    pub initial_nav_wad: u128, // quote per stack (native units, WAD) while backed_supply is 0
+    pub fee_bps: u16,
+    pub reserve: u64, // quote accounted to the reserve (donations to the vault are ignored)
+    pub backed_supply: u64, // stack minted against the reserve and not yet redeemed
+}
+impl Backing { const LEN: usize = 32 + 32 + 1 + 16 + 2 + 8 + 8; }
+
+#[derive(Accounts)]
+pub struct InitBacking<'info> {
+    pub creator: Signer<'info>,
+    #[account(mut, seeds=[b"stack_info", stack_info.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    #[account(constraint = backing_vault.mint == quote_mint.key() @ AmmError::InvalidVaultMint)]
+    pub backing_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/// Per-stack bounds on `mint_stack_via_pool` / `redeem_stack_via_pool`, in native stack units; 0 disables a limit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct SupplyLimits {
+    pub max_supply: u64, // ceiling on the mint's total supply after a mint
+    pub max_mint_per_tx: u64,
+    pub max_redeem_per_tx: u64,
+    pub window_secs: u32, // length of the sliding window for the two limits below
+    pub mint_per_window: u64,
+    pub redeem_per_window: u64,
+}
+impl SupplyLimits { const LEN: usize = 8 + 8 + 8 + 4 + 8 + 8; }
+
+/// Sliding-window counter: two fixed buckets of `window_secs`, with the previous bucket counted in proportion
+/// to its overlap with the window ending now
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct RateWindow {
+    pub start_ts: i64, // start of the current bucket
+    pub current: u64,
+    pub previous: u64,
+}
+impl RateWindow {
+    const LEN: usize = 8 + 8 + 8;
+
+    /// Add `amount` at `now` and return the sliding total including it (just `amount` when `window_secs` is 0)
+    fn record(&mut self, now: i64, window_secs: u32, amount: u64) -> Result<u64> {
+        if window_secs == 0 {
+            return Ok(amount);
+        }
+        let window = window_secs as i64;
+        let elapsed = now.saturating_sub(self.start_ts).max(0);
+        if elapsed >= 2 * window {
+            // idle for two buckets or more (or never used): start over, aligned to the window grid
+            self.previous = 0;
+            self.current = 0;
+            self.start_ts = now - now.rem_euclid(window);
+        } else if elapsed >= window {
+            self.previous = self.current;
+            self.current = 0;
+            self.start_ts += window;
+        }
+        self.current = self.current.saturating_add(amount);
+
+        let overlap = (window - now.saturating_sub(self.start_ts).clamp(0, window)) as u128;
+        let weighted_previous = (self.previous as u128)
+            .checked_mul(overlap).ok_or(AmmError::MathOverflow)?
+            .checked_div(window as u128).ok_or(AmmError::MathOverflow)?;
+        Ok(u64::try_from(weighted_previous + self.current as u128).unwrap_or(u64::MAX))
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetSupplyLimits<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, seeds=[b"stack_info", stack_info.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/// Rebalance-hook change queued by `queue_rebalance_hook`, applicable from `eta`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PendingRebalanceHook {
+    pub hook: Option<Pubkey>,
+    pub threshold_bps: u16,
+    pub eta: i64,
+}
+impl PendingRebalanceHook { const LEN: usize = (1 + 32) + 2 + 8; }
+
+#[derive(Accounts)]
+pub struct SetRebalanceHook<'info> {
+    pub creator: Signer<'info>,
+    #[account(mut, seeds=[b"stack_info", stack_info.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    /// read for the rebalance-hook program allowlist
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/// Trade that triggered a rebalance hook
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RebalanceTrade { StackToQuote, QuoteToStack, MintViaPool, RedeemViaPool }
+
+/// Instruction data of the rebalance-hook CPI, after `REBALANCE_HOOK_IX_DISCRIMINATOR`. The hook receives exactly
+/// four accounts: the `["rebalance_hook_signer", stack_info]` PDA of this program (signer, proves the call came
+/// from the AMM), the pool and stack info (read-only, pre-trade data), and the hook's own
+/// `["rebalance_hook", stack_info]` PDA (writable). Amounts are native units; reserves are after the trade.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct RebalanceHookArgs {
+    pub pool: Pubkey,
+    pub stack_mint: Pubkey,
+    pub trader: Pubkey,
+    pub trade: RebalanceTrade,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub reserve_stack: u64,
+    pub reserve_quote: u64,
+    pub stack_supply: u64,
+    pub backing_reserve: u64, // NAV issuance reserve and the stack it backs (0 without `init_backing`)
+    pub backed_supply: u64,
+}
+impl RebalanceHookArgs { const LEN: usize = 32 * 3 + 1 + 8 * 7; }
+
+#[derive(Accounts)]
+pub struct ExecuteRebalanceHook<'info> {
+    #[account(mut, seeds=[b"stack_info", stack_info.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    /// pool the pending request was recorded on
+    pub pool: Account<'info, Pool>,
+    /// read for the rebalance-hook program allowlist
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    /// CHECK: must be `stack_info.rebalance_hook` (checked in the handler)
+    pub hook_program: UncheckedAccount<'info>,
+    /// CHECK: PDA of this program that signs the hook CPI; holds nothing
+    #[account(seeds=[b"rebalance_hook_signer", stack_info.key().as_ref()], bump)]
+    pub hook_signer: UncheckedAccount<'info>,
+    /// CHECK: the hook's `["rebalance_hook", stack_info]` PDA under `hook_program` (checked in the handler)
+    #[account(mut)]
+    pub hook_state: UncheckedAccount<'info>,
+}
+
+/// Launch price curve: `Linear` p(s) = p0 * (1 + s / scale), `Exponential` p(s) = p0 * e^(s / scale),
+/// with s the stack sold and p in quote per stack (native units)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BondingCurveKind { Linear, Exponential }
+
+/// Bonding-curve launch state held in `StackInfo.launch`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct BondingCurve {
+    pub kind: BondingCurveKind,
+    pub pool: Pubkey, // seeded with the raised quote at graduation
+    pub quote_vault: Pubkey, // launch reserve, owned by the ["vault_authority", stack_info] PDA
+    pub vault_authority_bump: u8,
+    pub start_price_wad: u128, // p0, WAD-scaled
+    pub curve_scale: u64, // stack sold per doubling (linear) / e-fold (exponential) of the price
+    pub graduation_market_cap: u64, // quote native units
+    pub sold: u64,
+    pub reserve_quote: u64,
+    pub graduated: bool,
+}
+impl BondingCurve { const LEN: usize = 1 + 32 + 32 + 1 + 16 + 8 + 8 + 8 + 8 + 1; }
+
+#[derive(Accounts)]
+pub struct StartLaunch<'info> {
+    pub creator: Signer<'info>,
+    #[account(mut, seeds=[b"stack_info", stack_info.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    /// pool seeded at graduation (paused until then)
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    /// quote token account owned by the launch authority PDA; holds the raised quote
+    pub launch_vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct BuyLaunch<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    #[account(mut)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"stack_mint_auth", stack_mint.key().as_ref()], bump)]
+    pub stack_mint_auth: UncheckedAccount<'info>,
+    /// launch reserve (must match `stack_info.launch`)
+    #[account(mut)]
+    pub launch_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"vault_authority", stack_info.key().as_ref()], bump)]
+    pub launch_authority: UncheckedAccount<'info>,
+
+    // graduation: the launch pool and everything needed to seed it
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub pool_stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub pool_quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.lp_mint @ AmmError::InvalidVaultMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, seeds=[b"locked_lp", pool.key().as_ref()], bump)]
+    pub locked_lp: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+    #[account(mut, seeds=[b"observations", pool.key().as_ref()], bump)]
+    pub observations: Account<'info, PoolObservations>,
+
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+impl<'info> BuyLaunch<'info> {
+    /// Mint stack to `to` (stack_mint_auth PDA signs)
+    fn mint_stack(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        token_interface::mint_to(
+            CpiContext::new(
+                self.stack_token_program.to_account_info(),
+                MintTo {
+                    mint: self.stack_mint.to_account_info(),
+                    to,
+                    authority: self.stack_mint_auth.to_account_info(),
+                },
+            )
+            .with_signer(&[&[b"stack_mint_auth", self.stack_mint.key().as_ref(), &[self.stack_info.mint_auth_bump]]]),
+            amount,
+        )
+    }
+
+    /// Graduate: move the raised quote into the pool with stack minted at the final curve price (so the
+    /// pool opens where the curve stopped), lock the whole initial LP and unpause the pool
+    fn graduate(&mut self, curve: &BondingCurve) -> Result<()> {
+        let pool_key = self.pool.key();
+        let price_wad = launch_price_wad(curve, curve.sold as u128)?;
+        let stack_seed: u64 = mul_div_wide(curve.reserve_quote as u128, WAD, price_wad)?
+            .try_into().map_err(|_| AmmError::MathOverflow)?;
+        require!(stack_seed > 0, AmmError::ZeroLpMint);
+
+        let stack_decimals = self.stack_mint.decimals;
+        let quote_decimals = self.quote_mint.decimals;
+        let target_decimals = self.pool.decimal_normalize_to;
+        let stack_vault_before = self.pool_stack_vault.amount;
+        let quote_vault_before = self.pool_quote_vault.amount;
+        update_price_accumulators(
+            &mut self.pool,
+            &mut self.observations,
+            normalize_amount_u128(stack_vault_before, stack_decimals, target_decimals)?,
+            normalize_amount_u128(quote_vault_before, quote_decimals, target_decimals)?,
+        )?;
+
+        transfer_from_vault(
+            &self.token_program,
+            &self.launch_vault,
+            &self.pool_quote_vault,
+            &self.quote_mint,
+            &[],
+            &self.launch_authority,
+            self.stack_info.key(),
+            curve.vault_authority_bump,
+            curve.reserve_quote,
+        )?;
+        self.mint_stack(self.pool_stack_vault.to_account_info(), stack_seed)?;
+
+        // LP from what actually arrived (the quote leg may carry a transfer fee)
+        self.pool_stack_vault.reload()?;
+        self.pool_quote_vault.reload()?;
+        let seeded_stack = self.pool_stack_vault.amount.checked_sub(stack_vault_before).ok_or(AmmError::MathOverflow)?;
+        let seeded_quote = self.pool_quote_vault.amount.checked_sub(quote_vault_before).ok_or(AmmError::MathOverflow)?;
+        let lp_u128 = lp_for_deposit(
+            &self.pool,
+            normalize_amount_u128(seeded_stack, stack_decimals, target_decimals)?,
+            normalize_amount_u128(seeded_quote, quote_decimals, target_decimals)?,
+            0,
+            0,
+            0,
+        )?;
+        require!(lp_u128 > 0, AmmError::ZeroLpMint);
+        let lp_locked: u64 = lp_u128.try_into().map_err(|_| AmmError::MathOverflow)?;
+        token_interface::mint_to(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                MintTo {
+                    mint: self.lp_mint.to_account_info(),
+                    to: self.locked_lp.to_account_info(),
+                    authority: self.pool_vault_authority.to_account_info(),
+                },
+            )
+            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[self.pool.vault_authority_bump]]]),
+            lp_locked,
+        )?;
+
+        let pool = &mut self.pool;
+        pool.total_lp_supply = pool.total_lp_supply.checked_add(lp_u128).ok_or(AmmError::MathOverflow)?;
+        pool.paused = false;
+        emit!(LaunchGraduated {
+            stack_mint: self.stack_mint.key(),
+            pool: pool_key,
+            quote_seeded: seeded_quote,
+            stack_seeded: seeded_stack,
+            lp_locked,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SellLaunch<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    #[account(mut)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+    /// launch reserve (must match `stack_info.launch`)
+    #[account(mut, constraint = launch_vault.mint == quote_mint.key() @ AmmError::InvalidVaultMint)]
+    pub launch_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"vault_authority", stack_info.key().as_ref()], bump)]
+    pub launch_authority: UncheckedAccount<'info>,
+    /// the launch pool (must match `stack_info.launch`), read for its pause flags
+    pub pool: Account<'info, Pool>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+#[derive(Accounts)]
+pub struct CreatePool<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(mut, mint::token_program = stack_token_program)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, mint::token_program = token_program)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// LP mint
+    #[account(mut, mint::token_program = token_program)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(init, payer = creator, space = 8 + Pool::LEN, seeds=[b"pool", stack_mint.key().as_ref(), quote_mint.key().as_ref()], bump)]
+    pub pool: Account<'info, Pool>,
+
+    /// TWAP observation ring buffer for this pool
+    #[account(init, payer = creator, space = 8 + PoolObservations::LEN, seeds=[b"observations", pool.key().as_ref()], bump)]
+    pub observations: Account<'info, PoolObservations>,
+
+    /// Dead LP account receiving the minimum liquidity; it is its own authority and the program never signs for it
+    #[account(
+        init,
+        payer = creator,
+        seeds = [b"locked_lp", pool.key().as_ref()],
+        bump,
+        token::mint = lp_mint,
+        token::authority = locked_lp,
+        token::token_program = token_program,
+    )]
+    pub locked_lp: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+
+    /// vault authority PDA
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// oracle price account read on every swap; decoded according to the `oracle_source` argument
+    #[account(mut)]
+    pub oracle: UncheckedAccount<'info>,
+
+    /// program owning the stack mint (SPL Token or Token-2022); `token_program` owns the quote & LP mints
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+#[account]
+pub struct Pool {
+    pub version: u8,
+    pub stack_mint: Pubkey,
+    pub quote_mint: Pubkey,
     pub lp_mint: Pubkey,
+    pub stack_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub fee_bps: u16,
+    pub k: u128,
+    pub bump: u8,
+    pub vault_authority_bump: u8, // signs every vault transfer
+    pub paused: bool,
+    pub total_lp_supply: u128,
+    pub locked: bool,
+    pub decimal_normalize_to: u8,
+    pub treasury: Pubkey,
+    pub oracle: Pubkey,
+    pub oracle_source: OracleSource,
+    pub oracle_max_staleness_secs: i64,
+    pub oracle_max_conf_bps: u16,
+    // protocol/creator fee shares accrued in the swap input token (native units); they stay in the
+    // trading vaults until withdrawn/claimed and are excluded from the reserves
+    pub protocol_fees_stack: u64,
+    pub protocol_fees_quote: u64,
+    pub creator_fees_stack: u64,
+    pub creator_fees_quote: u64,
+    pub creator_last_claim_ts: i64,
+    pub max_price_deviation_bps: u16,
+    pub last_governance_nonce: u128, // replay-protection for governance approvals
+    // Uniswap-v2 style cumulative prices (price_x * seconds, wrapping), scaled like `view_mid_price`
+    pub price_cumulative_quote_per_stack: u128,
+    pub price_cumulative_stack_per_quote: u128,
+    pub last_price_update_ts: i64,
+    // outstanding flash loan (native units) and the trading reserves it was taken against;
+    // all zero outside a borrow/repay pair
+    pub flash_loan_stack: u64,
+    pub flash_loan_quote: u64,
+    pub flash_reserve_stack: u64,
+    pub flash_reserve_quote: u64,
+    // pricing curve; StableSwap amp ramps linearly from `amp_initial` to `amp_target` over the ramp window
+    pub curve_type: CurveType,
+    pub amp_initial: u64,
+    pub amp_target: u64,
+    pub amp_ramp_start_ts: i64,
+    pub amp_ramp_end_ts: i64,
+    // share of pool value on the stack side (bps); the quote side holds the rest. 50/50 unless Weighted
+    pub stack_weight_bps: u16,
+    pub pause_flags: u8, // `PAUSE_*` operations paused on this pool by `pause_pool`
+}
+impl Pool {
+    // rough size calc; adjust if you expand fields
+    const LEN: usize = 1 + 32*6 + 2 + 16 + 1 + 1 + 1 + 4 + 32 + 32 + 32 + 16 + 8 + 2 + 16 + (1 + 8 + 2) + (16 + 16 + 8) + 8 * 4 + 32 * 2 + 8 * 4 + (1 + 8 * 4) + 2 + 1;
+
+    /// `op` (a `PAUSE_*` flag) is stopped on this pool, by `pause_pool` or a running launch
+    fn is_paused(&self, op: u8) -> bool {
+        self.paused || self.pause_flags & op != 0
+    }
+
+    /// (input, output) curve weights in bps for a trade selling stack when `stack_in`
+    fn weights_bps(&self, stack_in: bool) -> (u128, u128) {
+        let stack_weight = self.stack_weight_bps as u128;
+        let quote_weight = BPS_DENOM - stack_weight;
+        if stack_in { (stack_weight, quote_weight) } else { (quote_weight, stack_weight) }
+    }
+
+    /// protocol + creator fees accrued in the stack vault
+    fn accrued_fees_stack(&self) -> Result<u64> {
+        Ok(self.protocol_fees_stack.checked_add(self.creator_fees_stack).ok_or(AmmError::MathOverflow)?)
+    }
+
+    /// protocol + creator fees accrued in the quote vault
+    fn accrued_fees_quote(&self) -> Result<u64> {
+        Ok(self.protocol_fees_quote.checked_add(self.creator_fees_quote).ok_or(AmmError::MathOverflow)?)
+    }
+}
+
+/* PROVIDE LIQUIDITY CONTEXT */
+#[derive(Accounts)]
+pub struct ProvideLiquidity<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut, address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// vault token accounts (owned by vault_authority PDA)
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+
+    #[account(mut, address = pool.lp_mint @ AmmError::InvalidVaultMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub user_lp_account: InterfaceAccount<'info, TokenAccount>,
+    /// dead LP account that receives the minimum liquidity on the first deposit
+    #[account(mut, seeds=[b"locked_lp", pool.key().as_ref()], bump)]
+    pub locked_lp: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// treasury ATA (quote-mint) where small dust amounts are swept (must be `pool.treasury`)
+    #[account(mut, address = pool.treasury @ AmmError::InvalidTreasury)]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// vault authority PDA
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// TWAP observation ring buffer
+    #[account(mut, seeds=[b"observations", pool.key().as_ref()], bump)]
+    pub observations: Account<'info, PoolObservations>,
+
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/* REMOVE LIQUIDITY CONTEXT */
+#[derive(Accounts)]
+pub struct RemoveLiquidity<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, address = pool.lp_mint @ AmmError::InvalidVaultMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub user_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    // add mint accounts so we can read decimals
+    #[account(mut, address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// treasury ATA for dust (must be `pool.treasury`)
+    #[account(mut, address = pool.treasury @ AmmError::InvalidTreasury)]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// TWAP observation ring buffer
+    #[account(mut, seeds=[b"observations", pool.key().as_ref()], bump)]
+    pub observations: Account<'info, PoolObservations>,
+
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/* SINGLE-SIDED (ZAP) LIQUIDITY CONTEXT — shared by provide/remove_liquidity_single_sided */
+#[derive(Accounts)]
+pub struct ZapLiquidity<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = pool.lp_mint @ AmmError::InvalidVaultMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub user_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// treasury ATA for dust sweeps (must be `pool.treasury`)
+    #[account(mut, address = pool.treasury @ AmmError::InvalidTreasury)]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// creator fee on the internal swap leg
+    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+
+    /// TWAP observation ring buffer
+    #[account(mut, seeds=[b"observations", pool.key().as_ref()], bump)]
+    pub observations: Account<'info, PoolObservations>,
+
+    /// pool oracle (must match `pool.oracle`)
+    #[account(address = pool.oracle @ AmmError::InvalidOracle)]
+    pub oracle: UncheckedAccount<'info>,
+
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/* FLASH LOAN CONTEXTS — vaults are pinned to the addresses recorded on the pool */
+#[derive(Accounts)]
+pub struct FlashBorrow<'info> {
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// borrower accounts receiving the loan
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// instructions sysvar, scanned for the matching `flash_repay`
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    #[account(address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub global: Account<'info, Global>,
+}
+
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    pub user: Signer<'info>,
+
+    // must stay at index 1: `flash_borrow` matches the repay instruction on this account
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// repayer accounts funding principal + fee
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// creator share of the flash fee
+    #[account(seeds=[b"stack_info", pool.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+
+    #[account(address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub global: Account<'info, Global>,
+}
+
+/* MULTI-HOP ROUTER CONTEXT — per-hop pool accounts arrive via remaining_accounts */
+#[derive(Accounts)]
+pub struct RouteSwap<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// user token account for the route's input mint
+    #[account(mut)]
+    pub user_source: InterfaceAccount<'info, TokenAccount>,
+    /// user token account for the route's output mint
+    #[account(mut)]
+    pub user_destination: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/// Whether an account holds a StackMint `Pool` (used to find where `route_swap` hops end)
+fn is_pool_account(info: &AccountInfo) -> bool {
+    *info.owner == crate::ID
+        && info
+            .try_borrow_data()
+            .map(|data| data.starts_with(&<Pool as anchor_lang::Discriminator>::DISCRIMINATOR))
+            .unwrap_or(false)
+}
+
+/// One validated `route_swap` hop, deserialized from a ROUTE_ACCOUNTS_PER_HOP slice of remaining_accounts
+struct RouteHop<'info> {
+    pool: Account<'info, Pool>,
+    stack_mint: InterfaceAccount<'info, Mint>,
+    quote_mint: InterfaceAccount<'info, Mint>,
+    stack_vault: InterfaceAccount<'info, TokenAccount>,
+    quote_vault: InterfaceAccount<'info, TokenAccount>,
+    vault_authority: AccountInfo<'info>,
+    stack_info: Account<'info, StackInfo>,
+    observations: Account<'info, PoolObservations>,
+    oracle: AccountInfo<'info>,
+    stack_token_program: Interface<'info, TokenInterface>,
+    quote_token_program: Interface<'info, TokenInterface>,
+    side: SwapDirection,
+}
+
+impl<'info> RouteHop<'info> {
+    /// Deserialize one hop and check it is a genuine StackMint pool trading `input_mint`
+    fn load(accounts: &'info [AccountInfo<'info>], input_mint: Pubkey) -> Result<Self> {
+        let pool: Account<'info, Pool> = Account::try_from(&accounts[0])?;
+        let pool_key = pool.key();
+        require!(!pool.is_paused(PAUSE_SWAPS), AmmError::PoolPaused);
+        require!(!pool.locked, AmmError::Reentrancy);
+
+        // pool must be the canonical PDA for its mint pair
+        let expected_pool = Pubkey::create_program_address(
+            &[b"pool", pool.stack_mint.as_ref(), pool.quote_mint.as_ref(), &[pool.bump]],
+            &crate::ID,
+        ).map_err(|_| AmmError::InvalidRoute)?;
+        require_keys_eq!(expected_pool, pool_key, AmmError::InvalidRoute);
+
+        let stack_mint: InterfaceAccount<'info, Mint> = InterfaceAccount::try_from(&accounts[1])?;
+        let quote_mint: InterfaceAccount<'info, Mint> = InterfaceAccount::try_from(&accounts[2])?;
+        require_keys_eq!(stack_mint.key(), pool.stack_mint, AmmError::InvalidVaultMint);
+        require_keys_eq!(quote_mint.key(), pool.quote_mint, AmmError::InvalidVaultMint);
+
+        // vaults must be the ones bound to the pool at creation, owned by its vault_authority PDA
+        let stack_vault: InterfaceAccount<'info, TokenAccount> = InterfaceAccount::try_from(&accounts[3])?;
+        let quote_vault: InterfaceAccount<'info, TokenAccount> = InterfaceAccount::try_from(&accounts[4])?;
+        require_keys_eq!(stack_vault.key(), pool.stack_vault, AmmError::InvalidVaultOwner);
+        require_keys_eq!(quote_vault.key(), pool.quote_vault, AmmError::InvalidVaultOwner);
+        let expected_vault_auth = Pubkey::create_program_address(
+            &[b"vault_authority", pool_key.as_ref(), &[pool.vault_authority_bump]],
+            &crate::ID,
+        ).map_err(|_| AmmError::InvalidVaultOwner)?;
+        let vault_authority = accounts[5].clone();
+        require_keys_eq!(vault_authority.key(), expected_vault_auth, AmmError::InvalidVaultOwner);
+        require_keys_eq!(stack_vault.owner, expected_vault_auth, AmmError::InvalidVaultOwner);
+        require_keys_eq!(quote_vault.owner, expected_vault_auth, AmmError::InvalidVaultOwner);
+
+        let stack_info: Account<'info, StackInfo> = Account::try_from(&accounts[6])?;
+        require_keys_eq!(stack_info.stack_mint, pool.stack_mint, AmmError::InvalidRoute);
+        let observations: Account<'info, PoolObservations> = Account::try_from(&accounts[7])?;
+        require_keys_eq!(observations.pool, pool_key, AmmError::InvalidRoute);
+        let oracle = accounts[8].clone();
+        require_keys_eq!(oracle.key(), pool.oracle, AmmError::InvalidOracle);
+
+        // token programs (SPL Token or Token-2022) owning each mint
+        let stack_token_program: Interface<'info, TokenInterface> = Interface::try_from(&accounts[9])?;
+        let quote_token_program: Interface<'info, TokenInterface> = Interface::try_from(&accounts[10])?;
+        require_keys_eq!(*stack_mint.to_account_info().owner, stack_token_program.key(), AmmError::InvalidRoute);
+        require_keys_eq!(*quote_mint.to_account_info().owner, quote_token_program.key(), AmmError::InvalidRoute);
+
+        let side = if input_mint == pool.stack_mint {
+            SwapDirection::StackToQuote
+        } else if input_mint == pool.quote_mint {
+            SwapDirection::QuoteToStack
+        } else {
+            return Err(AmmError::InvalidRoute.into());
+        };
+
+        Ok(Self {
+            pool,
+            stack_mint,
+            quote_mint,
+            stack_vault,
+            quote_vault,
+            vault_authority,
+            stack_info,
+            observations,
+            oracle,
+            stack_token_program,
+            quote_token_program,
+            side,
+        })
+    }
+
+    fn output_mint(&self) -> Pubkey {
+        match self.side {
+            SwapDirection::StackToQuote => self.pool.quote_mint,
+            SwapDirection::QuoteToStack => self.pool.stack_mint,
+        }
+    }
+
+    /// (token program, mint) of the token this hop takes in
+    fn input_token(&self) -> (&Interface<'info, TokenInterface>, &InterfaceAccount<'info, Mint>) {
+        match self.side {
+            SwapDirection::StackToQuote => (&self.stack_token_program, &self.stack_mint),
+            SwapDirection::QuoteToStack => (&self.quote_token_program, &self.quote_mint),
+        }
+    }
+
+    /// Pay `amount` of this hop's output token from its vault to `to` (vault PDA signs)
+    fn pay_out(&self, to: &InterfaceAccount<'info, TokenAccount>, hook_accounts: &[AccountInfo<'info>], amount: u64) -> Result<()> {
+        let (program, vault, mint) = match self.side {
+            SwapDirection::StackToQuote => (&self.quote_token_program, &self.quote_vault, &self.quote_mint),
+            SwapDirection::QuoteToStack => (&self.stack_token_program, &self.stack_vault, &self.stack_mint),
+        };
+        transfer_from_vault(program, vault, to, mint, hook_accounts, &self.vault_authority, self.pool.key(), self.pool.vault_authority_bump, amount)
+    }
+
+    /// Net amount arriving downstream of this hop's output transfer
+    fn output_after_transfer_fee(&self, amount: u64) -> Result<u64> {
+        match self.side {
+            SwapDirection::StackToQuote => amount_after_transfer_fee(&self.quote_mint, amount),
+            SwapDirection::QuoteToStack => amount_after_transfer_fee(&self.stack_mint, amount),
+        }
+    }
+}
+
+/* SWAP CONTEXTS — separated directions for safety */
+
+/* SWAP Stack -> Quote */
+#[derive(Accounts)]
+pub struct SwapStackToQuote<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+
+
+    /// treasury ATA for dust sweeps (must be `pool.treasury`)
+    #[account(mut, address = pool.treasury @ AmmError::InvalidTreasury)]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+
+    /// TWAP observation ring buffer
+    #[account(mut, seeds=[b"observations", pool.key().as_ref()], bump)]
+    pub observations: Account<'info, PoolObservations>,
+
+    /// pool oracle (must match `pool.oracle`)
+    #[account(address = pool.oracle @ AmmError::InvalidOracle)]
+    pub oracle: UncheckedAccount<'info>,
+
+    /// optional governance approval PDA written by `execute_proposal`; closed when used
+    #[account(mut)]
+    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
+    /// CHECK: receives the approval's rent; must be its `rent_payer`
+    #[account(mut)]
+    pub approval_rent_receiver: Option<UncheckedAccount<'info>>,
+
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/* SWAP Quote -> Stack */
+#[derive(Accounts)]
+pub struct SwapQuoteToStack<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+
+
+    /// treasury ATA for dust sweeps (must be `pool.treasury`)
+    #[account(mut, address = pool.treasury @ AmmError::InvalidTreasury)]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+
+    /// TWAP observation ring buffer
+    #[account(mut, seeds=[b"observations", pool.key().as_ref()], bump)]
+    pub observations: Account<'info, PoolObservations>,
+
+    /// pool oracle (must match `pool.oracle`)
+    #[account(address = pool.oracle @ AmmError::InvalidOracle)]
+    pub oracle: UncheckedAccount<'info>,
+
+    /// optional governance approval PDA; closed when used
+    #[account(mut)]
+    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
+    /// CHECK: receives the approval's rent; must be its `rent_payer`
+    #[account(mut)]
+    pub approval_rent_receiver: Option<UncheckedAccount<'info>>,
+
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/* MintStackViaPool & RedeemStackViaPool contexts — primary issuance against the stack's backing reserve.
+   The pool and its vaults are read-only: they are what the rebalance hook is told about. */
+#[derive(Accounts)]
+pub struct MintStackViaPool<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub pool: Account<'info, Pool>,
+    #[account(mut, address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    /// backing reserve (`stack_info.backing.vault`)
+    #[account(mut)]
+    pub backing_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"stack_mint_auth", stack_mint.key().as_ref()], bump)]
+    pub stack_mint_auth: UncheckedAccount<'info>,
+    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub global: Account<'info, Global>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemStackViaPool<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub pool: Account<'info, Pool>,
+    #[account(mut, address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    /// backing reserve (`stack_info.backing.vault`)
+    #[account(mut)]
+    pub backing_vault: InterfaceAccount<'info, TokenAccount>,
+    /// owner of the backing vault
+    #[account(seeds=[b"vault_authority", stack_info.key().as_ref()], bump)]
+    pub backing_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub global: Account<'info, Global>,
+}
+
+/* Claim Creator Fees */
+#[derive(Accounts)]
+pub struct ClaimCreatorFees<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut, seeds=[b"stack_info", pool.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    /// trading vaults holding the accrued creator fees
+    #[account(mut, constraint = stack_vault.mint == pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, constraint = quote_vault.mint == pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub creator_stack_receiver: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub creator_quote_receiver: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>, // <-- ADDED global so we can read lock secs
+}
+
+/* Admin/governance update of protocol-wide config held in Global */
+#[derive(Accounts)]
+pub struct UpdateGlobal<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptRole<'info> {
+    pub new_holder: Signer<'info>,
+    #[account(mut, seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+#[derive(Accounts)]
+pub struct SetGlobalParams<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    /// optional new treasury token account
+    pub treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+/* Pause/Resume context used for both emergency_pause and resume */
+#[derive(Accounts)]
+pub struct PauseResume<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut, seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+#[derive(Accounts)]
+pub struct PausePool<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+}
+
+#[derive(Accounts)]
+pub struct PauseClPool<'info> {
+    pub admin: Signer<'info>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    #[account(mut)]
+    pub cl_pool: Account<'info, ClPool>,
+}
+
+/* Emergency withdraw context uses same accounts as RemoveLiquidity but allows even when paused */
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    #[account(mut, address = pool.lp_mint @ AmmError::InvalidVaultMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub user_lp_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/* Withdraw protocol fees by admin/fee_manager/governance */
+#[derive(Accounts)]
+pub struct WithdrawProtocolFees<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    /// trading vaults holding the accrued protocol fees
+    #[account(mut, constraint = stack_vault.mint == pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, constraint = quote_vault.mint == pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub admin_stack_receiver: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub admin_quote_receiver: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault_authority: UncheckedAccount<'info>,
+    /// optional governance approval PDA; closed when used
+    #[account(mut)]
+    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
+    /// CHECK: receives the approval's rent; must be its `rent_payer`
+    #[account(mut)]
+    pub approval_rent_receiver: Option<UncheckedAccount<'info>>,
+    #[account(address = pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/* View mid price context */
+#[derive(Accounts)]
+pub struct ViewMidPrice<'info> {
+    pub pool: Account<'info, Pool>,
+    #[account(address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+/* Read-only quote context shared by quote_* and get_pool_state */
+#[derive(Accounts)]
+pub struct QuotePool<'info> {
+    pub pool: Account<'info, Pool>,
+    #[account(address = pool.stack_mint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.lp_mint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+impl<'info> QuotePool<'info> {
+    /// (reserve_stack, reserve_quote): vault balances minus accrued protocol/creator fees
+    fn reserves(&self) -> Result<(u64, u64)> {
+        Ok((
+            vault_reserve(self.stack_vault.amount, self.pool.accrued_fees_stack()?)?,
+            vault_reserve(self.quote_vault.amount, self.pool.accrued_fees_quote()?)?,
+        ))
+    }
+
+    /// (in_decimals, out_decimals, reserve_in, reserve_out) for a swap direction
+    fn oriented(&self, side: &SwapDirection) -> Result<(u8, u8, u64, u64)> {
+        let (reserve_stack, reserve_quote) = self.reserves()?;
+        Ok(match side {
+            SwapDirection::StackToQuote => (self.stack_mint.decimals, self.quote_mint.decimals, reserve_stack, reserve_quote),
+            SwapDirection::QuoteToStack => (self.quote_mint.decimals, self.stack_mint.decimals, reserve_quote, reserve_stack),
+        })
+    }
+
+    /// (mint_in, mint_out) for a swap direction
+    fn mints(&self, side: &SwapDirection) -> (&InterfaceAccount<'info, Mint>, &InterfaceAccount<'info, Mint>) {
+        match side {
+            SwapDirection::StackToQuote => (&self.stack_mint, &self.quote_mint),
+            SwapDirection::QuoteToStack => (&self.quote_mint, &self.stack_mint),
+        }
+    }
+}
+
+/// Return value of `quote_exact_in` / `quote_exact_out`; fees are in native input-token units
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SwapQuote {
+    pub side: SwapDirection,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    pub protocol_fee: u64,
+    pub creator_fee: u64,
+    pub price_impact_bps: u16,
+}
+
+/// Return value of `quote_add_liquidity` / `quote_remove_liquidity` (native units)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LiquidityQuote {
+    pub lp_amount: u64,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
+}
+
+/// Return value of `get_pool_state`; reserves exclude the accrued protocol/creator fees
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PoolStateView {
+    pub reserve_stack: u64,
+    pub reserve_quote: u64,
+    pub lp_supply: u64,
+    pub fee_bps: u16,
+    pub protocol_fee_bps: u16,
+    pub creator_fee_bps: u16,
+    pub mid_price_x: u128,
+    pub paused: bool,
+    pub pause_flags: u8, // global | pool `PAUSE_*` flags
+    pub oracle_source: OracleSource,
+    pub price_cumulative_quote_per_stack: u128,
+    pub price_cumulative_stack_per_quote: u128,
+    pub last_price_update_ts: i64,
+    pub protocol_fees_stack: u64,
+    pub protocol_fees_quote: u64,
+    pub creator_fees_stack: u64,
+    pub creator_fees_quote: u64,
+    pub curve_type: CurveType,
+    pub amp: u64, // current (ramped) StableSwap amplification, 0 for other curves
+    pub stack_weight_bps: u16,
+}
+
+/* TWAP read context */
+#[derive(Accounts)]
+pub struct GetTwap<'info> {
+    pub pool: Account<'info, Pool>,
+    #[account(seeds=[b"observations", pool.key().as_ref()], bump)]
+    pub observations: Account<'info, PoolObservations>,
+    #[account(address = pool.stack_mint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.quote_mint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+/* SetParams context for set_pool_params instruction */
+#[derive(Accounts)]
+pub struct SetParams<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    /// optional governance approval PDA; closed when used
+    #[account(mut)]
+    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
+    /// CHECK: receives the approval's rent; must be its `rent_payer`
+    #[account(mut)]
+    pub approval_rent_receiver: Option<UncheckedAccount<'info>>,
+}
+
+/// Pool parameter change waiting out `Global.params_timelock_secs`, at ["pending_pool_params", pool]
+#[account]
+pub struct PendingPoolParams {
+    pub pool: Pubkey,
+    pub new_fee_bps: Option<u16>,
+    pub new_k: Option<u128>,
+    pub max_price_deviation_bps: Option<u16>,
+    pub eta: i64,
+    pub queued_by: Pubkey, // paid the rent; gets it back on execute/cancel
+    pub bump: u8,
+}
+impl PendingPoolParams { const LEN: usize = 32 + (1 + 2) + (1 + 16) + (1 + 2) + 8 + 32 + 1; }
+
+#[derive(Accounts)]
+pub struct QueuePoolParams<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PendingPoolParams::LEN,
+        seeds=[b"pending_pool_params", pool.key().as_ref()],
+        bump
+    )]
+    pub pending_params: Account<'info, PendingPoolParams>,
+    /// optional governance approval PDA; closed when used
+    #[account(mut)]
+    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
+    /// CHECK: receives the approval's rent; must be its `rent_payer`
+    #[account(mut)]
+    pub approval_rent_receiver: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecutePoolParams<'info> {
+    pub caller: Signer<'info>,
+    #[account(mut)]
+    pub pool: Account<'info, Pool>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    #[account(mut, close = queued_by, seeds=[b"pending_pool_params", pool.key().as_ref()], bump = pending_params.bump)]
+    pub pending_params: Account<'info, PendingPoolParams>,
+    /// CHECK: receives the pending account's rent; must be whoever queued it
+    #[account(mut, address = pending_params.queued_by @ AmmError::Unauthorized)]
+    pub queued_by: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelPoolParams<'info> {
+    pub caller: Signer<'info>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    #[account(mut, close = queued_by, seeds=[b"pending_pool_params", pending_params.pool.as_ref()], bump = pending_params.bump)]
+    pub pending_params: Account<'info, PendingPoolParams>,
+    /// CHECK: receives the pending account's rent; must be whoever queued it
+    #[account(mut, address = pending_params.queued_by @ AmmError::Unauthorized)]
+    pub queued_by: UncheckedAccount<'info>,
+}
+
+/// Ring buffer of cumulative-price snapshots used to answer TWAP queries over arbitrary windows
+#[account]
+pub struct PoolObservations {
+    pub pool: Pubkey,
+    /// slot of the most recently written observation
+    pub index: u16,
+    pub observations: Vec<Observation>,
+}
+impl PoolObservations { const LEN: usize = 32 + 2 + 4 + OBSERVATION_CAPACITY * Observation::LEN; }
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct Observation {
+    pub timestamp: i64,
+    pub cumulative_quote_per_stack: u128,
+    pub cumulative_stack_per_quote: u128,
+}
+impl Observation { const LEN: usize = 8 + 16 + 16; }
+
+/// Return value of `get_twap`; prices scaled like `view_mid_price`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TwapPrice {
+    pub window_secs: u32,
+    pub quote_per_stack_x: u128,
+    pub stack_per_quote_x: u128,
+}
+
+/* Test price feed contexts (program-owned feed for local tests; the instructions only work in `test-feed` builds) */
+#[derive(Accounts)]
+pub struct InitTestPriceFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(init, payer = authority, space = 8 + TestPriceFeed::LEN)]
+    pub price_feed: Account<'info, TestPriceFeed>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetTestPriceFeed<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority @ AmmError::Unauthorized)]
+    pub price_feed: Account<'info, TestPriceFeed>,
+}
+
+/// Minimal Pyth-like price feed owned by this program; price = `price * 10^expo` quote per stack
+#[account]
+pub struct TestPriceFeed {
+    pub authority: Pubkey,
+    pub price: i64,
+    pub expo: i32,
+    pub conf: u64,
+    pub publish_ts: i64,
+}
+impl TestPriceFeed { const LEN: usize = 32 + 8 + 4 + 8 + 8; }
+
+/* -----------------------
+   GovernanceApproval PDA
+   - added `nonce` for replay protection (must be strictly increasing per-pool)
+   - written by `execute_proposal` at ["governance_approval", proposal]
+   - bound to one instruction (`action`) and its arguments (`payload_hash`); single-use, closed on consumption
+   ----------------------- */
+
+#[account]
+pub struct GovernanceApproval {
+    /// which pool this approval is for (or 0 if global)
+    pub target: Pubkey,
+    /// true if multisig has approved this action
+    pub approved: bool,
+    /// expiry to prevent reuse
+    pub expiry_ts: i64,
+    /// strictly increasing nonce to prevent replay of identical approvals
+    pub nonce: u128,
+    /// instruction this approval authorizes
+    pub action: GovernanceAction,
+    /// sha256 of the borsh-encoded payload the instruction must be called with (see `GovernanceAction`)
+    pub payload_hash: [u8; 32],
+    /// paid the approval's rent; gets it back when the approval is consumed
+    pub rent_payer: Pubkey,
+}
+impl GovernanceApproval { const LEN: usize = 32 + 1 + 8 + 16 + 1 + 32 + 32; }
+
+/// Instruction a governance approval authorizes. The payload hashed into `payload_hash` is the borsh
+/// encoding of the tuple listed for each action.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GovernanceAction {
+    /// `(oracle_max_staleness_secs, oracle_max_conf_bps)`
+    SetPoolParams,
+    /// `(admin_stack_receiver, admin_quote_receiver)`
+    WithdrawProtocolFees,
+    /// `(target_amp, ramp_end_ts)`
+    RampAmp,
+    /// `()` (empty payload)
+    StopRampAmp,
+    /// `(user, amount)`, `amount` being `ExactIn { amount_in, min_out }` (0) or `ExactOut { amount_out, max_in }` (1)
+    SwapStackToQuote,
+    /// same payload as `SwapStackToQuote`
+    SwapQuoteToStack,
+    /// `(signers, threshold)`
+    SetGovernanceConfig,
+    /// `(new_fee_bps, new_k, max_price_deviation_bps)`
+    QueuePoolParams,
+}
+
+/// M-of-N governance multisig at ["governance_config"]
+#[account]
+pub struct GovernanceConfig {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub proposal_count: u64, // id of the next proposal
+    pub last_governance_nonce: u128, // replay protection for approvals targeting the config
+}
+impl GovernanceConfig { const LEN: usize = (4 + 32 * MAX_GOVERNANCE_SIGNERS) + 1 + 8 + 16; }
+
+/// Governance proposal at ["proposal", id (u64 LE)]
+#[account]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub target: Pubkey,
+    pub action: GovernanceAction,
+    pub payload_hash: [u8; 32],
+    pub expiry_ts: i64,
+    pub approvers: Vec<Pubkey>,
+    pub executed: bool,
+    pub bump: u8,
+}
+impl Proposal { const LEN: usize = 8 + 32 + 32 + 1 + 32 + 8 + (4 + 32 * MAX_GOVERNANCE_SIGNERS) + 1 + 1; }
+
+#[derive(Accounts)]
+pub struct InitGovernance<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(init, payer = admin, space = 8 + GovernanceConfig::LEN, seeds=[b"governance_config"], bump)]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetGovernanceConfig<'info> {
+    #[account(mut, seeds=[b"governance_config"], bump)]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(mut, close = approval_rent_receiver)]
+    pub governance_approval: Account<'info, GovernanceApproval>,
+    /// CHECK: receives the approval's rent; must be its payer
+    #[account(mut, address = governance_approval.rent_payer @ AmmError::Unauthorized)]
+    pub approval_rent_receiver: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(mut, seeds=[b"governance_config"], bump)]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::LEN,
+        seeds=[b"proposal", governance_config.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveProposal<'info> {
+    pub signer: Signer<'info>,
+    #[account(seeds=[b"governance_config"], bump)]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(mut, seeds=[b"proposal", proposal.id.to_le_bytes().as_ref()], bump = proposal.bump)]
+    pub proposal: Account<'info, Proposal>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds=[b"governance_config"], bump)]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(mut, seeds=[b"proposal", proposal.id.to_le_bytes().as_ref()], bump = proposal.bump)]
+    pub proposal: Account<'info, Proposal>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + GovernanceApproval::LEN,
+        seeds=[b"governance_approval", proposal.key().as_ref()],
+        bump
+    )]
+    pub governance_approval: Account<'info, GovernanceApproval>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelProposal<'info> {
+    pub caller: Signer<'info>,
+    /// CHECK: receives the proposal's rent; must be its proposer
+    #[account(mut, address = proposal.proposer @ AmmError::Unauthorized)]
+    pub proposer: UncheckedAccount<'info>,
+    #[account(mut, close = proposer, seeds=[b"proposal", proposal.id.to_le_bytes().as_ref()], bump = proposal.bump)]
+    pub proposal: Account<'info, Proposal>,
+}
+
+/* -----------------------
+   CONCENTRATED LIQUIDITY ACCOUNTS & CONTEXTS
+   ----------------------- */
+
+#[derive(Accounts)]
+#[instruction(fee_bps: u16, tick_spacing: u16)]
+pub struct CreateClPool<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    #[account(mint::token_program = stack_token_program)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// stack must be registered (same StackInfo registry as constant-product pools)
+    #[account(seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + ClPool::LEN,
+        seeds=[b"cl_pool", stack_mint.key().as_ref(), quote_mint.key().as_ref(), &tick_spacing.to_le_bytes()],
+        bump
+    )]
+    pub cl_pool: Account<'info, ClPool>,
+
+    #[account(mut)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// vault authority PDA (same seeds as constant-product pools, keyed by the CL pool)
+    #[account(seeds=[b"vault_authority", cl_pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/// Concentrated-liquidity pool. `sqrt_price_x64` is sqrt(quote per stack) in Q64.64 over native amounts;
+/// `liquidity` is the sum of all positions whose range contains `tick_current`.
+#[account]
+pub struct ClPool {
+    pub version: u8,
+    pub stack_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub stack_vault: Pubkey,
+    pub quote_vault: Pubkey,
     pub fee_bps: u16,
-    pub k: u128,
+    pub tick_spacing: u16,
     pub bump: u8,
-    pub paused: bool,
-    pub total_lp_supply: u128,
+    pub vault_authority_bump: u8, // signs every vault transfer
     pub locked: bool,
-    pub decimal_normalize_to: u8,
-    pub fee_on_transfer: bool,
-    pub protocol_fee_vault: Pubkey,
-    pub creator_fee_vault: Pubkey,
-    pub treasury: Pubkey,
-    pub oracle: Pubkey,
-    pub creator_claimable: u128, // normalized units
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+    pub liquidity: u128,
+    // LP fees earned per unit of liquidity over the pool's life (Q64.64, wrapping)
+    pub fee_growth_global_stack_x64: u128,
+    pub fee_growth_global_quote_x64: u128,
+    // protocol/creator fee shares accrued in the swap input token (native units), held in the vaults
+    pub protocol_fees_stack: u64,
+    pub protocol_fees_quote: u64,
+    pub creator_fees_stack: u64,
+    pub creator_fees_quote: u64,
     pub creator_last_claim_ts: i64,
-    pub max_price_deviation_bps: u16,
-    pub last_governance_nonce: u128, // replay-protection for governance approvals
+    pub pause_flags: u8, // `PAUSE_*` operations paused on this pool by `pause_cl_pool`
+}
+impl ClPool {
+    const LEN: usize = 1 + 32 * 4 + 2 + 2 + 1 + 1 + 1 + 16 + 4 + 16 + 16 * 2 + 8 * 4 + 8 + 1;
+
+    /// `op` (a `PAUSE_*` flag) is stopped on this pool by `pause_cl_pool`
+    fn is_paused(&self, op: u8) -> bool {
+        self.pause_flags & op != 0
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(start_tick_index: i32)]
+pub struct InitTickArray<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub cl_pool: Account<'info, ClPool>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TickArray::LEN,
+        seeds=[b"tick_array", cl_pool.key().as_ref(), &start_tick_index.to_le_bytes()],
+        bump
+    )]
+    pub tick_array: Box<Account<'info, TickArray>>,
+    pub system_program: Program<'info, System>,
+}
+
+/// TICK_ARRAY_SIZE consecutive ticks (`tick_spacing` apart) starting at `start_tick_index`
+#[account]
+pub struct TickArray {
+    pub cl_pool: Pubkey,
+    pub start_tick_index: i32,
+    pub ticks: [Tick; TICK_ARRAY_SIZE],
+}
+impl TickArray {
+    const LEN: usize = 32 + 4 + TICK_ARRAY_SIZE * Tick::LEN;
+
+    /// Slot of `tick` in this array; it must be on the spacing grid and inside the array
+    fn tick_offset(&self, tick: i32, tick_spacing: u16) -> Result<usize> {
+        let offset = tick - self.start_tick_index;
+        let spacing = tick_spacing as i32;
+        require!(offset >= 0 && offset % spacing == 0, AmmError::TickArrayMismatch);
+        let offset = (offset / spacing) as usize;
+        require!(offset < TICK_ARRAY_SIZE, AmmError::TickArrayMismatch);
+        Ok(offset)
+    }
+}
+
+/// One tick; it is initialized (a position boundary) while `liquidity_gross > 0`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct Tick {
+    /// liquidity added when the price crosses this tick left to right
+    pub liquidity_net: i128,
+    /// total liquidity of positions bounded by this tick
+    pub liquidity_gross: u128,
+    /// fee growth on the other side of this tick from the current price (Q64.64, wrapping)
+    pub fee_growth_outside_stack_x64: u128,
+    pub fee_growth_outside_quote_x64: u128,
+}
+impl Tick {
+    const LEN: usize = 16 * 4;
+
+    /// Apply a position boundary's liquidity change. A tick initialized at or below the current tick
+    /// assumes all fee growth so far happened below it.
+    fn update(&mut self, tick: i32, tick_current: i32, liquidity_delta: i128, upper: bool, fee_growth_global: (u128, u128)) -> Result<()> {
+        let gross_before = self.liquidity_gross;
+        self.liquidity_gross = add_liquidity_delta(gross_before, liquidity_delta)?;
+        if gross_before == 0 && tick <= tick_current {
+            self.fee_growth_outside_stack_x64 = fee_growth_global.0;
+            self.fee_growth_outside_quote_x64 = fee_growth_global.1;
+        }
+        self.liquidity_net = if upper {
+            self.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            self.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or(AmmError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Flip the outside fee growth as the price crosses this tick; returns its `liquidity_net`
+    fn cross(&mut self, fee_growth_global: (u128, u128)) -> i128 {
+        self.fee_growth_outside_stack_x64 = fee_growth_global.0.wrapping_sub(self.fee_growth_outside_stack_x64);
+        self.fee_growth_outside_quote_x64 = fee_growth_global.1.wrapping_sub(self.fee_growth_outside_quote_x64);
+        self.liquidity_net
+    }
+}
+
+/// Whether an account holds a `TickArray` (used to find where `swap_cl`'s tick arrays end)
+fn is_tick_array_account(info: &AccountInfo) -> bool {
+    *info.owner == crate::ID
+        && info
+            .try_borrow_data()
+            .map(|data| data.starts_with(&<TickArray as anchor_lang::Discriminator>::DISCRIMINATOR))
+            .unwrap_or(false)
+}
+
+#[derive(Accounts)]
+#[instruction(tick_lower: i32, tick_upper: i32)]
+pub struct OpenClPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub cl_pool: Account<'info, ClPool>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + ClPosition::LEN,
+        seeds=[b"cl_position", cl_pool.key().as_ref(), owner.key().as_ref(), &tick_lower.to_le_bytes(), &tick_upper.to_le_bytes()],
+        bump
+    )]
+    pub position: Account<'info, ClPosition>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Liquidity over [tick_lower, tick_upper) owned by `owner`; fees are settled into `tokens_owed_*`
+/// whenever the position is touched
+#[account]
+pub struct ClPosition {
+    pub cl_pool: Pubkey,
+    pub owner: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    // fee growth inside the range as of the last settlement (Q64.64, wrapping)
+    pub fee_growth_inside_stack_last_x64: u128,
+    pub fee_growth_inside_quote_last_x64: u128,
+    pub tokens_owed_stack: u64,
+    pub tokens_owed_quote: u64,
+    pub bump: u8,
+}
+impl ClPosition { const LEN: usize = 32 + 32 + 4 + 4 + 16 + 16 * 2 + 8 * 2 + 1; }
+
+/* Shared by increase_cl_liquidity / decrease_cl_liquidity / collect_cl_fees */
+#[derive(Accounts)]
+pub struct ModifyClLiquidity<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(mut)]
+    pub cl_pool: Account<'info, ClPool>,
+    #[account(mut, has_one = owner @ AmmError::Unauthorized, has_one = cl_pool @ AmmError::Unauthorized)]
+    pub position: Account<'info, ClPosition>,
+    /// tick array holding `position.tick_lower`
+    #[account(mut, constraint = tick_array_lower.cl_pool == cl_pool.key() @ AmmError::TickArrayMismatch)]
+    pub tick_array_lower: Box<Account<'info, TickArray>>,
+    /// tick array holding `position.tick_upper`; omit when it is the same array as the lower tick's
+    #[account(
+        mut,
+        constraint = tick_array_upper.cl_pool == cl_pool.key() @ AmmError::TickArrayMismatch,
+        constraint = tick_array_upper.key() != tick_array_lower.key() @ AmmError::TickArrayMismatch
+    )]
+    pub tick_array_upper: Option<Box<Account<'info, TickArray>>>,
+    #[account(mut, address = cl_pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = cl_pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, constraint = owner_stack_account.mint == cl_pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub owner_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, constraint = owner_quote_account.mint == cl_pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub owner_quote_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"vault_authority", cl_pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = cl_pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = cl_pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+impl<'info> ModifyClLiquidity<'info> {
+    /// Apply `liquidity_delta` to the position: update both boundary ticks, settle the fees earned
+    /// since the last touch into `tokens_owed_*` and move in-range pool liquidity. Returns the
+    /// (stack, quote) amounts the change is worth, rounded up when adding and down when removing.
+    fn modify_position(&mut self, liquidity_delta: i128) -> Result<(u64, u64)> {
+        let pool = &mut self.cl_pool;
+        let position = &mut self.position;
+        let (tick_lower, tick_upper) = (position.tick_lower, position.tick_upper);
+        let fee_growth_global = (pool.fee_growth_global_stack_x64, pool.fee_growth_global_quote_x64);
+        let lower_array = &mut self.tick_array_lower;
+        let mut upper_array = self.tick_array_upper.as_deref_mut().map(|array| &mut **array);
+
+        let lower_offset = lower_array.tick_offset(tick_lower, pool.tick_spacing)?;
+        let upper_offset = cl_upper_array(lower_array, &mut upper_array).tick_offset(tick_upper, pool.tick_spacing)?;
+        if liquidity_delta != 0 {
+            lower_array.ticks[lower_offset].update(tick_lower, pool.tick_current, liquidity_delta, false, fee_growth_global)?;
+            cl_upper_array(lower_array, &mut upper_array).ticks[upper_offset]
+                .update(tick_upper, pool.tick_current, liquidity_delta, true, fee_growth_global)?;
+        }
+
+        let lower = lower_array.ticks[lower_offset];
+        let upper = cl_upper_array(lower_array, &mut upper_array).ticks[upper_offset];
+        let (inside_stack, inside_quote) = cl_fee_growth_inside(&lower, &upper, tick_lower, tick_upper, pool.tick_current, fee_growth_global);
+        if position.liquidity > 0 {
+            let owed_stack: u64 = mul_div_wide(position.liquidity, inside_stack.wrapping_sub(position.fee_growth_inside_stack_last_x64), CL_Q64)?
+                .try_into().map_err(|_| AmmError::MathOverflow)?;
+            let owed_quote: u64 = mul_div_wide(position.liquidity, inside_quote.wrapping_sub(position.fee_growth_inside_quote_last_x64), CL_Q64)?
+                .try_into().map_err(|_| AmmError::MathOverflow)?;
+            position.tokens_owed_stack = position.tokens_owed_stack.checked_add(owed_stack).ok_or(AmmError::MathOverflow)?;
+            position.tokens_owed_quote = position.tokens_owed_quote.checked_add(owed_quote).ok_or(AmmError::MathOverflow)?;
+        }
+        position.fee_growth_inside_stack_last_x64 = inside_stack;
+        position.fee_growth_inside_quote_last_x64 = inside_quote;
+        position.liquidity = add_liquidity_delta(position.liquidity, liquidity_delta)?;
+
+        // boundary ticks no position uses any more are cleared
+        if liquidity_delta < 0 {
+            if lower.liquidity_gross == 0 {
+                lower_array.ticks[lower_offset] = Tick::default();
+            }
+            if upper.liquidity_gross == 0 {
+                cl_upper_array(lower_array, &mut upper_array).ticks[upper_offset] = Tick::default();
+            }
+        }
+
+        let sqrt_lower = sqrt_price_at_tick(tick_lower)?;
+        let sqrt_upper = sqrt_price_at_tick(tick_upper)?;
+        let round_up = liquidity_delta > 0;
+        let liquidity = liquidity_delta.unsigned_abs();
+        let (amount_stack, amount_quote) = if pool.tick_current < tick_lower {
+            (cl_amount_stack(sqrt_lower, sqrt_upper, liquidity, round_up)?, 0)
+        } else if pool.tick_current < tick_upper {
+            pool.liquidity = add_liquidity_delta(pool.liquidity, liquidity_delta)?;
+            (
+                cl_amount_stack(pool.sqrt_price_x64, sqrt_upper, liquidity, round_up)?,
+                cl_amount_quote(sqrt_lower, pool.sqrt_price_x64, liquidity, round_up)?,
+            )
+        } else {
+            (0, cl_amount_quote(sqrt_lower, sqrt_upper, liquidity, round_up)?)
+        };
+        Ok((
+            amount_stack.try_into().map_err(|_| AmmError::MathOverflow)?,
+            amount_quote.try_into().map_err(|_| AmmError::MathOverflow)?,
+        ))
+    }
+
+    /// Pay the position owner from the pool vaults (vault PDA signs)
+    fn pay_out(&self, hook_accounts: &[AccountInfo<'info>], amount_stack: u64, amount_quote: u64) -> Result<()> {
+        let pool_key = self.cl_pool.key();
+        if amount_stack > 0 {
+            transfer_from_vault(
+                &self.stack_token_program,
+                &self.stack_vault,
+                &self.owner_stack_account,
+                &self.stack_mint,
+                hook_accounts,
+                &self.vault_authority,
+                pool_key,
+                self.cl_pool.vault_authority_bump,
+                amount_stack,
+            )?;
+        }
+        if amount_quote > 0 {
+            transfer_from_vault(
+                &self.token_program,
+                &self.quote_vault,
+                &self.owner_quote_account,
+                &self.quote_mint,
+                &[],
+                &self.vault_authority,
+                pool_key,
+                self.cl_pool.vault_authority_bump,
+                amount_quote,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The tick array holding a position's upper tick: its own array when passed, else the lower tick's
+fn cl_upper_array<'a>(lower: &'a mut TickArray, upper: &'a mut Option<&mut TickArray>) -> &'a mut TickArray {
+    match upper {
+        Some(array) => array,
+        None => lower,
+    }
+}
+
+#[derive(Accounts)]
+pub struct CloseClPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(mut, close = owner, has_one = owner @ AmmError::Unauthorized)]
+    pub position: Account<'info, ClPosition>,
+}
+
+#[derive(Accounts)]
+pub struct SwapCl<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub cl_pool: Account<'info, ClPool>,
+    #[account(seeds=[b"stack_info", cl_pool.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    #[account(mut, address = cl_pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = cl_pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_stack_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_quote_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"vault_authority", cl_pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = cl_pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = cl_pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+/* Shared by claim_cl_creator_fees (creator) and withdraw_cl_protocol_fees (admin/fee manager/governance) */
+#[derive(Accounts)]
+pub struct CollectClPoolFees<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub cl_pool: Account<'info, ClPool>,
+    #[account(seeds=[b"stack_info", cl_pool.stack_mint.as_ref()], bump)]
+    pub stack_info: Account<'info, StackInfo>,
+    #[account(mut, address = cl_pool.stack_vault @ AmmError::InvalidVaultOwner)]
+    pub stack_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = cl_pool.quote_vault @ AmmError::InvalidVaultOwner)]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub stack_receiver: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub quote_receiver: InterfaceAccount<'info, TokenAccount>,
+    #[account(seeds=[b"vault_authority", cl_pool.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+    #[account(address = cl_pool.stack_mint @ AmmError::InvalidVaultMint)]
+    pub stack_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = cl_pool.quote_mint @ AmmError::InvalidVaultMint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    pub stack_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(seeds=[b"global"], bump)]
+    pub global: Account<'info, Global>,
+}
+
+impl<'info> CollectClPoolFees<'info> {
+    /// Pay accrued pool fees to the receivers (vault PDA signs)
+    fn pay_out(&self, hook_accounts: &[AccountInfo<'info>], amount_stack: u64, amount_quote: u64) -> Result<()> {
+        let pool_key = self.cl_pool.key();
+        if amount_stack > 0 {
+            transfer_from_vault(
+                &self.stack_token_program,
+                &self.stack_vault,
+                &self.stack_receiver,
+                &self.stack_mint,
+                hook_accounts,
+                &self.vault_authority,
+                pool_key,
+                self.cl_pool.vault_authority_bump,
+                amount_stack,
+            )?;
+        }
+        if amount_quote > 0 {
+            transfer_from_vault(
+                &self.token_program,
+                &self.quote_vault,
+                &self.quote_receiver,
+                &self.quote_mint,
+                &[],
+                &self.vault_authority,
+                pool_key,
+                self.cl_pool.vault_authority_bump,
+                amount_quote,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/* -----------------------
+   EVENTS
+   ----------------------- */
+
+#[event]
+pub struct GlobalInitialized { pub admin: Pubkey }
+
+#[event]
+pub struct StackRegistered {
+    pub stack_mint: Pubkey,
+    pub creator: Pubkey,
+    pub creator_fee_bps: u16,
+}
+
+#[event]
+pub struct BackingInitialized {
+    pub stack_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub vault: Pubkey,
+    pub initial_nav_wad: u128,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct SupplyLimitsUpdated { pub stack_mint: Pubkey, pub by: Pubkey, pub limits: SupplyLimits }
+
+/// Sliding-window total after a limited mint or redeem (`window_limit` 0 = only tracked)
+#[event]
+pub struct SupplyWindowUsage {
+    pub stack_mint: Pubkey,
+    pub is_mint: bool,
+    pub amount: u64,
+    pub window_used: u64,
+    pub window_limit: u64,
+}
+
+#[event]
+pub struct PoolCreated {
+    pub pool: Pubkey,
+    pub stack_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct LiquidityProvided {
+    pub pool: Pubkey,
+    pub provider: Pubkey,
+    pub lp_minted: u64,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
+}
+
+#[event]
+pub struct LiquidityRemoved {
+    pub pool: Pubkey,
+    pub provider: Pubkey,
+    pub lp_burned: u64,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
 }
-impl Pool {
-    // rough size calc; adjust if you expand fields
-    const LEN: usize = 1 + 32*6 + 2 + 16 + 1 + 1 + 1 + 4 + 32 + 32 + 32 + 16 + 8 + 2 + 16;
+
+#[event]
+pub struct Swap {
+    pub pool: Pubkey,
+    pub trader: Pubkey,
+    pub side: SwapDirection,
+    pub amount_in: u64,
+    pub amount_out: u64,
 }
 
-/* PROVIDE LIQUIDITY CONTEXT */
-#[derive(Accounts)]
-pub struct ProvideLiquidity<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub stack_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub quote_mint: Account<'info, Mint>,
+#[event]
+pub struct FlashLoan {
+    pub pool: Pubkey,
+    pub borrower: Pubkey,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
+}
 
-    /// vault token accounts (owned by vault_authority PDA)
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
+/// Flash fees are in native units of each borrowed token
+#[event]
+pub struct FlashLoanRepaid {
+    pub pool: Pubkey,
+    pub repayer: Pubkey,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
+    pub fee_stack: u64,
+    pub fee_quote: u64,
+}
 
-    /// fee vaults
-    #[account(mut)]
-    pub protocol_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub creator_fee_vault: Account<'info, TokenAccount>,
+/// Aggregate of a `route_swap`; each hop also emits its own `Swap`
+#[event]
+pub struct RouteSwapped {
+    pub trader: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub hops: u8,
+    pub amount_in: u64,
+    pub amount_out: u64,
+}
 
-    #[account(mut)]
-    pub lp_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub user_lp_account: Account<'info, TokenAccount>,
+/// Per-swap fee split in native units of `fee_mint`; `lp_fee` stays in the reserves
+#[event]
+pub struct SwapFees {
+    pub pool: Pubkey,
+    pub fee_mint: Pubkey,
+    pub lp_fee: u64,
+    pub protocol_fee: u64,
+    pub creator_fee: u64,
+}
 
-    #[account(mut)]
-    pub user_stack_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_quote_account: Account<'info, TokenAccount>,
+#[event]
+pub struct CreatorClaimed {
+    pub pool: Pubkey,
+    pub creator: Pubkey,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
+}
 
-    /// treasury ATA (quote-mint) where small dust amounts are swept
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+#[event]
+pub struct ProtocolFeesWithdrawn {
+    pub pool: Pubkey,
+    pub to_stack: Pubkey,
+    pub to_quote: Pubkey,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
+}
 
-    /// vault authority PDA
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+#[event]
+pub struct EmergencyWithdrawal {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub lp_burned: u64,
+}
 
-    pub token_program: Program<'info, Token>,
-    pub global: Account<'info, Global>,
+#[event]
+pub struct MidPrice {
+    pub pool: Pubkey,
+    pub price_x: u128,
 }
 
-/* REMOVE LIQUIDITY CONTEXT */
-#[derive(Accounts)]
-pub struct RemoveLiquidity<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct TwapRead {
+    pub pool: Pubkey,
+    pub window_secs: u32,
+    pub quote_per_stack_x: u128,
+    pub stack_per_quote_x: u128,
+}
 
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
+#[event]
+pub struct AmpRampStarted {
+    pub pool: Pubkey,
+    pub amp_initial: u64,
+    pub amp_target: u64,
+    pub start_ts: i64,
+    pub end_ts: i64,
+}
 
-    #[account(mut)]
-    pub lp_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub user_lp_account: Account<'info, TokenAccount>,
+#[event]
+pub struct AmpRampStopped { pub pool: Pubkey, pub amp: u64 }
 
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
+#[event]
+pub struct LaunchStarted {
+    pub stack_mint: Pubkey,
+    pub pool: Pubkey,
+    pub kind: BondingCurveKind,
+    pub start_price_wad: u128,
+    pub curve_scale: u64,
+    pub graduation_market_cap: u64,
+}
 
-    // add mint accounts so we can read decimals
-    #[account(mut)]
-    pub stack_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub quote_mint: Account<'info, Mint>,
+#[event]
+pub struct LaunchTraded {
+    pub stack_mint: Pubkey,
+    pub trader: Pubkey,
+    pub is_buy: bool,
+    pub quote_amount: u64,
+    pub stack_amount: u64,
+    pub sold: u64,
+}
 
-    #[account(mut)]
-    pub user_stack_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_quote_account: Account<'info, TokenAccount>,
+#[event]
+pub struct LaunchGraduated {
+    pub stack_mint: Pubkey,
+    pub pool: Pubkey,
+    pub quote_seeded: u64,
+    pub stack_seeded: u64,
+    pub lp_locked: u64,
+}
 
-    /// treasury ATA for dust
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+#[event]
+pub struct ClPoolCreated {
+    pub cl_pool: Pubkey,
+    pub stack_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub fee_bps: u16,
+    pub tick_spacing: u16,
+    pub sqrt_price_x64: u128,
+}
 
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+#[event]
+pub struct ClLiquidityChanged {
+    pub cl_pool: Pubkey,
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub liquidity_delta: i128,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
+}
 
-    pub token_program: Program<'info, Token>,
-    pub global: Account<'info, Global>,
+#[event]
+pub struct ClFeesCollected {
+    pub cl_pool: Pubkey,
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub amount_stack: u64,
+    pub amount_quote: u64,
 }
 
-/* SWAP CONTEXTS — separated directions for safety */
+#[event]
+pub struct TransferHookProgramsUpdated { pub by: Pubkey, pub programs: Vec<Pubkey> }
 
-/* SWAP Stack -> Quote */
-#[derive(Accounts)]
-pub struct SwapStackToQuote<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct RebalanceHookProgramsUpdated { pub by: Pubkey, pub programs: Vec<Pubkey> }
+#[event]
+pub struct RebalanceHookQueued { pub stack_mint: Pubkey, pub hook: Option<Pubkey>, pub threshold_bps: u16, pub eta: i64 }
+#[event]
+pub struct RebalanceHookUpdated { pub stack_mint: Pubkey, pub hook: Option<Pubkey>, pub threshold_bps: u16 }
+#[event]
+pub struct RebalanceHookRequested {
+    pub stack_mint: Pubkey,
+    pub pool: Pubkey,
+    pub hook: Pubkey,
+    pub trade: RebalanceTrade,
+    pub amount_in: u64,
+    pub replaced_pending: bool, // an earlier request was still waiting for `execute_rebalance_hook` and is dropped
+}
+#[event]
+pub struct RebalanceHookInvoked { pub stack_mint: Pubkey, pub pool: Pubkey, pub hook: Pubkey, pub trade: RebalanceTrade, pub compute_units: u64 }
 
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
+#[event]
+pub struct ProtocolPaused { pub by: Pubkey }
+#[event]
+pub struct ProtocolResumed { pub by: Pubkey }
+#[event]
+pub struct PoolParamsUpdated { pub pool: Pubkey, pub by: Pubkey }
+#[event]
+pub struct ProtocolPauseFlagsUpdated { pub pause_flags: u8, pub by: Pubkey }
+#[event]
+pub struct PoolPauseFlagsUpdated { pub pool: Pubkey, pub pause_flags: u8, pub by: Pubkey }
+#[event]
+pub struct RoleProposed { pub role: GlobalRole, pub current: Pubkey, pub proposed: Option<Pubkey>, pub by: Pubkey }
+#[event]
+pub struct RoleTransferred { pub role: GlobalRole, pub previous: Pubkey, pub new_holder: Pubkey }
+#[event]
+pub struct GlobalParamsUpdated {
+    pub by: Pubkey,
+    pub protocol_fee_bps: u16,
+    pub max_fee_bps: u16,
+    pub dust_threshold: u64,
+    pub creator_claim_lock_secs: i64,
+    pub treasury: Pubkey,
+}
+#[event]
+pub struct ParamsTimelockUpdated { pub by: Pubkey, pub secs: i64 }
+#[event]
+pub struct PoolParamsQueued {
+    pub pool: Pubkey,
+    pub new_fee_bps: Option<u16>,
+    pub new_k: Option<u128>,
+    pub max_price_deviation_bps: Option<u16>,
+    pub eta: i64,
+    pub by: Pubkey,
+}
+#[event]
+pub struct PoolParamsExecuted {
+    pub pool: Pubkey,
+    pub new_fee_bps: Option<u16>,
+    pub new_k: Option<u128>,
+    pub max_price_deviation_bps: Option<u16>,
+    pub by: Pubkey,
+}
+#[event]
+pub struct PoolParamsCancelled { pub pool: Pubkey, pub by: Pubkey }
 
-    #[account(mut)]
-    pub stack_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub quote_mint: Account<'info, Mint>,
+#[event]
+pub struct GovernanceConfigUpdated { pub signers: Vec<Pubkey>, pub threshold: u8 }
+#[event]
+pub struct ProposalCreated {
+    pub proposal: Pubkey,
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub target: Pubkey,
+    pub action: GovernanceAction,
+    pub payload_hash: [u8; 32],
+    pub expiry_ts: i64,
+}
+#[event]
+pub struct ProposalApproved { pub proposal: Pubkey, pub signer: Pubkey, pub approvals: u8 }
+#[event]
+pub struct ProposalExecuted { pub proposal: Pubkey, pub approval: Pubkey, pub nonce: u128 }
+#[event]
+pub struct ProposalCancelled { pub proposal: Pubkey, pub by: Pubkey }
 
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
+/* extra events for minted/redeemed flows */
+#[event]
+pub struct MintedStackViaPool {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub quote_in: u64,
+    pub stack_out: u64,
+    pub backing_reserve: u64, // after the mint
+    pub backed_supply: u64,
+}
+#[event]
+pub struct RedeemedStackViaPool {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub stack_in: u64,
+    pub quote_out: u64,
+    pub backing_reserve: u64, // after the redeem
+    pub backed_supply: u64,
+}
+#[event]
+pub struct ProtocolFeesWithdrawn2 { pub pool: Pubkey, pub to: Pubkey, pub amount: u64 }
 
-    #[account(mut)]
-    pub user_stack_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_quote_account: Account<'info, TokenAccount>,
+/* -----------------------
+   ENUMS & HELPERS
+   ----------------------- */
 
-    #[account(mut)]
-    pub protocol_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub creator_fee_vault: Account<'info, TokenAccount>,
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum SwapDirection { StackToQuote, QuoteToStack }
 
-    /// treasury ATA for dust sweeps
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+/// Trade size for the swap processors: spend exactly `amount_in`, or receive exactly `amount_out`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+enum SwapAmount {
+    ExactIn { amount_in: u64, min_out: u64 },
+    ExactOut { amount_out: u64, max_in: u64 },
+}
 
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+/// Pricing invariant of a pool: x*y=k, Curve-style StableSwap for pegged pairs, or Balancer-style
+/// weighted constant mean (x^w_x * y^w_y) for non-50/50 pools
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CurveType { ConstantProduct, StableSwap, Weighted }
+
+/// Which decoder `pool.oracle` is read with; `None` disables the oracle guardrail for the pool.
+/// `TestFeed` only exists in `test-feed` builds.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum OracleSource {
+    None,
+    Pyth,
+    #[cfg(feature = "test-feed")]
+    TestFeed,
+}
 
-    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
-    pub stack_info: Account<'info, StackInfo>,
+fn integer_sqrt(value: u128) -> u128 {
+    if value <= 1 { return value; }
+    let mut left: u128 = 1;
+    let mut right: u128 = value;
+    while left <= right {
+        let mid = (left + right) >> 1;
+        let sq = mid.saturating_mul(mid);
+        if sq == value { return mid; }
+        if sq < value { left = mid + 1; } else { right = mid - 1; }
+    }
+    right
+}
+
+/// central math wrapper example
+fn checked_mul_div(a: u128, b: u128, c: u128) -> Result<u128> {
+    let mul = a.checked_mul(b).ok_or(AmmError::MathOverflow)?;
+    let div = mul.checked_div(c).ok_or(AmmError::MathOverflow)?;
+    Ok(div)
+}
+
+/// Normalize any token amount to `target_decimals` as u128 (for internal math)
+fn normalize_amount_u128(amount: u64, src_decimals: u8, target_decimals: u8) -> Result<u128> {
+    if src_decimals == target_decimals {
+        return Ok(amount as u128);
+    } else if src_decimals < target_decimals {
+        let mul = 10u128.pow((target_decimals - src_decimals) as u32);
+        Ok((amount as u128).checked_mul(mul).ok_or(AmmError::MathOverflow)?)
+    } else {
+        let div = 10u128.pow((src_decimals - target_decimals) as u32);
+        Ok((amount as u128).checked_div(div).ok_or(AmmError::MathOverflow)?)
+    }
+}
+
+/// Normalize like `normalize_amount_u128` but round up when precision is dropped (exact-output targets)
+fn normalize_amount_u128_ceil(amount: u64, src_decimals: u8, target_decimals: u8) -> Result<u128> {
+    if src_decimals <= target_decimals {
+        normalize_amount_u128(amount, src_decimals, target_decimals)
+    } else {
+        let div = 10u128.pow((src_decimals - target_decimals) as u32);
+        checked_div_ceil(amount as u128, div)
+    }
+}
+
+/// Denormalize from normalized u128 back to native decimals u64 (floor)
+fn denormalize_amount_u64(amount_norm: u128, dst_decimals: u8, target_decimals: u8) -> Result<u64> {
+    if dst_decimals == target_decimals {
+        Ok(amount_norm.try_into().map_err(|_| AmmError::MathOverflow)?)
+    } else if dst_decimals < target_decimals {
+        let div = 10u128.pow((target_decimals - dst_decimals) as u32);
+        Ok(amount_norm.checked_div(div).ok_or(AmmError::MathOverflow)?.try_into().map_err(|_| AmmError::MathOverflow)?)
+    } else {
+        let mul = 10u128.pow((dst_decimals - target_decimals) as u32);
+        Ok(amount_norm.checked_mul(mul).ok_or(AmmError::MathOverflow)?.try_into().map_err(|_| AmmError::MathOverflow)?)
+    }
+}
+
+/// Denormalize like `denormalize_amount_u64` but round up (amounts the pool charges)
+fn denormalize_amount_u64_ceil(amount_norm: u128, dst_decimals: u8, target_decimals: u8) -> Result<u64> {
+    if dst_decimals < target_decimals {
+        let div = 10u128.pow((target_decimals - dst_decimals) as u32);
+        Ok(checked_div_ceil(amount_norm, div)?.try_into().map_err(|_| AmmError::MathOverflow)?)
+    } else {
+        denormalize_amount_u64(amount_norm, dst_decimals, target_decimals)
+    }
+}
+
+/// ceil(a / b)
+fn checked_div_ceil(a: u128, b: u128) -> Result<u128> {
+    let q = a.checked_div(b).ok_or(AmmError::MathOverflow)?;
+    if q.checked_mul(b).ok_or(AmmError::MathOverflow)? < a { Ok(q + 1) } else { Ok(q) }
+}
+
+/// Reject a transaction that lands after the caller's deadline (unix timestamp and/or slot; `None` = no limit)
+fn check_deadline(deadline_unix_ts: Option<i64>, max_slot: Option<u64>) -> Result<()> {
+    if deadline_unix_ts.is_none() && max_slot.is_none() {
+        return Ok(());
+    }
+    let clock = Clock::get()?;
+    if let Some(deadline) = deadline_unix_ts {
+        require!(clock.unix_timestamp <= deadline, AmmError::DeadlineExceeded);
+    }
+    if let Some(slot) = max_slot {
+        require!(clock.slot <= slot, AmmError::DeadlineExceeded);
+    }
+    Ok(())
+}
 
-    /// optional governance approval PDA created by off-chain multisig flows
-    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
+/// Largest deposit at the current reserve ratio within the (max_stack, max_quote) budget (native units)
+fn optimal_deposit(max_stack: u64, max_quote: u64, reserve_stack: u64, reserve_quote: u64) -> Result<(u64, u64)> {
+    require!(reserve_stack > 0 && reserve_quote > 0, AmmError::NoLiquidity);
+    let quote_optimal = checked_mul_div(max_stack as u128, reserve_quote as u128, reserve_stack as u128)?;
+    if quote_optimal <= max_quote as u128 {
+        Ok((max_stack, quote_optimal as u64))
+    } else {
+        let stack_optimal = checked_mul_div(max_quote as u128, reserve_stack as u128, reserve_quote as u128)?;
+        Ok((stack_optimal.min(max_stack as u128) as u64, max_quote))
+    }
+}
 
-    pub token_program: Program<'info, Token>,
-    pub global: Account<'info, Global>,
+/// LP locked on a pool's first deposit (global config, falling back to the constant when unset)
+fn minimum_liquidity(global: &Global) -> u64 {
+    if global.minimum_liquidity == 0 { FALLBACK_MINIMUM_LIQUIDITY } else { global.minimum_liquidity }
 }
 
-/* SWAP Quote -> Stack */
-#[derive(Accounts)]
-pub struct SwapQuoteToStack<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+/// LP shares for a deposit (normalized units): sqrt(x*y) on the first deposit (the weighted mean on a
+/// weighted pool), else the smaller pro-rata side
+fn lp_for_deposit(
+    pool: &Pool,
+    norm_stack: u128,
+    norm_quote: u128,
+    reserve_stack_norm: u128,
+    reserve_quote_norm: u128,
+    total_lp: u128,
+) -> Result<u128> {
+    if total_lp == 0 {
+        if pool.curve_type == CurveType::Weighted {
+            return weighted_geometric_mean(norm_stack, norm_quote, pool.stack_weight_bps as u128);
+        }
+        return Ok(integer_sqrt(norm_stack.checked_mul(norm_quote).ok_or(AmmError::MathOverflow)?));
+    }
+    require!(reserve_stack_norm > 0 && reserve_quote_norm > 0, AmmError::NoLiquidity);
+    let lp_from_stack = checked_mul_div(norm_stack, total_lp, reserve_stack_norm)?;
+    let lp_from_quote = checked_mul_div(norm_quote, total_lp, reserve_quote_norm)?;
+    Ok(lp_from_stack.min(lp_from_quote))
+}
 
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
+/// Part of a single-sided deposit to swap so the remainder and the swap output match the post-swap reserve
+/// ratio (native input units). Closed form of the zap quadratic with f = fee_bps / BPS_DENOM:
+/// s = (sqrt(r^2 (2 - f)^2 + 4 (1 - f) a r) - r (2 - f)) / (2 (1 - f)), evaluated scaled by BPS_DENOM.
+fn optimal_zap_swap_in(amount_in: u128, reserve_in: u128, pool_fee_bps: u16) -> Result<u128> {
+    require!(reserve_in > 0, AmmError::NoLiquidity);
+    let keep_bps = BPS_DENOM.checked_sub(pool_fee_bps as u128).ok_or(AmmError::InvalidFee)?;
+    let two_minus_fee = (BPS_DENOM * 2).checked_sub(pool_fee_bps as u128).ok_or(AmmError::InvalidFee)?;
+    // discriminant factored as r * (r (2 - f)^2 + 4 (1 - f) a) so it stays in range for large reserves
+    let inner = reserve_in
+        .checked_mul(two_minus_fee * two_minus_fee).ok_or(AmmError::MathOverflow)?
+        .checked_add(
+            amount_in
+                .checked_mul(4 * keep_bps * BPS_DENOM).ok_or(AmmError::MathOverflow)?,
+        ).ok_or(AmmError::MathOverflow)?;
+    let root = sqrt_product(reserve_in, inner);
+    let linear = reserve_in.checked_mul(two_minus_fee).ok_or(AmmError::MathOverflow)?;
+    let swap_in = root.saturating_sub(linear).checked_div(2 * keep_bps).ok_or(AmmError::MathOverflow)?;
+    Ok(swap_in.min(amount_in))
+}
 
-    #[account(mut)]
-    pub stack_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub quote_mint: Account<'info, Mint>,
+/// floor(sqrt(a * b)) for factors whose product may not fit: the larger factor is scaled down by 4^k and
+/// the root scaled back by 2^k (only low-order bits are lost). Products stay below 2^127 for `integer_sqrt`.
+fn sqrt_product(mut a: u128, mut b: u128) -> u128 {
+    let mut shift = 0u32;
+    loop {
+        if let Some(product) = a.checked_mul(b).filter(|p| p >> 127 == 0) {
+            return integer_sqrt(product) << shift;
+        }
+        if a >= b { a >>= 2; } else { b >>= 2; }
+        shift += 1;
+    }
+}
 
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
+/// Zap split for the pool's curve (native input units): closed form on x*y=k, bisection on the other curves
+#[allow(clippy::too_many_arguments)]
+fn zap_swap_in(
+    pool: &Pool,
+    stack_in: bool,
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    in_decimals: u8,
+    out_decimals: u8,
+) -> Result<u128> {
+    if pool.curve_type == CurveType::ConstantProduct {
+        return optimal_zap_swap_in(amount_in as u128, reserve_in as u128, pool.fee_bps);
+    }
+    let target_decimals = pool.decimal_normalize_to;
+    let amount_in_norm = normalize_amount_u128(amount_in, in_decimals, target_decimals)?;
+    let reserve_in_norm = normalize_amount_u128(reserve_in, in_decimals, target_decimals)?;
+    let reserve_out_norm = normalize_amount_u128(reserve_out, out_decimals, target_decimals)?;
+    require!(reserve_in_norm > 0 && reserve_out_norm > 0, AmmError::NoLiquidity);
+    let swap_in_norm = if pool.curve_type == CurveType::StableSwap {
+        // the invariant doesn't move while searching, so solve it once
+        let amp = current_amp(pool, Clock::get()?.unix_timestamp)?;
+        let d = stable_invariant(amp, reserve_in_norm, reserve_out_norm)?;
+        bisect_zap_swap_in(pool.fee_bps, amount_in_norm, reserve_in_norm, reserve_out_norm, |net_in| {
+            stable_out_given_d(amp, d, net_in, reserve_in_norm, reserve_out_norm)
+        })?
+    } else {
+        let (weight_in, weight_out) = pool.weights_bps(stack_in);
+        bisect_zap_swap_in(pool.fee_bps, amount_in_norm, reserve_in_norm, reserve_out_norm, |net_in| {
+            weighted_amount_out(net_in, reserve_in_norm, reserve_out_norm, weight_in, weight_out)
+        })?
+    };
+    Ok(denormalize_amount_u64(swap_in_norm, in_decimals, target_decimals)? as u128)
+}
 
-    #[account(mut)]
-    pub user_stack_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_quote_account: Account<'info, TokenAccount>,
+/// Zap split by bisection (normalized units): the largest swap input whose unswapped remainder still
+/// outweighs the swap output relative to the post-swap reserves. `curve_out` prices the net (post-fee) input.
+fn bisect_zap_swap_in(
+    pool_fee_bps: u16,
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    curve_out: impl Fn(u128) -> Result<u128>,
+) -> Result<u128> {
+    let keep_bps = BPS_DENOM.checked_sub(pool_fee_bps as u128).ok_or(AmmError::InvalidFee)?;
+    let (mut lo, mut hi) = (0u128, amount_in);
+    for _ in 0..ZAP_SEARCH_ITERATIONS {
+        if lo >= hi {
+            break;
+        }
+        let mid = hi - (hi - lo) / 2;
+        let out = curve_out(checked_mul_div(mid, keep_bps, BPS_DENOM)?)?;
+        // (a - s) / out >= (r_in + s) / (r_out - out): the remainder is still too heavy, swap more
+        let remainder_side = mul_u128_wide(amount_in - mid, reserve_out.saturating_sub(out));
+        let output_side = mul_u128_wide(out, reserve_in.checked_add(mid).ok_or(AmmError::MathOverflow)?);
+        if remainder_side >= output_side { lo = mid; } else { hi = mid - 1; }
+    }
+    Ok(lo)
+}
 
-    #[account(mut)]
-    pub protocol_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub creator_fee_vault: Account<'info, TokenAccount>,
+/* -----------------------
+   STABLESWAP CURVE (2 coins, normalized units)
+   ----------------------- */
 
-    /// treasury ATA for dust sweeps
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+/// Amplification at `now`, linearly interpolated across the ramp window
+fn current_amp(pool: &Pool, now: i64) -> Result<u64> {
+    if now >= pool.amp_ramp_end_ts {
+        return Ok(pool.amp_target);
+    }
+    if now <= pool.amp_ramp_start_ts {
+        return Ok(pool.amp_initial);
+    }
+    let elapsed = (now - pool.amp_ramp_start_ts) as u128;
+    let duration = (pool.amp_ramp_end_ts - pool.amp_ramp_start_ts) as u128;
+    let (initial, target) = (pool.amp_initial as u128, pool.amp_target as u128);
+    let amp = if target >= initial {
+        initial + checked_mul_div(target - initial, elapsed, duration)?
+    } else {
+        initial - checked_mul_div(initial - target, elapsed, duration)?
+    };
+    Ok(amp as u64)
+}
 
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+/// StableSwap invariant D for reserves (x, y): A n^n (x + y) + D = A n^n D + D^3 / (n^n x y), n = 2.
+/// Newton: D' = (Ann S + 2 D_p) D / ((Ann - 1) D + 3 D_p) with D_p = D^3 / (4 x y).
+fn stable_invariant(amp: u64, x: u128, y: u128) -> Result<u128> {
+    let sum = x.checked_add(y).ok_or(AmmError::MathOverflow)?;
+    if sum == 0 {
+        return Ok(0);
+    }
+    require!(x > 0 && y > 0, AmmError::NoLiquidity);
+    let ann = (amp as u128) * 4;
+    let ann_sum = ann.checked_mul(sum).ok_or(AmmError::MathOverflow)?;
+    let (mut d, mut prev_prev) = (sum, 0u128);
+    for _ in 0..STABLE_MAX_ITERATIONS {
+        let d_p = mul_div_wide(mul_div_wide(d, d, x * 2)?, d, y * 2)?;
+        let numerator = ann_sum.checked_add(d_p.checked_mul(2).ok_or(AmmError::MathOverflow)?).ok_or(AmmError::MathOverflow)?;
+        let denominator = (ann - 1)
+            .checked_mul(d).ok_or(AmmError::MathOverflow)?
+            .checked_add(d_p.checked_mul(3).ok_or(AmmError::MathOverflow)?).ok_or(AmmError::MathOverflow)?;
+        let prev = d;
+        d = mul_div_wide(numerator, d, denominator)?;
+        if d.abs_diff(prev) <= 1 {
+            return Ok(d);
+        }
+        // floor rounding can leave Newton bouncing between two neighbours on very imbalanced reserves
+        if d == prev_prev {
+            return Ok(d.min(prev));
+        }
+        prev_prev = prev;
+    }
+    err!(AmmError::StableSwapNotConverged)
+}
 
-    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
-    pub stack_info: Account<'info, StackInfo>,
+/// Reserve of the other coin that keeps the invariant at `d` when one coin's reserve is `x`.
+/// Newton on y^2 + (b - D) y = c with b = x + D / Ann, c = D^3 / (4 x Ann).
+fn stable_get_y(amp: u64, x: u128, d: u128) -> Result<u128> {
+    require!(x > 0, AmmError::NoLiquidity);
+    let ann = (amp as u128) * 4;
+    let c = mul_div_wide(mul_div_wide(d, d, x * 2)?, d, ann * 2)?;
+    let b = x.checked_add(d / ann).ok_or(AmmError::MathOverflow)?;
+    let (mut y, mut prev_prev) = (d, 0u128);
+    for _ in 0..STABLE_MAX_ITERATIONS {
+        // y' = (y^2 + c) / (2y + b - D), the numerator held in 256 bits
+        let (hi, lo) = mul_u128_wide(y, y);
+        let (lo, carry) = lo.overflowing_add(c);
+        let hi = hi.checked_add(carry as u128).ok_or(AmmError::MathOverflow)?;
+        let denominator = y
+            .checked_mul(2).ok_or(AmmError::MathOverflow)?
+            .checked_add(b).ok_or(AmmError::MathOverflow)?
+            .checked_sub(d).ok_or(AmmError::MathOverflow)?;
+        let prev = y;
+        y = div_wide(hi, lo, denominator)?;
+        if y.abs_diff(prev) <= 1 {
+            return Ok(y);
+        }
+        // settle a rounding 2-cycle on the larger reserve, which favors the pool
+        if y == prev_prev {
+            return Ok(y.max(prev));
+        }
+        prev_prev = prev;
+    }
+    err!(AmmError::StableSwapNotConverged)
+}
 
-    /// optional governance approval PDA
-    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
+/// Output for `amount_in` against a known invariant; one unit is held back so Newton rounding favors the pool
+fn stable_out_given_d(amp: u64, d: u128, amount_in: u128, reserve_in: u128, reserve_out: u128) -> Result<u128> {
+    let new_in = reserve_in.checked_add(amount_in).ok_or(AmmError::MathOverflow)?;
+    let y = stable_get_y(amp, new_in, d)?;
+    Ok(reserve_out.saturating_sub(y).saturating_sub(1))
+}
 
-    pub token_program: Program<'info, Token>,
-    pub global: Account<'info, Global>,
+/// StableSwap get amount out (u128 normalized arithmetic)
+fn stable_amount_out(amp: u64, amount_in: u128, reserve_in: u128, reserve_out: u128) -> Result<u128> {
+    require!(reserve_in > 0 && reserve_out > 0, AmmError::NoLiquidity);
+    let d = stable_invariant(amp, reserve_in, reserve_out)?;
+    stable_out_given_d(amp, d, amount_in, reserve_in, reserve_out)
 }
 
-/* MintStackViaPool & RedeemStackViaPool contexts — include treasuries & fee vaults */
-#[derive(Accounts)]
-pub struct MintStackViaPool<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub stack_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub quote_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_quote_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_stack_account: Account<'info, TokenAccount>,
-    #[account(seeds=[b"stack_mint_auth", stack_mint.key().as_ref()], bump)]
-    pub stack_mint_auth: UncheckedAccount<'info>,
-    #[account(mut)]
-    pub protocol_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub creator_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
-    pub stack_info: Account<'info, StackInfo>,
+/// StableSwap get amount in for an exact output; checked against the forward curve so the exact-in leg
+/// executed for it always covers `amount_out`
+fn stable_amount_in(amp: u64, amount_out: u128, reserve_in: u128, reserve_out: u128) -> Result<u128> {
+    require!(reserve_in > 0 && reserve_out > 0, AmmError::NoLiquidity);
+    require!(amount_out < reserve_out, AmmError::InsufficientLiquidity);
+    let d = stable_invariant(amp, reserve_in, reserve_out)?;
+    let x = stable_get_y(amp, reserve_out - amount_out, d)?;
+    let mut amount_in = x.saturating_sub(reserve_in).checked_add(1).ok_or(AmmError::MathOverflow)?;
+    for _ in 0..3 {
+        if stable_out_given_d(amp, d, amount_in, reserve_in, reserve_out)? >= amount_out {
+            return Ok(amount_in);
+        }
+        amount_in = amount_in.checked_add(1).ok_or(AmmError::MathOverflow)?;
+    }
+    err!(AmmError::StableSwapNotConverged)
+}
 
-    /// treasury ATA for dust sweeps
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+/// Marginal price (output per unit of input, scaled by 10^decimal_normalize_to like `price_ratio_x`) for a
+/// trade selling stack when `stack_in`. StableSwap: p = y (Ann x + Q) / (x (Ann y + Q)) with Q = D^3 / (4 x y);
+/// weighted: p = (y / w_y) / (x / w_x).
+fn spot_price_x(pool: &Pool, stack_in: bool, reserve_in_norm: u128, reserve_out_norm: u128, now: i64) -> Result<u128> {
+    match pool.curve_type {
+        CurveType::ConstantProduct => price_ratio_x(reserve_out_norm, reserve_in_norm, pool.decimal_normalize_to),
+        CurveType::Weighted => {
+            let (weight_in, weight_out) = pool.weights_bps(stack_in);
+            price_ratio_x(
+                reserve_out_norm.checked_mul(weight_in).ok_or(AmmError::MathOverflow)?,
+                reserve_in_norm.checked_mul(weight_out).ok_or(AmmError::MathOverflow)?,
+                pool.decimal_normalize_to,
+            )
+        }
+        CurveType::StableSwap => {
+            let (x, y) = (reserve_in_norm, reserve_out_norm);
+            require!(x > 0 && y > 0, AmmError::NoLiquidity);
+            let amp = current_amp(pool, now)?;
+            let ann = (amp as u128) * 4;
+            let d = stable_invariant(amp, x, y)?;
+            let q = mul_div_wide(mul_div_wide(d, d, x * 2)?, d, y * 2)?;
+            let ann_x_q = ann.checked_mul(x).ok_or(AmmError::MathOverflow)?.checked_add(q).ok_or(AmmError::MathOverflow)?;
+            let ann_y_q = ann.checked_mul(y).ok_or(AmmError::MathOverflow)?.checked_add(q).ok_or(AmmError::MathOverflow)?;
+            let ratio = mul_div_wide(ann_x_q, 10u128.pow(pool.decimal_normalize_to as u32), ann_y_q)?;
+            mul_div_wide(ratio, y, x)
+        }
+    }
+}
 
-    /// vault authority PDA
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+/// floor(a * b / c) through a 256-bit product (the StableSwap terms multiply three reserve-sized values)
+fn mul_div_wide(a: u128, b: u128, c: u128) -> Result<u128> {
+    require!(c > 0, AmmError::MathOverflow);
+    if let Some(product) = a.checked_mul(b) {
+        return Ok(product / c);
+    }
+    let (hi, lo) = mul_u128_wide(a, b);
+    div_wide(hi, lo, c)
+}
 
-    pub token_program: Program<'info, Token>,
-    pub global: Account<'info, Global>,
+/// ceil(a * b / c) with a 256-bit intermediate
+fn mul_div_wide_ceil(a: u128, b: u128, c: u128) -> Result<u128> {
+    let quotient = mul_div_wide(a, b, c)?;
+    if mul_u128_wide(quotient, c) == mul_u128_wide(a, b) {
+        Ok(quotient)
+    } else {
+        Ok(quotient.checked_add(1).ok_or(AmmError::MathOverflow)?)
+    }
 }
 
-#[derive(Accounts)]
-pub struct RedeemStackViaPool<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub stack_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub quote_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_stack_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_quote_account: Account<'info, TokenAccount>,
-    #[account(mut, seeds=[b"stack_info", stack_mint.key().as_ref()], bump)]
-    pub stack_info: Account<'info, StackInfo>,
-    #[account(mut)]
-    pub protocol_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub creator_fee_vault: Account<'info, TokenAccount>,
+/// Full 256-bit product of two u128 as (high, low) halves
+fn mul_u128_wide(a: u128, b: u128) -> (u128, u128) {
+    let mask = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & mask);
+    let (b_hi, b_lo) = (b >> 64, b & mask);
+    let ll = a_lo * b_lo;
+    let lh = a_lo * b_hi;
+    let hl = a_hi * b_lo;
+    let hh = a_hi * b_hi;
+    let mid = (ll >> 64) + (lh & mask) + (hl & mask);
+    let lo = (ll & mask) | (mid << 64);
+    let hi = hh + (lh >> 64) + (hl >> 64) + (mid >> 64);
+    (hi, lo)
+}
 
-    /// treasury ATA
-    #[account(mut)]
-    pub treasury_token_account: Account<'info, TokenAccount>,
+/// floor((hi * 2^128 + lo) / c); the quotient must fit in u128
+fn div_wide(hi: u128, lo: u128, c: u128) -> Result<u128> {
+    require!(c > 0 && hi < c, AmmError::MathOverflow);
+    if hi == 0 {
+        return Ok(lo / c);
+    }
+    // restoring long division, one bit of `lo` at a time; `rem` stays below `c`
+    let mut rem = hi;
+    let mut quotient = 0u128;
+    for i in (0..128).rev() {
+        let carry = rem >> 127;
+        rem = (rem << 1) | ((lo >> i) & 1);
+        quotient <<= 1;
+        if carry == 1 || rem >= c {
+            rem = rem.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    Ok(quotient)
+}
 
-    /// vault authority PDA (needed by transfers)
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
+/* -----------------------
+   WEIGHTED CURVE (Balancer constant mean, WAD fixed point)
+   ----------------------- */
 
-    pub token_program: Program<'info, Token>,
-    pub global: Account<'info, Global>,
+/// Weighted get amount out: out = r_out (1 - (r_in / (r_in + a))^(w_in / w_out)), every rounding toward the pool
+fn weighted_amount_out(amount_in: u128, reserve_in: u128, reserve_out: u128, weight_in: u128, weight_out: u128) -> Result<u128> {
+    require!(reserve_in > 0 && reserve_out > 0, AmmError::NoLiquidity);
+    let new_in = reserve_in.checked_add(amount_in).ok_or(AmmError::MathOverflow)?;
+    // base (<= 1) rounded up and the exponent rounded down both keep the power high and the output low
+    let base = mul_div_wide(reserve_in, WAD, new_in)?.checked_add(1).ok_or(AmmError::MathOverflow)?.min(WAD);
+    let exponent = checked_mul_div(weight_in, WAD, weight_out)?;
+    let power = pow_wad_up(base, exponent)?.min(WAD);
+    let amount_out = mul_div_wide(reserve_out, WAD - power, WAD)?;
+    Ok(amount_out.min(reserve_out - 1))
 }
 
-/* Claim Creator Fees */
-#[derive(Accounts)]
-pub struct ClaimCreatorFees<'info> {
-    #[account(mut)]
-    pub creator: Signer<'info>,
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub stack_info: Account<'info, StackInfo>,
-    #[account(mut)]
-    pub quote_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub creator_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub creator_receiver: Account<'info, TokenAccount>,
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    pub token_program: Program<'info, Token>,
-    #[account(seeds=[b"global"], bump)]
-    pub global: Account<'info, Global>, // <-- ADDED global so we can read lock secs
+/// Weighted get amount in for an exact output: in = r_in ((r_out / (r_out - o))^(w_out / w_in) - 1), rounded up,
+/// then topped up until the forward curve covers `amount_out` (the two pow evaluations round independently)
+fn weighted_amount_in(amount_out: u128, reserve_in: u128, reserve_out: u128, weight_in: u128, weight_out: u128) -> Result<u128> {
+    require!(reserve_in > 0 && reserve_out > 0, AmmError::NoLiquidity);
+    require!(amount_out < reserve_out, AmmError::InsufficientLiquidity);
+    let base = mul_div_wide(reserve_out, WAD, reserve_out - amount_out)?.checked_add(1).ok_or(AmmError::MathOverflow)?;
+    let exponent = checked_mul_div(weight_out, WAD, weight_in)?.checked_add(1).ok_or(AmmError::MathOverflow)?;
+    let power = pow_wad_up(base, exponent)?;
+    let mut amount_in = mul_div_wide(reserve_in, power.saturating_sub(WAD), WAD)?.checked_add(1).ok_or(AmmError::MathOverflow)?;
+    for _ in 0..3 {
+        let out = weighted_amount_out(amount_in, reserve_in, reserve_out, weight_in, weight_out)?;
+        if out >= amount_out {
+            return Ok(amount_in);
+        }
+        // top up the shortfall at twice the post-trade marginal price (input per output)
+        let new_in = reserve_in.checked_add(amount_in).ok_or(AmmError::MathOverflow)?;
+        let shortfall = (amount_out - out).checked_mul(2 * weight_out).ok_or(AmmError::MathOverflow)?;
+        let top_up = mul_div_wide(shortfall, new_in, (reserve_out - out).checked_mul(weight_in).ok_or(AmmError::MathOverflow)?)?;
+        amount_in = amount_in.checked_add(top_up + 1).ok_or(AmmError::MathOverflow)?;
+    }
+    err!(AmmError::MathOverflow)
 }
 
-/* Pause/Resume context used for both emergency_pause and resume */
-#[derive(Accounts)]
-pub struct PauseResume<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(mut, seeds=[b"global"], bump)]
-    pub global: Account<'info, Global>,
+/// Weighted constant mean stack^w_s * quote^w_q (normalized units): the first-deposit LP of a weighted pool.
+/// `ln_wad(n)` of a plain integer is ln n - ln 1e18; the offset cancels because the weights sum to one, so
+/// `exp_wad` of the weighted sum is the mean itself in integer units.
+fn weighted_geometric_mean(stack: u128, quote: u128, stack_weight_bps: u128) -> Result<u128> {
+    if stack == 0 || quote == 0 {
+        return Ok(0);
+    }
+    let quote_weight_bps = BPS_DENOM - stack_weight_bps;
+    let weighted_ln = ln_wad(stack)?
+        .checked_mul(stack_weight_bps as i128).ok_or(AmmError::MathOverflow)?
+        .checked_add(ln_wad(quote)?.checked_mul(quote_weight_bps as i128).ok_or(AmmError::MathOverflow)?)
+        .ok_or(AmmError::MathOverflow)?
+        / BPS_DENOM as i128;
+    exp_wad(weighted_ln)
 }
 
-/* Emergency withdraw context uses same accounts as RemoveLiquidity but allows even when paused */
-#[derive(Accounts)]
-pub struct EmergencyWithdraw<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub lp_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub user_lp_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_stack_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_quote_account: Account<'info, TokenAccount>,
-    #[account(seeds=[b"vault_authority", pool.key().as_ref()], bump)]
-    pub vault_authority: UncheckedAccount<'info>,
-    pub token_program: Program<'info, Token>,
+/// base^exponent (both WAD) padded up by the worst-case relative error of the ln/exp evaluation
+fn pow_wad_up(base: u128, exponent: u128) -> Result<u128> {
+    let power = pow_wad(base, exponent)?;
+    let padding = mul_div_wide(power, MAX_POW_RELATIVE_ERROR, WAD)?;
+    power.checked_add(padding + 1).ok_or(AmmError::MathOverflow.into())
 }
 
-/* Withdraw protocol fees by admin/fee_manager/governance */
-#[derive(Accounts)]
-pub struct WithdrawProtocolFees<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub protocol_fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub admin_receiver: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub vault_authority: UncheckedAccount<'info>,
-    /// optional governance approval PDA
-    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
-    pub token_program: Program<'info, Token>,
-    #[account(seeds=[b"global"], bump)]
-    pub global: Account<'info, Global>,
+/// base^exponent = e^(exponent * ln base), both WAD fixed point
+fn pow_wad(base: u128, exponent: u128) -> Result<u128> {
+    if exponent == 0 {
+        return Ok(WAD);
+    }
+    let exponent = i128::try_from(exponent).map_err(|_| AmmError::MathOverflow)?;
+    let product = ln_wad(base)?.checked_mul(exponent).ok_or(AmmError::MathOverflow)?;
+    exp_wad(product / WAD as i128)
 }
 
-/* View mid price context */
-#[derive(Accounts)]
-pub struct ViewMidPrice<'info> {
-    pub pool: Account<'info, Pool>,
-    #[account(mut)]
-    pub stack_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub quote_vault: Account<'info, TokenAccount>,
+/// ln(x / 1e18), WAD-scaled. x = m * 2^k with m in [1, 2), then ln m = 2 atanh((m - 1) / (m + 1)) as a series
+/// in z^2 <= 1/9, so each term gains about one decimal digit.
+fn ln_wad(x: u128) -> Result<i128> {
+    require!(x > 0, AmmError::MathOverflow);
+    // 2^59 <= WAD < 2^60: moving the top bit to position 59 puts m in [0.57, 1.15) WAD
+    let mut k = 127 - x.leading_zeros() as i32 - 59;
+    let mut m = if k >= 0 { x >> k } else { x << -k };
+    if m < WAD {
+        m <<= 1;
+        k -= 1;
+    }
+    let z = (m - WAD) * WAD / (m + WAD);
+    let z2 = z * z / WAD;
+    let (mut sum, mut term, mut i) = (z, z, 1u128);
+    loop {
+        term = term * z2 / WAD;
+        if term == 0 {
+            break;
+        }
+        sum += term / (2 * i + 1);
+        i += 1;
+    }
+    Ok(k as i128 * LN2_WAD + 2 * sum as i128)
 }
 
-/* SetParams context for set_pool_params instruction */
-#[derive(Accounts)]
-pub struct SetParams<'info> {
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    #[account(mut)]
-    pub pool: Account<'info, Pool>,
-    #[account(seeds=[b"global"], bump)]
-    pub global: Account<'info, Global>,
-    /// optional governance approval PDA
-    pub governance_approval: Option<Account<'info, GovernanceApproval>>,
+/// e^(x / 1e18) as WAD. x = k ln2 + r with |r| <= ln2 / 2, e^r by its Taylor series, then shifted by k.
+/// Results under one WAD unit flush to zero; results past u128 are `MathOverflow`.
+fn exp_wad(x: i128) -> Result<u128> {
+    let wad = WAD as i128;
+    // e^-42 * 1e18 < 1
+    if x < -42 * wad {
+        return Ok(0);
+    }
+    let half_ln2 = if x >= 0 { LN2_WAD / 2 } else { -LN2_WAD / 2 };
+    let k = x.checked_add(half_ln2).ok_or(AmmError::MathOverflow)? / LN2_WAD;
+    let r = x - k * LN2_WAD;
+    let (mut sum, mut term, mut i) = (wad, wad, 1i128);
+    loop {
+        term = term * r / (i * wad);
+        if term == 0 {
+            break;
+        }
+        sum += term;
+        i += 1;
+    }
+    let e_r = sum as u128;
+    if k >= 0 {
+        require!(k < e_r.leading_zeros() as i128, AmmError::MathOverflow);
+        Ok(e_r << k)
+    } else {
+        Ok(e_r.checked_shr((-k) as u32).unwrap_or(0))
+    }
 }
 
 /* -----------------------
-   GovernanceApproval PDA
-   - added `nonce` for replay protection (must be strictly increasing per-pool)
+   CONCENTRATED LIQUIDITY MATH
+   tick t prices 1.0001^t quote per stack (native units); sqrt prices are Q64.64. Amounts follow
+   Uniswap v3: stack = L * (1/sqrt_lo - 1/sqrt_hi), quote = L * (sqrt_hi - sqrt_lo).
    ----------------------- */
 
-#[account]
-pub struct GovernanceApproval {
-    /// which pool this approval is for (or 0 if global)
-    pub target: Pubkey,
-    /// true if multisig has approved this action
-    pub approved: bool,
-    /// expiry to prevent reuse
-    pub expiry_ts: i64,
-    /// strictly increasing nonce to prevent replay of identical approvals
-    pub nonce: u128,
+/// sqrt(1.0001^tick) in Q64.64: the factors for each set bit of |tick| multiply to 1/sqrt(1.0001^|tick|),
+/// which is inverted for positive ticks
+fn sqrt_price_at_tick(tick: i32) -> Result<u128> {
+    require!((CL_MIN_TICK..=CL_MAX_TICK).contains(&tick), AmmError::InvalidTickRange);
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = if abs_tick & 1 != 0 { CL_SQRT_PRICE_FACTORS[0] } else { CL_Q64 };
+    for (bit, factor) in CL_SQRT_PRICE_FACTORS.iter().enumerate().skip(1) {
+        if abs_tick & (1 << bit) != 0 {
+            // ratio <= 2^64 and factor < 2^64, so the product fits
+            ratio = (ratio * factor) >> 64;
+        }
+    }
+    if tick > 0 {
+        ratio = u128::MAX / ratio;
+    }
+    Ok(ratio)
 }
-impl GovernanceApproval { const LEN: usize = 32 + 1 + 8 + 16; }
 
-/* -----------------------
-   EVENTS
-   ----------------------- */
+/// Greatest tick whose sqrt price is at or below `sqrt_price_x64`
+fn tick_at_sqrt_price(sqrt_price_x64: u128) -> Result<i32> {
+    require!(
+        sqrt_price_x64 >= sqrt_price_at_tick(CL_MIN_TICK)? && sqrt_price_x64 <= sqrt_price_at_tick(CL_MAX_TICK)?,
+        AmmError::InvalidSqrtPrice
+    );
+    let (mut lo, mut hi) = (CL_MIN_TICK, CL_MAX_TICK);
+    while lo < hi {
+        let mid = hi - (hi - lo) / 2;
+        if sqrt_price_at_tick(mid)? <= sqrt_price_x64 {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    Ok(lo)
+}
 
-#[event]
-pub struct GlobalInitialized { pub admin: Pubkey }
+/// Ticks covered by one tick array
+fn tick_array_span(tick_spacing: u16) -> i32 {
+    TICK_ARRAY_SIZE as i32 * tick_spacing as i32
+}
 
-#[event]
-pub struct StackRegistered {
-    pub stack_mint: Pubkey,
-    pub creator: Pubkey,
-    pub creator_fee_bps: u16,
+/// Start index of the tick array containing `tick`
+fn tick_array_start(tick: i32, tick_spacing: u16) -> i32 {
+    let span = tick_array_span(tick_spacing);
+    tick.div_euclid(span) * span
 }
 
-#[event]
-pub struct PoolCreated {
-    pub pool: Pubkey,
-    pub stack_mint: Pubkey,
-    pub quote_mint: Pubkey,
-    pub fee_bps: u16,
+/// Next initialized tick in the swap direction (at or below the current tick when selling stack, above it
+/// otherwise) as ((array, offset), tick). Without one in the loaded arrays, the edge of the last array is
+/// returned as the step target with no tick to cross.
+fn next_initialized_tick(
+    tick_arrays: &[Account<TickArray>],
+    tick_current: i32,
+    tick_spacing: u16,
+    stack_in: bool,
+) -> Result<(Option<(usize, usize)>, i32)> {
+    let spacing = tick_spacing as i32;
+    for (array_index, tick_array) in tick_arrays.iter().enumerate() {
+        let tick_at = |offset: usize| tick_array.start_tick_index + offset as i32 * spacing;
+        let found = if stack_in {
+            (0..TICK_ARRAY_SIZE).rev().find(|&offset| tick_at(offset) <= tick_current && tick_array.ticks[offset].liquidity_gross > 0)
+        } else {
+            (0..TICK_ARRAY_SIZE).find(|&offset| tick_at(offset) > tick_current && tick_array.ticks[offset].liquidity_gross > 0)
+        };
+        if let Some(offset) = found {
+            return Ok((Some((array_index, offset)), tick_at(offset)));
+        }
+    }
+    let last = tick_arrays.last().ok_or(AmmError::TickArrayMismatch)?;
+    let edge = if stack_in { last.start_tick_index } else { last.start_tick_index + tick_array_span(tick_spacing) };
+    Ok((None, edge.clamp(CL_MIN_TICK, CL_MAX_TICK)))
 }
 
-#[event]
-pub struct LiquidityProvided {
-    pub pool: Pubkey,
-    pub provider: Pubkey,
-    pub lp_minted: u64,
+/// Stack amount spanned by `liquidity` between two sqrt prices
+fn cl_amount_stack(sqrt_a_x64: u128, sqrt_b_x64: u128, liquidity: u128, round_up: bool) -> Result<u128> {
+    let (lo, hi) = if sqrt_a_x64 < sqrt_b_x64 { (sqrt_a_x64, sqrt_b_x64) } else { (sqrt_b_x64, sqrt_a_x64) };
+    require!(lo > 0, AmmError::InvalidSqrtPrice);
+    if round_up {
+        mul_div_wide_ceil(mul_div_wide_ceil(liquidity, CL_Q64, lo)?, hi - lo, hi)
+    } else {
+        mul_div_wide(mul_div_wide(liquidity, CL_Q64, lo)?, hi - lo, hi)
+    }
 }
 
-#[event]
-pub struct LiquidityRemoved {
-    pub pool: Pubkey,
-    pub provider: Pubkey,
-    pub lp_burned: u64,
+/// Quote amount spanned by `liquidity` between two sqrt prices
+fn cl_amount_quote(sqrt_a_x64: u128, sqrt_b_x64: u128, liquidity: u128, round_up: bool) -> Result<u128> {
+    let diff = sqrt_a_x64.abs_diff(sqrt_b_x64);
+    if round_up { mul_div_wide_ceil(liquidity, diff, CL_Q64) } else { mul_div_wide(liquidity, diff, CL_Q64) }
 }
 
-#[event]
-pub struct Swap {
-    pub pool: Pubkey,
-    pub trader: Pubkey,
-    pub side: SwapDirection,
-    pub amount_in: u64,
-    pub amount_out: u64,
+/// Sqrt price after adding `amount_in` to the pool, rounded so the price moves no further than the input pays for
+fn cl_next_sqrt_price(sqrt_price_x64: u128, liquidity: u128, amount_in: u128, stack_in: bool) -> Result<u128> {
+    if stack_in {
+        let denominator = liquidity
+            .checked_add(mul_div_wide(amount_in, sqrt_price_x64, CL_Q64)?)
+            .ok_or(AmmError::MathOverflow)?;
+        mul_div_wide_ceil(liquidity, sqrt_price_x64, denominator)
+    } else {
+        Ok(sqrt_price_x64.checked_add(mul_div_wide(amount_in, CL_Q64, liquidity)?).ok_or(AmmError::MathOverflow)?)
+    }
 }
 
-#[event]
-pub struct CreatorClaimed {
-    pub pool: Pubkey,
-    pub creator: Pubkey,
-    pub amount: u64,
+/// One swap step towards `sqrt_target_x64` within constant liquidity: (sqrt_next, amount_in, amount_out, fee).
+/// Inputs round up and outputs down; if the target isn't reached the whole remainder is consumed.
+fn cl_swap_step(
+    sqrt_price_x64: u128,
+    sqrt_target_x64: u128,
+    liquidity: u128,
+    amount_remaining: u128,
+    fee_bps: u16,
+    stack_in: bool,
+) -> Result<(u128, u128, u128, u128)> {
+    let keep_bps = BPS_DENOM.checked_sub(fee_bps as u128).ok_or(AmmError::InvalidFee)?;
+    let amount_less_fee = amount_remaining.checked_mul(keep_bps).ok_or(AmmError::MathOverflow)? / BPS_DENOM;
+    let amount_to_target = if stack_in {
+        cl_amount_stack(sqrt_target_x64, sqrt_price_x64, liquidity, true)?
+    } else {
+        cl_amount_quote(sqrt_price_x64, sqrt_target_x64, liquidity, true)?
+    };
+    let reached = amount_less_fee >= amount_to_target;
+    let sqrt_next = if reached { sqrt_target_x64 } else { cl_next_sqrt_price(sqrt_price_x64, liquidity, amount_less_fee, stack_in)? };
+
+    let (amount_in, amount_out) = if stack_in {
+        (
+            if reached { amount_to_target } else { cl_amount_stack(sqrt_next, sqrt_price_x64, liquidity, true)? },
+            cl_amount_quote(sqrt_next, sqrt_price_x64, liquidity, false)?,
+        )
+    } else {
+        (
+            if reached { amount_to_target } else { cl_amount_quote(sqrt_price_x64, sqrt_next, liquidity, true)? },
+            cl_amount_stack(sqrt_price_x64, sqrt_next, liquidity, false)?,
+        )
+    };
+    let fee = if reached {
+        checked_div_ceil(amount_in.checked_mul(fee_bps as u128).ok_or(AmmError::MathOverflow)?, keep_bps)?
+    } else {
+        amount_remaining.checked_sub(amount_in).ok_or(AmmError::MathOverflow)?
+    };
+    Ok((sqrt_next, amount_in, amount_out, fee))
 }
 
-#[event]
-pub struct ProtocolFeesWithdrawn {
-    pub pool: Pubkey,
-    pub to: Pubkey,
-    pub amount: u64,
+/// (stack, quote) fee growth per unit of liquidity inside [tick_lower, tick_upper), wrapping
+fn cl_fee_growth_inside(
+    lower: &Tick,
+    upper: &Tick,
+    tick_lower: i32,
+    tick_upper: i32,
+    tick_current: i32,
+    fee_growth_global: (u128, u128),
+) -> (u128, u128) {
+    let inside = |global: u128, lower_outside: u128, upper_outside: u128| {
+        let below = if tick_current >= tick_lower { lower_outside } else { global.wrapping_sub(lower_outside) };
+        let above = if tick_current < tick_upper { upper_outside } else { global.wrapping_sub(upper_outside) };
+        global.wrapping_sub(below).wrapping_sub(above)
+    };
+    (
+        inside(fee_growth_global.0, lower.fee_growth_outside_stack_x64, upper.fee_growth_outside_stack_x64),
+        inside(fee_growth_global.1, lower.fee_growth_outside_quote_x64, upper.fee_growth_outside_quote_x64),
+    )
 }
 
-#[event]
-pub struct EmergencyWithdrawal {
-    pub pool: Pubkey,
-    pub user: Pubkey,
-    pub lp_burned: u64,
+fn add_liquidity_delta(liquidity: u128, liquidity_delta: i128) -> Result<u128> {
+    let changed = if liquidity_delta < 0 {
+        liquidity.checked_sub(liquidity_delta.unsigned_abs())
+    } else {
+        liquidity.checked_add(liquidity_delta as u128)
+    };
+    Ok(changed.ok_or(AmmError::MathOverflow)?)
 }
 
-#[event]
-pub struct MidPrice {
-    pub pool: Pubkey,
-    pub price_x: u128,
+/* -----------------------
+   BACKING (NAV) MATH
+   NAV = reserve / backed_supply quote per stack; every rounding favors the reserve
+   ----------------------- */
+
+/// Stack minted for `net_quote` at NAV (rounded down)
+fn backing_stack_for_quote(backing: &Backing, net_quote: u64) -> Result<u64> {
+    let stack = if backing.backed_supply == 0 {
+        mul_div_wide(net_quote as u128, WAD, backing.initial_nav_wad)?
+    } else {
+        mul_div_wide(net_quote as u128, backing.backed_supply as u128, backing.reserve as u128)?
+    };
+    u64::try_from(stack).map_err(|_| AmmError::MathOverflow.into())
 }
 
-#[event]
-pub struct ProtocolPaused { pub by: Pubkey }
-#[event]
-pub struct ProtocolResumed { pub by: Pubkey }
-#[event]
-pub struct PoolParamsUpdated { pub pool: Pubkey, pub by: Pubkey }
+/// Quote needed to mint `stack` at NAV (rounded up); inverse of `backing_stack_for_quote`
+fn backing_quote_for_stack(backing: &Backing, stack: u64) -> Result<u64> {
+    let quote = if backing.backed_supply == 0 {
+        mul_div_wide_ceil(stack as u128, backing.initial_nav_wad, WAD)?
+    } else {
+        mul_div_wide_ceil(stack as u128, backing.reserve as u128, backing.backed_supply as u128)?
+    };
+    u64::try_from(quote).map_err(|_| AmmError::MathOverflow.into())
+}
 
-/* extra events for minted/redeemed flows */
-#[event]
-pub struct MintedStackViaPool { pub pool: Pubkey, pub user: Pubkey, pub quote_in: u64, pub stack_out: u64 }
-#[event]
-pub struct RedeemedStackViaPool { pub pool: Pubkey, pub user: Pubkey, pub stack_in: u64, pub quote_out: u64 }
-#[event]
-pub struct ProtocolFeesWithdrawn2 { pub pool: Pubkey, pub to: Pubkey, pub amount: u64 }
+/// Reserve share of `stack` redeemed at NAV (rounded down); `stack` must not exceed `backed_supply`
+fn backing_redeem_quote(backing: &Backing, stack: u64) -> Result<u64> {
+    let quote = mul_div_wide(stack as u128, backing.reserve as u128, backing.backed_supply as u128)?;
+    u64::try_from(quote).map_err(|_| AmmError::MathOverflow.into())
+}
 
 /* -----------------------
-   ENUMS & HELPERS
+   BONDING-CURVE LAUNCH MATH
+   prices are quote per stack in native units, WAD-scaled
    ----------------------- */
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum SwapDirection { StackToQuote, QuoteToStack }
-
-fn integer_sqrt(value: u128) -> u128 {
-    if value <= 1 { return value; }
-    let mut left: u128 = 1;
-    let mut right: u128 = value;
-    while left <= right {
-        let mid = (left + right) >> 1;
-        let sq = mid.saturating_mul(mid);
-        if sq == value { return mid; }
-        if sq < value { left = mid + 1; } else { right = mid - 1; }
+/// Curve price after `sold` stack
+fn launch_price_wad(curve: &BondingCurve, sold: u128) -> Result<u128> {
+    let p0 = curve.start_price_wad;
+    let scale = curve.curve_scale as u128;
+    match curve.kind {
+        BondingCurveKind::Linear => Ok(p0.checked_add(mul_div_wide(p0, sold, scale)?).ok_or(AmmError::MathOverflow)?),
+        BondingCurveKind::Exponential => {
+            let x: i128 = mul_div_wide(sold, WAD, scale)?.try_into().map_err(|_| AmmError::MathOverflow)?;
+            mul_div_wide(p0, exp_wad(x)?, WAD)
+        }
     }
-    right
 }
 
-/// central math wrapper example
-fn checked_mul_div(a: u128, b: u128, c: u128) -> Result<u128> {
-    let mul = a.checked_mul(b).ok_or(AmmError::MathOverflow)?;
-    let div = mul.checked_div(c).ok_or(AmmError::MathOverflow)?;
-    Ok(div)
+/// Quote cost of moving the curve from `from` to `to` sold (the integral of the price), rounded up for
+/// buys and down for sells so the reserve always covers every sell
+fn launch_cost(curve: &BondingCurve, from: u128, to: u128, round_up: bool) -> Result<u128> {
+    let p0 = curve.start_price_wad;
+    let scale = curve.curve_scale as u128;
+    let delta = to.checked_sub(from).ok_or(AmmError::MathOverflow)?;
+    let mul_div = if round_up { mul_div_wide_ceil } else { mul_div_wide };
+    match curve.kind {
+        BondingCurveKind::Linear => {
+            // p0 * delta * (1 + (from + to) / (2 * scale))
+            let twice_scale = scale * 2;
+            mul_div(mul_div(p0, delta, twice_scale)?, twice_scale + from + to, WAD)
+        }
+        BondingCurveKind::Exponential => {
+            // p0 * scale * (e^(to / scale) - e^(from / scale)); each exp is padded by MAX_POW_RELATIVE_ERROR
+            let exp_at = |sold: u128, up: bool| -> Result<u128> {
+                let x = if up { mul_div_wide_ceil(sold, WAD, scale)? } else { mul_div_wide(sold, WAD, scale)? };
+                let e = exp_wad(x.try_into().map_err(|_| AmmError::MathOverflow)?)?;
+                let pad = mul_div_wide(e, MAX_POW_RELATIVE_ERROR, WAD)? + 1;
+                Ok(if up { e.checked_add(pad).ok_or(AmmError::MathOverflow)? } else { e.saturating_sub(pad) })
+            };
+            let diff = exp_at(to, round_up)?.saturating_sub(exp_at(from, !round_up)?);
+            mul_div(mul_div(p0, diff, WAD)?, scale, WAD)
+        }
+    }
 }
 
-/// Normalize any token amount to `target_decimals` as u128 (for internal math)
-fn normalize_amount_u128(amount: u64, src_decimals: u8, target_decimals: u8) -> Result<u128> {
-    if src_decimals == target_decimals {
-        return Ok(amount as u128);
-    } else if src_decimals < target_decimals {
-        let mul = 10u128.pow((target_decimals - src_decimals) as u32);
-        Ok((amount as u128).checked_mul(mul).ok_or(AmmError::MathOverflow)?)
-    } else {
-        let div = 10u128.pow((src_decimals - target_decimals) as u32);
-        Ok((amount as u128).checked_div(div).ok_or(AmmError::MathOverflow)?)
+/// Most stack the curve sells for `quote_in` (bisection on the rounded-up cost)
+fn launch_amount_out(curve: &BondingCurve, quote_in: u128) -> Result<u128> {
+    let sold = curve.sold as u128;
+    // the price never drops below p0, which bounds the search
+    let (mut lo, mut hi) = (0u128, mul_div_wide(quote_in, WAD, curve.start_price_wad)?.min(u64::MAX as u128 - sold));
+    while lo < hi {
+        let mid = hi - (hi - lo) / 2;
+        // an overflowing cost is simply unaffordable
+        if matches!(launch_cost(curve, sold, sold + mid, true), Ok(cost) if cost <= quote_in) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
     }
+    Ok(lo)
 }
 
-/// Denormalize from normalized u128 back to native decimals u64 (floor)
-fn denormalize_amount_u64(amount_norm: u128, dst_decimals: u8, target_decimals: u8) -> Result<u64> {
-    if dst_decimals == target_decimals {
-        Ok(amount_norm.try_into().map_err(|_| AmmError::MathOverflow)?)
-    } else if dst_decimals < target_decimals {
-        let div = 10u128.pow((target_decimals - dst_decimals) as u32);
-        Ok(amount_norm.checked_div(div).ok_or(AmmError::MathOverflow)?.try_into().map_err(|_| AmmError::MathOverflow)?)
-    } else {
-        let mul = 10u128.pow((dst_decimals - target_decimals) as u32);
-        Ok(amount_norm.checked_mul(mul).ok_or(AmmError::MathOverflow)?.try_into().map_err(|_| AmmError::MathOverflow)?)
+/// Require a top-level `flash_repay` for `pool` after the current instruction in this transaction
+fn require_flash_repay(instructions: &AccountInfo, pool: Pubkey) -> Result<()> {
+    let current = load_current_index_checked(instructions)? as usize;
+    let mut index = current + 1;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions) {
+        if ix.program_id == crate::ID
+            && ix.data.get(..8) == Some(&<crate::instruction::FlashRepay as anchor_lang::Discriminator>::DISCRIMINATOR[..])
+            && ix.accounts.get(1).map(|meta| meta.pubkey) == Some(pool)
+        {
+            return Ok(());
+        }
+        index += 1;
     }
+    Err(AmmError::MissingFlashRepay.into())
 }
 
 /// Validate that token account matches the mint provided
-fn validate_token_account_matches_mint<'info>(token_acc: &Account<'info, TokenAccount>, mint: &Account<'info, Mint>) -> Result<()> {
+fn validate_token_account_matches_mint<'info>(token_acc: &InterfaceAccount<'info, TokenAccount>, mint: &InterfaceAccount<'info, Mint>) -> Result<()> {
     require_keys_eq!(token_acc.mint, mint.key(), AmmError::InvalidVaultMint);
     Ok(())
 }
@@ -1835,7 +7017,121 @@ fn get_amount_out(amount_in: u128, reserve_in: u128, reserve_out: u128) -> Resul
     Ok(out)
 }
 
-/// Compute fee breakdown in normalized units (centralized)
+/// constant-product get amount in for an exact output (rounds up, in the pool's favor)
+fn get_amount_in(amount_out: u128, reserve_in: u128, reserve_out: u128) -> Result<u128> {
+    // x*y=k: in = reserve_in * amount_out / (reserve_out - amount_out)
+    require!(reserve_in > 0 && reserve_out > 0, AmmError::NoLiquidity);
+    require!(amount_out < reserve_out, AmmError::InsufficientLiquidity);
+    let numerator = reserve_in.checked_mul(amount_out).ok_or(AmmError::MathOverflow)?;
+    let denominator = reserve_out - amount_out;
+    checked_div_ceil(numerator, denominator)
+}
+
+/// Curve output for `amount_in` (normalized units, pool fee already taken out of the input); `stack_in`
+/// orients the weights of a weighted pool
+fn curve_amount_out(pool: &Pool, stack_in: bool, amount_in: u128, reserve_in: u128, reserve_out: u128) -> Result<u128> {
+    match pool.curve_type {
+        CurveType::ConstantProduct => get_amount_out(amount_in, reserve_in, reserve_out),
+        CurveType::StableSwap => {
+            let amp = current_amp(pool, Clock::get()?.unix_timestamp)?;
+            stable_amount_out(amp, amount_in, reserve_in, reserve_out)
+        }
+        CurveType::Weighted => {
+            let (weight_in, weight_out) = pool.weights_bps(stack_in);
+            weighted_amount_out(amount_in, reserve_in, reserve_out, weight_in, weight_out)
+        }
+    }
+}
+
+/// Curve input (before the pool fee) needed for exactly `amount_out`, rounded in the pool's favor
+fn curve_amount_in(pool: &Pool, stack_in: bool, amount_out: u128, reserve_in: u128, reserve_out: u128) -> Result<u128> {
+    match pool.curve_type {
+        CurveType::ConstantProduct => get_amount_in(amount_out, reserve_in, reserve_out),
+        CurveType::StableSwap => {
+            let amp = current_amp(pool, Clock::get()?.unix_timestamp)?;
+            stable_amount_in(amp, amount_out, reserve_in, reserve_out)
+        }
+        CurveType::Weighted => {
+            let (weight_in, weight_out) = pool.weights_bps(stack_in);
+            weighted_amount_in(amount_out, reserve_in, reserve_out, weight_in, weight_out)
+        }
+    }
+}
+
+/// Smallest gross input whose `compute_fees` net amount covers `net_in` (inverse of the pool fee, rounded up)
+fn gross_up_for_fee(net_in: u128, pool_fee_bps: u16) -> Result<u128> {
+    let keep_bps = BPS_DENOM.checked_sub(pool_fee_bps as u128).ok_or(AmmError::InvalidFee)?;
+    checked_div_ceil(net_in.checked_mul(BPS_DENOM).ok_or(AmmError::MathOverflow)?, keep_bps)
+}
+
+/// Native input (fee included) needed for exactly `amount_out` native output, rounded up in the pool's favor
+#[allow(clippy::too_many_arguments)]
+fn exact_out_amount_in(
+    amount_out: u64,
+    reserve_in_norm: u128,
+    reserve_out_norm: u128,
+    in_decimals: u8,
+    out_decimals: u8,
+    target_decimals: u8,
+    pool: &Pool,
+    stack_in: bool,
+) -> Result<u64> {
+    let amount_out_norm = normalize_amount_u128_ceil(amount_out, out_decimals, target_decimals)?;
+    let net_in = curve_amount_in(pool, stack_in, amount_out_norm, reserve_in_norm, reserve_out_norm)?;
+    let gross_in_norm = gross_up_for_fee(net_in, pool.fee_bps)?;
+    denormalize_amount_u64_ceil(gross_in_norm, in_decimals, target_decimals)
+}
+
+/// Native output actually paid: the curve output for exact-in (must reach `min_out`), or exactly the
+/// requested amount for exact-out (the curve output must cover it; any rounding surplus stays in the pool).
+/// `out_mint` is the mint transferred out of a vault, whose Token-2022 transfer fee is charged on the way:
+/// `min_out` is checked against the net received and exact-out sends enough to deliver `amount_out`.
+/// `None` when the output is minted rather than transferred.
+fn settle_amount_out(
+    amount: SwapAmount,
+    amount_out_norm: u128,
+    out_decimals: u8,
+    target_decimals: u8,
+    out_mint: Option<&InterfaceAccount<Mint>>,
+) -> Result<u64> {
+    let curve_out = denormalize_amount_u64(amount_out_norm, out_decimals, target_decimals)?;
+    match amount {
+        SwapAmount::ExactIn { min_out, .. } => {
+            let received = match out_mint {
+                Some(mint) => amount_after_transfer_fee(mint, curve_out)?,
+                None => curve_out,
+            };
+            require!(received >= min_out, AmmError::SlippageExceeded);
+            Ok(curve_out)
+        }
+        SwapAmount::ExactOut { amount_out, .. } => {
+            let sent = match out_mint {
+                Some(mint) => transfer_amount_for(mint, amount_out)?,
+                None => amount_out,
+            };
+            require!(curve_out >= sent, AmmError::SlippageExceeded);
+            Ok(sent)
+        }
+    }
+}
+
+/// Execution price vs. spot price shortfall in bps (0 when the trade is too small to measure)
+fn price_impact_bps(pool: &Pool, stack_in: bool, net_in: u128, amount_out: u128, reserve_in: u128, reserve_out: u128) -> Result<u16> {
+    let spot_out = match pool.curve_type {
+        CurveType::ConstantProduct => checked_mul_div(net_in, reserve_out, reserve_in)?,
+        CurveType::StableSwap | CurveType::Weighted => {
+            let spot_price = spot_price_x(pool, stack_in, reserve_in, reserve_out, Clock::get()?.unix_timestamp)?;
+            mul_div_wide(net_in, spot_price, 10u128.pow(pool.decimal_normalize_to as u32))?
+        }
+    };
+    if spot_out == 0 {
+        return Ok(0);
+    }
+    let impact = checked_mul_div(spot_out.saturating_sub(amount_out), BPS_DENOM, spot_out)?;
+    Ok(impact.min(BPS_DENOM) as u16)
+}
+
+/// Compute fee breakdown (gross, protocol, creator, net) in the units of `amount_norm` (centralized)
 fn compute_fees(amount_norm: u128, pool_fee_bps: u16, protocol_fee_bps: u16, creator_fee_bps: u16) -> Result<(u128, u128, u128, u128)> {
     // gross_fee = amount_norm * pool_fee_bps / BPS_DENOM
     let gross_fee = amount_norm
@@ -1851,150 +7147,504 @@ fn compute_fees(amount_norm: u128, pool_fee_bps: u16, protocol_fee_bps: u16, cre
     Ok((gross_fee, protocol_fee, creator_fee, net_in))
 }
 
-/// Route normalized protocol & creator fee (in *input* denom) -> quote native vaults by simulating AMM output.
-/// This helper calculates the quote-equivalent of a normalized input fee via `get_amount_out` and transfers native quote tokens
-/// from `quote_vault` -> protocol_fee_vault / creator_fee_vault.
-/// Returns normalized quote-equivalents (protocol_fee_quote_norm, creator_fee_quote_norm) so caller can update pool state.
+/// Trading reserve held in a vault: its balance minus the protocol/creator fees accrued there
+fn vault_reserve(vault_amount: u64, accrued_fees: u64) -> Result<u64> {
+    Ok(vault_amount.checked_sub(accrued_fees).ok_or(AmmError::MathOverflow)?)
+}
+
+/// Book a swap's fee split (native units of the fee token). The protocol & creator shares accrue on the
+/// pool until withdrawn/claimed; the LP share is simply left in the vault, growing the reserves (and k).
+fn book_swap_fees(pool: &mut Pool, pool_key: Pubkey, fee_in_stack: bool, gross_fee: u128, protocol_fee: u128, creator_fee: u128) -> Result<()> {
+    let lp_fee: u64 = gross_fee
+        .checked_sub(protocol_fee).ok_or(AmmError::MathOverflow)?
+        .checked_sub(creator_fee).ok_or(AmmError::MathOverflow)?
+        .try_into().map_err(|_| AmmError::MathOverflow)?;
+    let protocol_fee: u64 = protocol_fee.try_into().map_err(|_| AmmError::MathOverflow)?;
+    let creator_fee: u64 = creator_fee.try_into().map_err(|_| AmmError::MathOverflow)?;
+
+    let fee_mint = if fee_in_stack {
+        pool.protocol_fees_stack = pool.protocol_fees_stack.checked_add(protocol_fee).ok_or(AmmError::MathOverflow)?;
+        pool.creator_fees_stack = pool.creator_fees_stack.checked_add(creator_fee).ok_or(AmmError::MathOverflow)?;
+        pool.stack_mint
+    } else {
+        pool.protocol_fees_quote = pool.protocol_fees_quote.checked_add(protocol_fee).ok_or(AmmError::MathOverflow)?;
+        pool.creator_fees_quote = pool.creator_fees_quote.checked_add(creator_fee).ok_or(AmmError::MathOverflow)?;
+        pool.quote_mint
+    };
+    if creator_fee > 0 {
+        pool.creator_last_claim_ts = Clock::get()?.unix_timestamp;
+    }
+
+    emit!(SwapFees { pool: pool_key, fee_mint, lp_fee, protocol_fee, creator_fee });
+    Ok(())
+}
+
+/// Sweep a vault's trading reserve to the treasury once it falls to `dust_threshold` or below.
+/// Reloads the vault first; accrued protocol/creator fees held in it are left untouched. A treasury
+/// account for a different mint is skipped rather than failing the whole instruction.
 #[allow(clippy::too_many_arguments)]
-fn route_fee_to_quote_vault<'info>(
-    token_program: &Program<'info, Token>,
-    quote_vault: &Account<'info, TokenAccount>,
-    protocol_fee_vault: &Account<'info, TokenAccount>,
-    creator_fee_vault: &Account<'info, TokenAccount>,
+fn sweep_vault_dust<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    mint: &InterfaceAccount<'info, Mint>,
+    hook_accounts: &[AccountInfo<'info>],
+    vault: &mut InterfaceAccount<'info, TokenAccount>,
+    treasury: &InterfaceAccount<'info, TokenAccount>,
     vault_authority: &UncheckedAccount<'info>,
     pool_key: Pubkey,
-    pool_bump: u8,
-    protocol_fee_norm: u128,
-    creator_fee_norm: u128,
-    reserve_in_norm: u128,
-    reserve_out_norm: u128,
-    quote_decimals: u8,
-    target_decimals: u8,
-) -> Result<(u128, u128)> {
-    // compute quote amount for each normalized fee using constant-product simulation
-    let protocol_fee_quote_norm = get_amount_out(protocol_fee_norm, reserve_in_norm, reserve_out_norm)?;
-    let creator_fee_quote_norm = get_amount_out(creator_fee_norm, reserve_in_norm, reserve_out_norm)?;
-
-    // denormalize to native quote units
-    let protocol_fee_quote_native = denormalize_amount_u64(protocol_fee_quote_norm, quote_decimals, target_decimals)?;
-    let creator_fee_quote_native = denormalize_amount_u64(creator_fee_quote_norm, quote_decimals, target_decimals)?;
-
-    // transfer protocol fee
-    if protocol_fee_quote_native > 0 {
-        token::transfer(
-            CpiContext::new(
-                token_program.to_account_info(),
-                Transfer {
-                    from: quote_vault.to_account_info().clone(),
-                    to: protocol_fee_vault.to_account_info().clone(),
-                    authority: vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-            protocol_fee_quote_native,
-        )?;
+    vault_authority_bump: u8,
+    accrued_fees: u64,
+    dust_threshold: u64,
+) -> Result<()> {
+    if treasury.mint != vault.mint {
+        return Ok(());
     }
-
-    // transfer creator fee
-    if creator_fee_quote_native > 0 {
-        token::transfer(
-            CpiContext::new(
-                token_program.to_account_info(),
-                Transfer {
-                    from: quote_vault.to_account_info().clone(),
-                    to: creator_fee_vault.to_account_info().clone(),
-                    authority: vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-            creator_fee_quote_native,
-        )?;
+    vault.reload()?;
+    let amt = vault_reserve(vault.amount, accrued_fees)?;
+    if amt > 0 && amt <= dust_threshold {
+        transfer_from_vault(token_program, vault, treasury, mint, hook_accounts, vault_authority, pool_key, vault_authority_bump, amt)?;
     }
+    Ok(())
+}
 
-    Ok((protocol_fee_quote_norm, creator_fee_quote_norm))
+/* -----------------------
+   TOKEN TRANSFERS (SPL Token & Token-2022)
+   ----------------------- */
+
+/// User-signed `transfer_checked` into a pool account
+fn transfer_from_user<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    hook_accounts: &[AccountInfo<'info>],
+    authority: &Signer<'info>,
+    amount: u64,
+) -> Result<()> {
+    transfer_checked_with_hook(
+        token_program,
+        from.to_account_info(),
+        to.to_account_info(),
+        mint,
+        authority.to_account_info(),
+        hook_accounts,
+        amount,
+        &[],
+    )
 }
 
-/// Route normalized protocol & creator fee (in quote denom) -> stack native vaults by simulating AMM output.
-/// This helper transfers stack native tokens from `stack_vault` -> protocol_fee_vault / creator_fee_vault,
-/// and increments `pool.creator_claimable` by the normalized creator fee in *quote norm* when provided.
+/// Vault-authority-signed `transfer_checked` out of a pool vault
 #[allow(clippy::too_many_arguments)]
-fn route_fee_to_stack_vault<'info>(
-    token_program: &Program<'info, Token>,
-    stack_vault: &Account<'info, TokenAccount>,
-    protocol_fee_vault: &Account<'info, TokenAccount>,
-    creator_fee_vault: &Account<'info, TokenAccount>,
-    vault_authority: &UncheckedAccount<'info>,
+fn transfer_from_vault<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    hook_accounts: &[AccountInfo<'info>],
+    vault_authority: &AccountInfo<'info>,
     pool_key: Pubkey,
-    pool_bump: u8,
-    protocol_fee_norm: u128,
-    creator_fee_norm: u128,
-    reserve_quote_norm: u128,
-    reserve_stack_norm: u128,
-    stack_decimals: u8,
-    target_decimals: u8,
-    pool: &mut Account<'info, Pool>,
+    vault_authority_bump: u8,
+    amount: u64,
 ) -> Result<()> {
-    // compute stack amount for each normalized fee using constant-product simulation (quote->stack)
-    let protocol_fee_stack_norm = get_amount_out(protocol_fee_norm, reserve_quote_norm, reserve_stack_norm)?;
-    let creator_fee_stack_norm = get_amount_out(creator_fee_norm, reserve_quote_norm, reserve_stack_norm)?;
-    let protocol_fee_stack_native = denormalize_amount_u64(protocol_fee_stack_norm, stack_decimals, target_decimals)?;
-    let creator_fee_stack_native = denormalize_amount_u64(creator_fee_stack_norm, stack_decimals, target_decimals)?;
-
-    if protocol_fee_stack_native > 0 {
-        token::transfer(
-            CpiContext::new(
+    transfer_checked_with_hook(
+        token_program,
+        vault.to_account_info(),
+        to.to_account_info(),
+        mint,
+        vault_authority.clone(),
+        hook_accounts,
+        amount,
+        &[&[b"vault_authority", pool_key.as_ref(), &[vault_authority_bump]]],
+    )
+}
+
+/// `transfer_checked` CPI. For a Token-2022 mint with a transfer hook, the hook program, its
+/// extra-account-metas PDA and every account that list resolves to are looked up in `hook_accounts`
+/// (the instruction's remaining_accounts) and forwarded; otherwise `hook_accounts` is ignored.
+#[allow(clippy::too_many_arguments)]
+fn transfer_checked_with_hook<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    from: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    authority: AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    if mint_transfer_hook(mint)?.is_none() {
+        return token_interface::transfer_checked(
+            CpiContext::new_with_signer(
                 token_program.to_account_info(),
-                Transfer {
-                    from: stack_vault.to_account_info().clone(),
-                    to: protocol_fee_vault.to_account_info().clone(),
-                    authority: vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-            protocol_fee_stack_native,
-        )?;
+                TransferChecked { from, mint: mint.to_account_info(), to, authority },
+                signer_seeds,
+            ),
+            amount,
+            mint.decimals,
+        );
     }
-    if creator_fee_stack_native > 0 {
-        token::transfer(
-            CpiContext::new(
-                token_program.to_account_info(),
-                Transfer {
-                    from: stack_vault.to_account_info().clone(),
-                    to: creator_fee_vault.to_account_info().clone(),
-                    authority: vault_authority.to_account_info().clone(),
-                },
-            )
-            .with_signer(&[&[b"vault_authority", pool_key.as_ref(), &[pool_bump]]]),
-            creator_fee_stack_native,
-        )?;
-        // increment normalized creator claimable (simulate quote-equivalent by computing via get_amount_out above)
-        // We compute quote-equivalent of creator fee by simulating the opposite direction:
-        let creator_fee_quote_norm = get_amount_out(creator_fee_stack_norm, reserve_stack_norm, reserve_quote_norm)?;
-        pool.creator_claimable = pool.creator_claimable.checked_add(creator_fee_quote_norm).ok_or(AmmError::MathOverflow)?;
-        pool.creator_last_claim_ts = Clock::get()?.unix_timestamp;
+    invoke_transfer_checked(
+        token_program.key,
+        from,
+        mint.to_account_info(),
+        to,
+        authority,
+        hook_accounts,
+        amount,
+        mint.decimals,
+        signer_seeds,
+    )?;
+    Ok(())
+}
+
+/// Transfer-hook program of a Token-2022 mint, if it has one
+fn mint_transfer_hook(mint: &InterfaceAccount<Mint>) -> Result<Option<Pubkey>> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(transfer_hook::get_program_id(&state))
+}
+
+/// Token-2022 transfer fee withheld when `amount` is sent in the current epoch (0 for classic SPL mints)
+fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    match state.get_extension::<TransferFeeConfig>() {
+        Ok(cfg) => Ok(cfg.calculate_epoch_fee(Clock::get()?.epoch, amount).ok_or(AmmError::MathOverflow)?),
+        Err(_) => Ok(0),
     }
+}
+
+/// Amount to send so that `received` arrives after the Token-2022 transfer fee (identity for classic mints)
+fn transfer_amount_for(mint: &InterfaceAccount<Mint>, received: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID || received == 0 {
+        return Ok(received);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let fee = match state.get_extension::<TransferFeeConfig>() {
+        Ok(cfg) => cfg.calculate_inverse_epoch_fee(Clock::get()?.epoch, received).ok_or(AmmError::MathOverflow)?,
+        Err(_) => 0,
+    };
+    Ok(received.checked_add(fee).ok_or(AmmError::MathOverflow)?)
+}
 
+/// Net amount that lands in the destination when `amount` is sent
+fn amount_after_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    Ok(amount.checked_sub(transfer_fee(mint, amount)?).ok_or(AmmError::MathOverflow)?)
+}
+
+/// Reject Token-2022 mints whose extensions break pool accounting or custody: a permanent delegate can
+/// drain the vaults, non-transferable tokens can't be swapped, and frozen-by-default / confidential /
+/// closable mints can strand reserves. Transfer fees are accounted for explicitly; metadata and
+/// interest-bearing display are harmless. A transfer hook is accepted only when `hook_allowlist` is
+/// given and holds its program (an unknown hook could fail every vault transfer and brick the pool).
+fn validate_mint_extensions(mint: &InterfaceAccount<Mint>, hook_allowlist: Option<&Vec<Pubkey>>) -> Result<()> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = mint_info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    for ext in state.get_extension_types()? {
+        match ext {
+            ExtensionType::TransferFeeConfig
+            | ExtensionType::InterestBearingConfig
+            | ExtensionType::MetadataPointer
+            | ExtensionType::TokenMetadata => {}
+            ExtensionType::TransferHook => {
+                if let Some(program_id) = transfer_hook::get_program_id(&state) {
+                    let allowed = hook_allowlist.map(|list| list.contains(&program_id)).unwrap_or(false);
+                    require!(allowed, AmmError::TransferHookNotAllowed);
+                }
+            }
+            _ => return err!(AmmError::UnsupportedMintExtension),
+        }
+    }
     Ok(())
 }
 
-/// Validate GovernanceApproval PDA and provide simple replay protection by requiring approval.nonce > pool.last_governance_nonce.
-/// This function *mutates* the provided `pool` to update `last_governance_nonce` so approvals cannot be replayed.
-fn validate_governance_approval<'info>(maybe_approval: &Option<Account<'info, GovernanceApproval>>, target: Pubkey, pool: &mut Account<'info, Pool>) -> Result<()> {
-    match maybe_approval {
-        Some(approval) => {
-            require!(approval.approved, AmmError::GovernanceNotApproved);
-            let now = Clock::get()?.unix_timestamp;
-            require!(approval.expiry_ts >= now, AmmError::GovernanceApprovalExpired);
-            require_keys_eq!(approval.target, target, AmmError::GovernanceApprovalTargetMismatch);
+/* -----------------------
+   TWAP ACCUMULATORS
+   ----------------------- */
+
+/// price_x = numer * 10^decimals / denom (same scaling as `view_mid_price`)
+fn price_ratio_x(numer_norm: u128, denom_norm: u128, decimals: u8) -> Result<u128> {
+    let price = numer_norm
+        .checked_mul(10u128.pow(decimals as u32)).ok_or(AmmError::MathOverflow)?
+        .checked_div(denom_norm).ok_or(AmmError::MathOverflow)?;
+    Ok(price)
+}
+
+/// Pool accumulators extrapolated to `now` assuming the given reserves held since `pool.last_price_update_ts`
+fn current_cumulative_prices(pool: &Pool, reserve_stack_norm: u128, reserve_quote_norm: u128, now: i64) -> Result<Observation> {
+    let mut obs = Observation {
+        timestamp: now,
+        cumulative_quote_per_stack: pool.price_cumulative_quote_per_stack,
+        cumulative_stack_per_quote: pool.price_cumulative_stack_per_quote,
+    };
+    let elapsed = now.checked_sub(pool.last_price_update_ts).ok_or(AmmError::MathOverflow)?;
+    if elapsed > 0 && reserve_stack_norm > 0 && reserve_quote_norm > 0 {
+        let quote_per_stack = spot_price_x(pool, true, reserve_stack_norm, reserve_quote_norm, now)?;
+        let stack_per_quote = spot_price_x(pool, false, reserve_quote_norm, reserve_stack_norm, now)?;
+        // accumulators are allowed to wrap; consumers only ever look at differences
+        obs.cumulative_quote_per_stack = obs.cumulative_quote_per_stack.wrapping_add(quote_per_stack.wrapping_mul(elapsed as u128));
+        obs.cumulative_stack_per_quote = obs.cumulative_stack_per_quote.wrapping_add(stack_per_quote.wrapping_mul(elapsed as u128));
+    }
+    Ok(obs)
+}
+
+/// Accumulate the price implied by the reserves *before* this instruction changes them, and record an
+/// observation (at most one per second). Must run before any transfer that moves pool reserves.
+fn update_price_accumulators(
+    pool: &mut Account<Pool>,
+    observations: &mut Account<PoolObservations>,
+    reserve_stack_norm: u128,
+    reserve_quote_norm: u128,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    if now <= pool.last_price_update_ts {
+        return Ok(());
+    }
+    let obs = current_cumulative_prices(pool, reserve_stack_norm, reserve_quote_norm, now)?;
+    pool.price_cumulative_quote_per_stack = obs.cumulative_quote_per_stack;
+    pool.price_cumulative_stack_per_quote = obs.cumulative_stack_per_quote;
+    pool.last_price_update_ts = now;
+
+    if observations.observations.len() < OBSERVATION_CAPACITY {
+        observations.observations.push(obs);
+        observations.index = (observations.observations.len() - 1) as u16;
+    } else {
+        let next = (observations.index as usize + 1) % OBSERVATION_CAPACITY;
+        observations.observations[next] = obs;
+        observations.index = next as u16;
+    }
+    Ok(())
+}
 
-            // replay protection: nonce must be strictly increasing
-            require!(approval.nonce > pool.last_governance_nonce, AmmError::GovernanceNotApproved);
-            // update pool to consume nonce — this changes pool state so caller must pass mutable pool
-            pool.last_governance_nonce = approval.nonce;
-            Ok(())
+/// Cumulative prices at `target_ts`, linearly interpolated between the surrounding observations
+/// (exact, since the price is constant between two consecutive accumulator updates).
+fn cumulative_prices_at(observations: &PoolObservations, current: Observation, target_ts: i64) -> Result<Observation> {
+    let mut before: Option<Observation> = None;
+    let mut after: Option<Observation> = None;
+    for obs in observations.observations.iter() {
+        if obs.timestamp <= target_ts {
+            if !matches!(before, Some(b) if b.timestamp >= obs.timestamp) {
+                before = Some(*obs);
+            }
+        } else if !matches!(after, Some(a) if a.timestamp <= obs.timestamp) {
+            after = Some(*obs);
         }
-        None => Err(AmmError::GovernanceApprovalMissing.into()),
     }
+    let before = before.ok_or(AmmError::TwapWindowTooLong)?;
+    if before.timestamp == target_ts {
+        return Ok(before);
+    }
+    // no later observation: the live accumulators close the interval
+    let after = match after {
+        Some(a) => a,
+        None => current,
+    };
+
+    let span = (after.timestamp - before.timestamp) as u128;
+    let offset = (target_ts - before.timestamp) as u128;
+    let interpolate = |lo: u128, hi: u128| -> Result<u128> {
+        let delta = hi.wrapping_sub(lo)
+            .checked_mul(offset).ok_or(AmmError::MathOverflow)?
+            .checked_div(span).ok_or(AmmError::MathOverflow)?;
+        Ok(lo.wrapping_add(delta))
+    };
+    Ok(Observation {
+        timestamp: target_ts,
+        cumulative_quote_per_stack: interpolate(before.cumulative_quote_per_stack, after.cumulative_quote_per_stack)?,
+        cumulative_stack_per_quote: interpolate(before.cumulative_stack_per_quote, after.cumulative_stack_per_quote)?,
+    })
+}
+
+/* -----------------------
+   ORACLE READERS
+   ----------------------- */
+
+/// Raw oracle reading: `price * 10^expo` quote per stack, with confidence interval in the same units
+struct OraclePrice {
+    price: i64,
+    expo: i32,
+    conf: u64,
+    publish_ts: i64,
+}
+
+/// Decode an oracle account according to its declared source (checks owner & layout, not freshness)
+fn load_oracle_price(source: OracleSource, oracle: &AccountInfo) -> Result<OraclePrice> {
+    match source {
+        OracleSource::Pyth => read_pyth_price(oracle),
+        #[cfg(feature = "test-feed")]
+        OracleSource::TestFeed => read_test_feed_price(oracle),
+        OracleSource::None => Err(AmmError::InvalidOracle.into()),
+    }
+}
+
+/// Pyth v2 price account: aggregate price/conf/status plus the aggregate publish timestamp
+fn read_pyth_price(oracle: &AccountInfo) -> Result<OraclePrice> {
+    require!(
+        *oracle.owner == pyth_mainnet::ID || *oracle.owner == pyth_devnet::ID,
+        AmmError::InvalidOracle
+    );
+    let data = oracle.try_borrow_data()?;
+    require!(data.len() >= PYTH_PRICE_ACCOUNT_MIN_LEN, AmmError::InvalidOracle);
+    require!(read_u32_le(&data, 0)? == PYTH_MAGIC, AmmError::InvalidOracle);
+    require!(read_u32_le(&data, 4)? == PYTH_VERSION_2, AmmError::InvalidOracle);
+    require!(read_u32_le(&data, 8)? == PYTH_ACCOUNT_TYPE_PRICE, AmmError::InvalidOracle);
+    require!(read_u32_le(&data, PYTH_AGG_STATUS_OFFSET)? == PYTH_STATUS_TRADING, AmmError::InvalidOraclePrice);
+
+    Ok(OraclePrice {
+        price: read_u64_le(&data, PYTH_AGG_PRICE_OFFSET)? as i64,
+        expo: read_u32_le(&data, PYTH_EXPO_OFFSET)? as i32,
+        conf: read_u64_le(&data, PYTH_AGG_CONF_OFFSET)?,
+        publish_ts: read_u64_le(&data, PYTH_TIMESTAMP_OFFSET)? as i64,
+    })
+}
+
+/// Program-owned `TestPriceFeed` (owner + account discriminator checked)
+#[cfg(feature = "test-feed")]
+fn read_test_feed_price(oracle: &AccountInfo) -> Result<OraclePrice> {
+    require_keys_eq!(*oracle.owner, crate::ID, AmmError::InvalidOracle);
+    let data = oracle.try_borrow_data()?;
+    let feed = TestPriceFeed::try_deserialize(&mut &data[..]).map_err(|_| AmmError::InvalidOracle)?;
+    Ok(OraclePrice {
+        price: feed.price,
+        expo: feed.expo,
+        conf: feed.conf,
+        publish_ts: feed.publish_ts,
+    })
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data.get(offset..offset + 4).ok_or(AmmError::InvalidOracle)?;
+    Ok(u32::from_le_bytes(bytes.try_into().map_err(|_| AmmError::InvalidOracle)?))
+}
+
+fn read_u64_le(data: &[u8], offset: usize) -> Result<u64> {
+    let bytes = data.get(offset..offset + 8).ok_or(AmmError::InvalidOracle)?;
+    Ok(u64::from_le_bytes(bytes.try_into().map_err(|_| AmmError::InvalidOracle)?))
+}
+
+/// Read the pool oracle and return its price scaled like `view_mid_price` (quote per stack * 10^decimal_normalize_to).
+/// Enforces the pool's staleness and confidence limits. Returns None when the pool has no oracle configured.
+fn read_pool_oracle_price(pool: &Pool, oracle: &AccountInfo) -> Result<Option<u128>> {
+    if pool.oracle_source == OracleSource::None {
+        return Ok(None);
+    }
+    require_keys_eq!(oracle.key(), pool.oracle, AmmError::InvalidOracle);
+    let reading = load_oracle_price(pool.oracle_source, oracle)?;
+    require!(reading.price > 0, AmmError::InvalidOraclePrice);
+
+    let now = Clock::get()?.unix_timestamp;
+    let age = now.checked_sub(reading.publish_ts).ok_or(AmmError::MathOverflow)?;
+    require!(age <= pool.oracle_max_staleness_secs, AmmError::OracleStale);
+
+    // confidence interval relative to price, in bps
+    let conf_bps = (reading.conf as u128)
+        .checked_mul(BPS_DENOM).ok_or(AmmError::MathOverflow)?
+        .checked_div(reading.price as u128).ok_or(AmmError::MathOverflow)?;
+    require!(conf_bps <= pool.oracle_max_conf_bps as u128, AmmError::OracleConfidenceTooWide);
+
+    Ok(Some(scale_oracle_price(reading.price as u128, reading.expo, pool.decimal_normalize_to)?))
+}
+
+/// price * 10^expo * 10^target_decimals, computed without intermediate precision loss
+fn scale_oracle_price(price: u128, expo: i32, target_decimals: u8) -> Result<u128> {
+    let exp = (target_decimals as i32).checked_add(expo).ok_or(AmmError::MathOverflow)?;
+    if exp >= 0 {
+        let mul = 10u128.checked_pow(exp as u32).ok_or(AmmError::MathOverflow)?;
+        Ok(price.checked_mul(mul).ok_or(AmmError::MathOverflow)?)
+    } else {
+        let div = 10u128.checked_pow(exp.unsigned_abs()).ok_or(AmmError::MathOverflow)?;
+        Ok(price.checked_div(div).ok_or(AmmError::MathOverflow)?)
+    }
+}
+
+/// Compare the executed price (quote per stack, normalized amounts) against the pool oracle and enforce
+/// `pool.max_price_deviation_bps`. No-op for pools created with `OracleSource::None`.
+fn enforce_oracle_guardrail(pool: &Pool, oracle: &AccountInfo, quote_norm: u128, stack_norm: u128) -> Result<()> {
+    let oracle_price_x = match read_pool_oracle_price(pool, oracle)? {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    if stack_norm == 0 {
+        return Err(AmmError::SlippageExceeded.into());
+    }
+    let implied_price_x = quote_norm
+        .checked_mul(10u128.pow(pool.decimal_normalize_to as u32)).ok_or(AmmError::MathOverflow)?
+        .checked_div(stack_norm).ok_or(AmmError::MathOverflow)?;
+    require!(oracle_price_x > 0, AmmError::InvalidOraclePrice);
+    let allowed = pool.max_price_deviation_bps as u128;
+    let diff = oracle_price_x.abs_diff(implied_price_x);
+    let pct = diff.checked_mul(BPS_DENOM).ok_or(AmmError::MathOverflow)?.checked_div(oracle_price_x).ok_or(AmmError::MathOverflow)?;
+    require!(pct <= allowed, AmmError::OraclePriceMismatch);
+    Ok(())
+}
+
+/// Check a pool-targeted approval against the instruction's action and payload, consume its nonce and close it
+/// (rent back to its payer), so each approval authorizes exactly one call
+fn consume_governance_approval<'info>(
+    maybe_approval: &Option<Account<'info, GovernanceApproval>>,
+    rent_receiver: &Option<UncheckedAccount<'info>>,
+    target: Pubkey,
+    action: GovernanceAction,
+    payload_hash: [u8; 32],
+    pool: &mut Account<'info, Pool>,
+) -> Result<()> {
+    let approval = maybe_approval.as_ref().ok_or(AmmError::GovernanceApprovalMissing)?;
+    let rent_receiver = rent_receiver.as_ref().ok_or(AmmError::GovernanceApprovalMissing)?;
+    require_keys_eq!(rent_receiver.key(), approval.rent_payer, AmmError::Unauthorized);
+    // consuming the nonce changes pool state, so the caller must pass a mutable pool
+    check_governance_approval(approval, target, action, payload_hash, &mut pool.last_governance_nonce)?;
+    approval.close(rent_receiver.to_account_info())
+}
+
+/// Check an approval against `target`, `action` and `payload_hash` and consume its nonce from `last_nonce`
+/// (strictly increasing)
+fn check_governance_approval(
+    approval: &Account<GovernanceApproval>,
+    target: Pubkey,
+    action: GovernanceAction,
+    payload_hash: [u8; 32],
+    last_nonce: &mut u128,
+) -> Result<()> {
+    require!(approval.approved, AmmError::GovernanceNotApproved);
+    let now = Clock::get()?.unix_timestamp;
+    require!(approval.expiry_ts >= now, AmmError::GovernanceApprovalExpired);
+    require_keys_eq!(approval.target, target, AmmError::GovernanceApprovalTargetMismatch);
+    require!(approval.action == action, AmmError::GovernanceActionMismatch);
+    require!(approval.payload_hash == payload_hash, AmmError::GovernancePayloadMismatch);
+
+    // replay protection: nonce must be strictly increasing
+    require!(approval.nonce > *last_nonce, AmmError::GovernanceNotApproved);
+    *last_nonce = approval.nonce;
+    Ok(())
+}
+
+/// sha256 of a governance payload's borsh encoding, as stored in `GovernanceApproval.payload_hash`
+fn governance_payload_hash<T: AnchorSerialize>(payload: &T) -> Result<[u8; 32]> {
+    let bytes = payload.try_to_vec().map_err(|_| AmmError::MathOverflow)?;
+    Ok(anchor_lang::solana_program::hash::hash(&bytes).to_bytes())
+}
+
+/// Signer set of the governance multisig: 1..=MAX_GOVERNANCE_SIGNERS distinct keys, 1 <= threshold <= signers
+fn validate_governance_signers(signers: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(!signers.is_empty() && signers.len() <= MAX_GOVERNANCE_SIGNERS, AmmError::InvalidGovernanceConfig);
+    require!(threshold >= 1 && threshold as usize <= signers.len(), AmmError::InvalidGovernanceConfig);
+    for (i, signer) in signers.iter().enumerate() {
+        require!(!signers[..i].contains(signer), AmmError::InvalidGovernanceConfig);
+    }
+    Ok(())
 }
 
 /* -----------------------
@@ -2043,5 +7693,121 @@ pub enum AmmError {
     GovernanceApprovalExpired,
     #[msg("Governance approval target mismatch")]
     GovernanceApprovalTargetMismatch,
+    #[msg("Invalid oracle account")]
+    InvalidOracle,
+    #[msg("Invalid oracle price")]
+    InvalidOraclePrice,
+    #[msg("Oracle price is stale")]
+    OracleStale,
+    #[msg("Oracle confidence interval too wide")]
+    OracleConfidenceTooWide,
+    #[msg("Invalid TWAP window")]
+    InvalidTwapWindow,
+    #[msg("TWAP window older than oldest observation")]
+    TwapWindowTooLong,
+    #[msg("Insufficient liquidity for requested output")]
+    InsufficientLiquidity,
+    #[msg("Transaction deadline exceeded")]
+    DeadlineExceeded,
+    #[msg("Invalid swap route")]
+    InvalidRoute,
+    #[msg("Flash loan has no matching repay instruction")]
+    MissingFlashRepay,
+    #[msg("No flash loan outstanding")]
+    NoFlashLoan,
+    #[msg("Flash loan repayment violates the pool invariant")]
+    FlashLoanInvariant,
+    #[msg("Mint has an unsupported Token-2022 extension")]
+    UnsupportedMintExtension,
+    #[msg("Transfer hook program is not on the allowlist")]
+    TransferHookNotAllowed,
+    #[msg("Too many transfer hook programs")]
+    TooManyTransferHookPrograms,
+    #[msg("Invalid amplification coefficient or ramp")]
+    InvalidAmp,
+    #[msg("StableSwap math did not converge")]
+    StableSwapNotConverged,
+    #[msg("Pool weight out of bounds, or set on a pool that isn't weighted")]
+    InvalidWeight,
+    #[msg("Invalid tick range")]
+    InvalidTickRange,
+    #[msg("Invalid tick spacing")]
+    InvalidTickSpacing,
+    #[msg("Sqrt price out of range")]
+    InvalidSqrtPrice,
+    #[msg("Tick array does not match the pool or tick")]
+    TickArrayMismatch,
+    #[msg("Swap crosses past the supplied tick arrays")]
+    MissingTickArray,
+    #[msg("Position still holds liquidity or owed tokens")]
+    PositionNotEmpty,
+    #[msg("Invalid bonding curve parameters or launch pool")]
+    InvalidBondingCurve,
+    #[msg("Stack already has a launch")]
+    LaunchAlreadyStarted,
+    #[msg("No active launch for this stack")]
+    LaunchNotActive,
+    #[msg("Rebalance hook program is not on the global allowlist")]
+    RebalanceHookNotAllowed,
+    #[msg("Too many rebalance-hook programs")]
+    TooManyRebalanceHookPrograms,
+    #[msg("No rebalance-hook change is queued")]
+    NoPendingRebalanceHook,
+    #[msg("Queued rebalance-hook change is still timelocked")]
+    RebalanceHookTimelocked,
+    #[msg("Rebalance hook accounts don't match the stack's hook")]
+    InvalidRebalanceHookAccounts,
+    #[msg("No rebalance-hook request is pending")]
+    NoPendingRebalance,
+    #[msg("Window limits need a non-zero window")]
+    InvalidSupplyLimits,
+    #[msg("Mint would exceed the stack's max supply")]
+    SupplyCapExceeded,
+    #[msg("Mint exceeds the per-transaction limit")]
+    MintLimitExceeded,
+    #[msg("Mint exceeds the sliding-window limit")]
+    MintRateLimited,
+    #[msg("Redeem exceeds the per-transaction limit")]
+    RedeemLimitExceeded,
+    #[msg("Redeem exceeds the sliding-window limit")]
+    RedeemRateLimited,
+    #[msg("Stack has no backing reserve")]
+    BackingNotInitialized,
+    #[msg("Stack already has a backing reserve")]
+    BackingAlreadyInitialized,
+    #[msg("Invalid backing parameters")]
+    InvalidBacking,
+    #[msg("Redeem exceeds the backed supply")]
+    InsufficientBacking,
+    #[msg("Governance signers must be distinct, at most MAX_GOVERNANCE_SIGNERS, with 1 <= threshold <= signers")]
+    InvalidGovernanceConfig,
+    #[msg("Not a governance signer")]
+    NotGovernanceSigner,
+    #[msg("Signer already approved this proposal")]
+    AlreadyApproved,
+    #[msg("Proposal already executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Governance approval is for a different instruction")]
+    GovernanceActionMismatch,
+    #[msg("Governance approval is for different arguments")]
+    GovernancePayloadMismatch,
+    #[msg("Params timelock must be 0 or within MIN_PARAMS_TIMELOCK_SECS..=MAX_PARAMS_TIMELOCK_SECS")]
+    InvalidTimelock,
+    #[msg("Queued pool parameters are still timelocked")]
+    PoolParamsTimelocked,
+    #[msg("No pending transfer for this role")]
+    NoPendingRole,
+    #[msg("Global parameter out of bounds")]
+    InvalidGlobalParams,
+    #[msg("Pause flags must be a non-empty combination of PAUSE_* bits")]
+    InvalidPauseFlags,
+    #[msg("Test price feeds need a build with the test-feed feature")]
+    TestFeedDisabled,
+    #[msg("Treasury account does not match the pool's treasury")]
+    InvalidTreasury,
+    #[msg("Not enough compute budget left to run the rebalance hook")]
+    RebalanceHookComputeBudget,
+    #[msg("Rebalance hook used more than its compute cap")]
+    RebalanceHookComputeExceeded,
 }
 
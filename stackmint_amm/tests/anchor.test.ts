@@ -7,6 +7,7 @@ import {
   Transaction,
   SystemProgram,
   SYSVAR_RENT_PUBKEY,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
   PublicKey,
   LAMPORTS_PER_SOL,
   Connection,
@@ -323,6 +324,18 @@ describe("stackmint_amm diagnostics test", () => {
     );
     console.log("VaultAuth PDA:", vaultAuthPda.toBase58(), "bump:", vaultAuthBump);
 
+    const [observationsPda] = await PublicKey.findProgramAddress(
+      [Buffer.from("observations"), poolPda.toBuffer()],
+      progId
+    );
+    console.log("Observations PDA:", observationsPda.toBase58());
+
+    const [lockedLpPda] = await PublicKey.findProgramAddress(
+      [Buffer.from("locked_lp"), poolPda.toBuffer()],
+      progId
+    );
+    console.log("LockedLp PDA:", lockedLpPda.toBase58());
+
     // Treasury ATA (use adminPubkey as payer for ATA creation)
     console.log("\n=== Step 2: Create treasury ATA (quote mint) ===");
     const treasuryAta = await getOrCreateAtaAndLog(adminPubkey, quoteMint, "treasury (quote)");
@@ -341,9 +354,10 @@ describe("stackmint_amm diagnostics test", () => {
       const maxFeeBps = new BN(2000);
       const dustThreshold = new BN(10);
       const creatorClaimLockSecs = new BN(60 * 60 * 24 * 7);
+      const minimumLiquidity = new BN(1_000);
 
       const txSig = await program.methods
-        .initGlobal(protocolFeeBps, pauser, feeManager, governance, maxFeeBps, dustThreshold, creatorClaimLockSecs)
+        .initGlobal(protocolFeeBps, pauser, feeManager, governance, maxFeeBps, dustThreshold, creatorClaimLockSecs, minimumLiquidity)
         .accounts({
           global: globalPda,
           admin: adminPubkey,
@@ -390,6 +404,25 @@ describe("stackmint_amm diagnostics test", () => {
       }
     }
 
+    // Step 3b: set_transfer_hook_programs (allowlist round-trip; the test mints carry no hook)
+    console.log("\n=== Step 3b: set_transfer_hook_programs ===");
+    try {
+      const hookProgram = Keypair.generate().publicKey;
+      const txSig = await program.methods
+        .setTransferHookPrograms([hookProgram])
+        .accounts({ admin: adminPubkey, global: globalPda })
+        .rpc();
+      console.log("set_transfer_hook_programs tx:", txSig);
+      await printTxLogs(txSig);
+
+      const globalState: any = await program.account.global.fetch(globalPda);
+      const hooks: PublicKey[] = globalState.transferHookPrograms ?? globalState.transfer_hook_programs ?? [];
+      assert(hooks.some((k) => k.toBase58() === hookProgram.toBase58()), "hook program allowlisted");
+    } catch (err) {
+      console.error("set_transfer_hook_programs failed:", err);
+      throw err;
+    }
+
     // Step 4: register_stack
     console.log("\n=== Step 4: register_stack ===");
     try {
@@ -403,6 +436,7 @@ describe("stackmint_amm diagnostics test", () => {
           stackInfo: stackInfoPda,
           systemProgram: SystemProgram.programId,
           rent: SYSVAR_RENT_PUBKEY,
+          global: globalPda,
         })
         .rpc();
       console.log("register_stack tx:", txSig);
@@ -440,31 +474,45 @@ describe("stackmint_amm diagnostics test", () => {
     console.log("\n=== Step 6: create vault token accounts (owned by vault PDA) ===");
     let stackVault: PublicKey;
     let quoteVault: PublicKey;
-    let protocolFeeVault: PublicKey;
-    let creatorFeeVault: PublicKey;
     try {
       stackVault = await createTokenAccountOwnedBy(stackMint, vaultAuthPda, "stack_vault");
       quoteVault = await createTokenAccountOwnedBy(quoteMint, vaultAuthPda, "quote_vault");
-      protocolFeeVault = await createTokenAccountOwnedBy(quoteMint, vaultAuthPda, "protocol_fee_vault");
-      creatorFeeVault = await createTokenAccountOwnedBy(quoteMint, vaultAuthPda, "creator_fee_vault");
 
       await Promise.all([
         ensureExists(stackVault, "stack_vault"),
         ensureExists(quoteVault, "quote_vault"),
-        ensureExists(protocolFeeVault, "protocol_fee_vault"),
-        ensureExists(creatorFeeVault, "creator_fee_vault"),
       ]);
     } catch (err) {
       console.error("Failed creating vault token accounts:", err);
       throw err;
     }
 
+    // Step 6b: test price feed (program-owned oracle; pool reserves below price stack at 2 quote).
+    // Needs the program built with `--features test-feed`.
+    console.log("\n=== Step 6b: init_test_price_feed ===");
+    const priceFeed = Keypair.generate();
+    try {
+      const txSig = await program.methods
+        .initTestPriceFeed(new BN(200), -2, new BN(1))
+        .accounts({
+          authority: adminPubkey,
+          priceFeed: priceFeed.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([priceFeed])
+        .rpc();
+      console.log("init_test_price_feed tx:", txSig);
+      await printTxLogs(txSig);
+    } catch (err) {
+      console.error("init_test_price_feed failed:", err);
+      throw err;
+    }
+
     // Step 7: create_pool
     console.log("\n=== Step 7: create_pool ===");
     try {
       const feeBps = new BN(30);
       const kValBN = new BN("1000000000000000000");
-      const feeOnTransfer = false;
       const decimalNormalizeTo = 9;
 
       // sanity checks before call
@@ -473,25 +521,24 @@ describe("stackmint_amm diagnostics test", () => {
       await ensureExists(lpMint, "lpMint");
       await ensureExists(stackVault, "stackVault");
       await ensureExists(quoteVault, "quoteVault");
-      await ensureExists(protocolFeeVault, "protocolFeeVault");
-      await ensureExists(creatorFeeVault, "creatorFeeVault");
       await ensureExists(globalPda, "globalPda");
 
       const txSig = await program.methods
-        .createPool(feeBps, kValBN, feeOnTransfer, decimalNormalizeTo)
+        .createPool(feeBps, kValBN, decimalNormalizeTo, { testFeed: {} }, { constantProduct: {} }, new BN(0), 0)
         .accounts({
           creator: adminPubkey,
           stackMint: stackMint,
           quoteMint: quoteMint,
           lpMint: lpMint,
           pool: poolPda,
+          observations: observationsPda,
+          lockedLp: lockedLpPda,
           stackVault: stackVault,
           quoteVault: quoteVault,
-          protocolFeeVault: protocolFeeVault,
-          creatorFeeVault: creatorFeeVault,
           vaultAuthority: vaultAuthPda,
-          oracle: Keypair.generate().publicKey,
+          oracle: priceFeed.publicKey,
           tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
           rent: SYSVAR_RENT_PUBKEY,
           global: globalPda,
@@ -509,6 +556,9 @@ describe("stackmint_amm diagnostics test", () => {
       });
       assert.equal(poolState.stackMint.toBase58(), stackMint.toBase58());
       assert.equal(poolState.quoteMint.toBase58(), quoteMint.toBase58());
+      assert.ok(poolState.curveType.constantProduct !== undefined, "pool should default to constant product");
+      assert.equal(Number(poolState.ampTarget), 0);
+      assert.equal(Number(poolState.stackWeightBps), 5000, "non-weighted pools are 50/50");
     } catch (err) {
       console.error("create_pool failed:", err);
       throw err;
@@ -531,7 +581,7 @@ describe("stackmint_amm diagnostics test", () => {
       const amountQuote = new BN(200_000);
 
       const txSig = await program.methods
-        .provideLiquidity(amountStack, amountQuote)
+        .provideLiquidity(amountStack, amountQuote, new BN(1), null, null)
         .accounts({
           user: adminPubkey,
           pool: poolPda,
@@ -539,15 +589,16 @@ describe("stackmint_amm diagnostics test", () => {
           quoteMint: quoteMint,
           stackVault: stackVault,
           quoteVault: quoteVault,
-          protocolFeeVault: protocolFeeVault,
-          creatorFeeVault: creatorFeeVault,
           lpMint: lpMint,
           userLpAccount: userLpAta,
+          lockedLp: lockedLpPda,
           userStackAccount: userStackAta,
           userQuoteAccount: userQuoteAta,
           treasuryTokenAccount: treasuryAta,
           vaultAuthority: vaultAuthPda,
+          observations: observationsPda,
           tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
           global: globalPda,
         })
         .rpc();
@@ -561,11 +612,48 @@ describe("stackmint_amm diagnostics test", () => {
       const userLpBalance = await connection.getTokenAccountBalance(userLpAta);
       console.log("user LP balance:", userLpBalance.value.amount);
       assert(Number(userLpBalance.value.amount) > 0, "LP minted to user");
+
+      // first deposit locks the minimum liquidity; the user gets the rest of the supply
+      const lockedLpBalance = await connection.getTokenAccountBalance(lockedLpPda);
+      console.log("locked LP balance:", lockedLpBalance.value.amount);
+      assert.equal(Number(lockedLpBalance.value.amount), 1_000, "minimum liquidity locked");
+      assert.equal(
+        Number(userLpBalance.value.amount) + 1_000,
+        Number(poolState.total_lp_supply ?? poolState.totalLpSupply),
+        "user LP + locked LP equals total supply"
+      );
     } catch (err) {
       console.error("provide_liquidity failed:", err);
       throw err;
     }
 
+    // Step 9b: quote the upcoming swap via return data (simulated, no transaction)
+    console.log("\n=== Step 9b: quote_exact_in (view) ===");
+    try {
+      const quote: any = await program.methods
+        .quoteExactIn(new BN(1_000), { stackToQuote: {} })
+        .accounts({
+          pool: poolPda,
+          stackMint: stackMint,
+          quoteMint: quoteMint,
+          lpMint: lpMint,
+          stackVault: stackVault,
+          quoteVault: quoteVault,
+          stackInfo: stackInfoPda,
+          global: globalPda,
+        })
+        .view();
+      console.log("quote_exact_in:", {
+        amount_out: (quote.amountOut ?? quote.amount_out)?.toString?.(),
+        fee: quote.fee?.toString?.(),
+        price_impact_bps: quote.priceImpactBps ?? quote.price_impact_bps,
+      });
+      assert(Number(quote.amountOut ?? quote.amount_out) > 0, "quote returns a positive output");
+    } catch (err) {
+      console.error("quote_exact_in failed:", err);
+      throw err;
+    }
+
     // Step 10: swap stack -> quote
     console.log("\n=== Step 10: swap_stack_to_quote ===");
     try {
@@ -573,7 +661,7 @@ describe("stackmint_amm diagnostics test", () => {
       const minOut = 1;
 
       const txSig = await program.methods
-        .swapStackToQuote(new BN(swapIn), new BN(minOut), null, false)
+        .swapStackToQuote(new BN(swapIn), new BN(minOut), false, null, null)
         .accounts({
           user: adminPubkey,
           pool: poolPda,
@@ -583,13 +671,15 @@ describe("stackmint_amm diagnostics test", () => {
           quoteVault: quoteVault,
           userStackAccount: userStackAta,
           userQuoteAccount: userQuoteAta,
-          protocolFeeVault: protocolFeeVault,
-          creatorFeeVault: creatorFeeVault,
           treasuryTokenAccount: treasuryAta,
           vaultAuthority: vaultAuthPda,
           stackInfo: stackInfoPda,
+          observations: observationsPda,
+          oracle: priceFeed.publicKey,
           governanceApproval: null,
+          approvalRentReceiver: null,
           tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
           global: globalPda,
         })
         .rpc();
@@ -603,6 +693,202 @@ describe("stackmint_amm diagnostics test", () => {
       throw err;
     }
 
+    // Step 10b: swap stack -> quote for an exact quote output
+    console.log("\n=== Step 10b: swap_stack_to_quote_exact_out ===");
+    try {
+      const exactOut = 100;
+      const maxIn = 10_000;
+      const quoteBalBefore = await connection.getTokenAccountBalance(userQuoteAta);
+
+      const txSig = await program.methods
+        .swapStackToQuoteExactOut(new BN(exactOut), new BN(maxIn), false, null, null)
+        .accounts({
+          user: adminPubkey,
+          pool: poolPda,
+          stackMint: stackMint,
+          quoteMint: quoteMint,
+          stackVault: stackVault,
+          quoteVault: quoteVault,
+          userStackAccount: userStackAta,
+          userQuoteAccount: userQuoteAta,
+          treasuryTokenAccount: treasuryAta,
+          vaultAuthority: vaultAuthPda,
+          stackInfo: stackInfoPda,
+          observations: observationsPda,
+          oracle: priceFeed.publicKey,
+          governanceApproval: null,
+          approvalRentReceiver: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          global: globalPda,
+        })
+        .rpc();
+      console.log("exact-out swap tx:", txSig);
+      await printTxLogs(txSig);
+
+      const quoteBalAfter = await connection.getTokenAccountBalance(userQuoteAta);
+      const received = Number(quoteBalAfter.value.amount) - Number(quoteBalBefore.value.amount);
+      assert(received === exactOut, `exact-out swap pays exactly ${exactOut} quote (got ${received})`);
+    } catch (err) {
+      console.error("swap_stack_to_quote_exact_out failed:", err);
+      throw err;
+    }
+
+    // Step 10c: a swap whose deadline has already passed must be rejected
+    console.log("\n=== Step 10c: swap_stack_to_quote past deadline ===");
+    {
+      const pastDeadline = new BN(Math.floor(Date.now() / 1000) - 3600);
+      let rejected = false;
+      try {
+        await program.methods
+          .swapStackToQuote(new BN(1_000), new BN(1), false, pastDeadline, null)
+          .accounts({
+            user: adminPubkey,
+            pool: poolPda,
+            stackMint: stackMint,
+            quoteMint: quoteMint,
+            stackVault: stackVault,
+            quoteVault: quoteVault,
+            userStackAccount: userStackAta,
+            userQuoteAccount: userQuoteAta,
+            treasuryTokenAccount: treasuryAta,
+            vaultAuthority: vaultAuthPda,
+            stackInfo: stackInfoPda,
+            observations: observationsPda,
+            oracle: priceFeed.publicKey,
+            governanceApproval: null,
+            approvalRentReceiver: null,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            stackTokenProgram: TOKEN_PROGRAM_ID,
+            global: globalPda,
+          })
+          .rpc();
+      } catch (err) {
+        rejected = String(err).includes("DeadlineExceeded");
+        console.log("expired swap rejected:", rejected);
+      }
+      assert(rejected, "swap past its deadline fails with DeadlineExceeded");
+    }
+
+    // Step 10d: route_swap over a single hop (quote -> stack); hop accounts go in remainingAccounts
+    console.log("\n=== Step 10d: route_swap (1 hop) ===");
+    try {
+      const stackBefore = Number((await connection.getTokenAccountBalance(userStackAta)).value.amount);
+      const hop = [
+        { pubkey: poolPda, isWritable: true, isSigner: false },
+        { pubkey: stackMint, isWritable: false, isSigner: false },
+        { pubkey: quoteMint, isWritable: false, isSigner: false },
+        { pubkey: stackVault, isWritable: true, isSigner: false },
+        { pubkey: quoteVault, isWritable: true, isSigner: false },
+        { pubkey: vaultAuthPda, isWritable: false, isSigner: false },
+        { pubkey: stackInfoPda, isWritable: false, isSigner: false },
+        { pubkey: observationsPda, isWritable: true, isSigner: false },
+        { pubkey: priceFeed.publicKey, isWritable: false, isSigner: false },
+        { pubkey: TOKEN_PROGRAM_ID, isWritable: false, isSigner: false },
+        { pubkey: TOKEN_PROGRAM_ID, isWritable: false, isSigner: false },
+      ];
+
+      const txSig = await program.methods
+        .routeSwap(new BN(1_000), new BN(1), null, null)
+        .accounts({
+          user: adminPubkey,
+          userSource: userQuoteAta,
+          userDestination: userStackAta,
+          global: globalPda,
+        })
+        .remainingAccounts(hop)
+        .rpc();
+      console.log("route_swap tx:", txSig);
+      await printTxLogs(txSig);
+
+      const stackAfter = Number((await connection.getTokenAccountBalance(userStackAta)).value.amount);
+      console.log("route_swap stack received:", stackAfter - stackBefore);
+      assert(stackAfter > stackBefore, "route output reaches the destination account");
+    } catch (err) {
+      console.error("route_swap failed:", err);
+      throw err;
+    }
+
+    // Step 10e: flash loan — borrow and repay in the same transaction
+    console.log("\n=== Step 10e: flash_borrow + flash_repay ===");
+    try {
+      const poolBefore: any = await program.account.pool.fetch(poolPda);
+      const protocolFeesBefore = Number(poolBefore.protocolFeesQuote ?? poolBefore.protocol_fees_quote ?? 0);
+      const repayIx = await program.methods
+        .flashRepay()
+        .accounts({
+          user: adminPubkey,
+          pool: poolPda,
+          stackMint: stackMint,
+          quoteMint: quoteMint,
+          stackVault: stackVault,
+          quoteVault: quoteVault,
+          userStackAccount: userStackAta,
+          userQuoteAccount: userQuoteAta,
+          stackInfo: stackInfoPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          global: globalPda,
+        })
+        .instruction();
+
+      const txSig = await program.methods
+        .flashBorrow(new BN(0), new BN(10_000))
+        .accounts({
+          user: adminPubkey,
+          pool: poolPda,
+          stackMint: stackMint,
+          quoteMint: quoteMint,
+          stackVault: stackVault,
+          quoteVault: quoteVault,
+          userStackAccount: userStackAta,
+          userQuoteAccount: userQuoteAta,
+          vaultAuthority: vaultAuthPda,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          global: globalPda,
+        })
+        .postInstructions([repayIx])
+        .rpc();
+      console.log("flash loan tx:", txSig);
+      await printTxLogs(txSig);
+
+      const poolAfter: any = await program.account.pool.fetch(poolPda);
+      assert.equal(poolAfter.locked, false, "pool unlocked after repay");
+      assert.equal(Number(poolAfter.flashLoanQuote ?? poolAfter.flash_loan_quote), 0, "loan cleared");
+      assert(Number(poolAfter.protocolFeesQuote ?? poolAfter.protocol_fees_quote) >= protocolFeesBefore, "flash fee booked");
+
+      // a borrow with no repay in the transaction is rejected up front
+      let rejected = false;
+      try {
+        await program.methods
+          .flashBorrow(new BN(0), new BN(10_000))
+          .accounts({
+            user: adminPubkey,
+            pool: poolPda,
+            stackMint: stackMint,
+            quoteMint: quoteMint,
+            stackVault: stackVault,
+            quoteVault: quoteVault,
+            userStackAccount: userStackAta,
+            userQuoteAccount: userQuoteAta,
+            vaultAuthority: vaultAuthPda,
+            instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            stackTokenProgram: TOKEN_PROGRAM_ID,
+            global: globalPda,
+          })
+          .rpc();
+      } catch (err) {
+        rejected = String(err).includes("MissingFlashRepay");
+      }
+      assert(rejected, "flash_borrow without flash_repay fails with MissingFlashRepay");
+    } catch (err) {
+      console.error("flash loan failed:", err);
+      throw err;
+    }
+
     // Step 11: remove_liquidity (burn half)
     console.log("\n=== Step 11: remove_liquidity ===");
     try {
@@ -613,7 +899,7 @@ describe("stackmint_amm diagnostics test", () => {
       const removeAmount = Math.floor(lpAmount / 2);
 
       const txSig = await program.methods
-        .removeLiquidity(new BN(removeAmount))
+        .removeLiquidity(new BN(removeAmount), new BN(0), new BN(0), null, null)
         .accounts({
           user: adminPubkey,
           pool: poolPda,
@@ -627,7 +913,9 @@ describe("stackmint_amm diagnostics test", () => {
           userQuoteAccount: userQuoteAta,
           treasuryTokenAccount: treasuryAta,
           vaultAuthority: vaultAuthPda,
+          observations: observationsPda,
           tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
           global: globalPda,
         })
         .rpc();
@@ -643,25 +931,126 @@ describe("stackmint_amm diagnostics test", () => {
       throw err;
     }
 
+    // Step 11b/11c: single-sided zap in (quote only) and back out (quote only)
+    const zapAccounts = async () => ({
+      user: adminPubkey,
+      pool: poolPda,
+      stackMint: stackMint,
+      quoteMint: quoteMint,
+      stackVault: stackVault,
+      quoteVault: quoteVault,
+      lpMint: lpMint,
+      userLpAccount: await (splToken as any).getAssociatedTokenAddress(lpMint, adminPubkey, false, TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID),
+      userStackAccount: userStackAta,
+      userQuoteAccount: userQuoteAta,
+      treasuryTokenAccount: treasuryAta,
+      vaultAuthority: vaultAuthPda,
+      stackInfo: stackInfoPda,
+      observations: observationsPda,
+      oracle: priceFeed.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      stackTokenProgram: TOKEN_PROGRAM_ID,
+      global: globalPda,
+    });
+
+    console.log("\n=== Step 11b: provide_liquidity_single_sided (quote only) ===");
+    let zapLpMinted = 0;
+    try {
+      const accounts = await zapAccounts();
+      const stackBefore = Number((await connection.getTokenAccountBalance(userStackAta)).value.amount);
+      const lpBefore = Number((await connection.getTokenAccountBalance(accounts.userLpAccount)).value.amount);
+
+      const txSig = await program.methods
+        .provideLiquiditySingleSided({ quoteToStack: {} }, new BN(10_000), new BN(1), null, null)
+        .accounts(accounts)
+        .rpc();
+      console.log("provide_liquidity_single_sided tx:", txSig);
+      await printTxLogs(txSig);
+
+      const stackAfter = Number((await connection.getTokenAccountBalance(userStackAta)).value.amount);
+      const lpAfter = Number((await connection.getTokenAccountBalance(accounts.userLpAccount)).value.amount);
+      zapLpMinted = lpAfter - lpBefore;
+      console.log("zap LP minted:", zapLpMinted);
+      assert(zapLpMinted > 0, "zap mints LP");
+      assert.equal(stackAfter, stackBefore, "zap in pulls no stack from the user");
+    } catch (err) {
+      console.error("provide_liquidity_single_sided failed:", err);
+      throw err;
+    }
+
+    console.log("\n=== Step 11c: remove_liquidity_single_sided (quote only) ===");
+    try {
+      const accounts = await zapAccounts();
+      const stackBefore = Number((await connection.getTokenAccountBalance(userStackAta)).value.amount);
+      const quoteBefore = Number((await connection.getTokenAccountBalance(userQuoteAta)).value.amount);
+
+      const txSig = await program.methods
+        .removeLiquiditySingleSided(new BN(zapLpMinted), { stackToQuote: {} }, new BN(1), null, null)
+        .accounts(accounts)
+        .rpc();
+      console.log("remove_liquidity_single_sided tx:", txSig);
+      await printTxLogs(txSig);
+
+      const stackAfter = Number((await connection.getTokenAccountBalance(userStackAta)).value.amount);
+      const quoteAfter = Number((await connection.getTokenAccountBalance(userQuoteAta)).value.amount);
+      console.log("zap out quote received:", quoteAfter - quoteBefore);
+      assert(quoteAfter > quoteBefore, "zap out pays quote");
+      assert.equal(stackAfter, stackBefore, "zap out pays no stack");
+    } catch (err) {
+      console.error("remove_liquidity_single_sided failed:", err);
+      throw err;
+    }
+
+    // Step 11d: a zap out against a throwaway LP mint (supply 1) must not price the real reserves off it
+    console.log("\n=== Step 11d: remove_liquidity_single_sided with a foreign LP mint ===");
+    try {
+      const fakeLpMint = await createMintDecimals(6, adminPubkey);
+      const fakeLpAta = await getOrCreateAtaAndLog(adminPubkey, fakeLpMint, "fake_lp");
+      await mintTokensTo(fakeLpMint, fakeLpAta, 1, adminPubkey);
+      const quoteVaultBefore = Number((await connection.getTokenAccountBalance(quoteVault)).value.amount);
+
+      let rejected = false;
+      try {
+        await program.methods
+          .removeLiquiditySingleSided(new BN(1), { stackToQuote: {} }, new BN(1), null, null)
+          .accounts({ ...(await zapAccounts()), lpMint: fakeLpMint, userLpAccount: fakeLpAta })
+          .rpc();
+      } catch (e) {
+        rejected = String(e).includes("InvalidVaultMint");
+        console.log("zap with a foreign LP mint rejected:", rejected);
+      }
+      assert(rejected, "zaps must fail with InvalidVaultMint unless lp_mint is pool.lp_mint");
+      const quoteVaultAfter = Number((await connection.getTokenAccountBalance(quoteVault)).value.amount);
+      assert.equal(quoteVaultAfter, quoteVaultBefore, "rejected zap leaves the vault untouched");
+    } catch (err) {
+      console.error("foreign LP mint zap check failed:", err);
+      throw err;
+    }
+
     // Optional: claim_creator_fees (non-fatal)
     console.log("\n=== Optional: claim_creator_fees (diagnostic) ===");
     try {
       const poolStateAny: any = await program.account.pool.fetch(poolPda);
-      const creatorClaimable = Number(poolStateAny.creator_claimable ?? poolStateAny.creatorClaimable ?? 0);
-      console.log("creator_claimable (normalized):", creatorClaimable);
-      if (creatorClaimable > 0) {
-        const creatorReceiver = await getOrCreateAtaAndLog(adminPubkey, quoteMint, "creator_receiver");
+      const creatorFeesStack = Number(poolStateAny.creatorFeesStack ?? poolStateAny.creator_fees_stack ?? 0);
+      const creatorFeesQuote = Number(poolStateAny.creatorFeesQuote ?? poolStateAny.creator_fees_quote ?? 0);
+      console.log("creator fees accrued (native):", { stack: creatorFeesStack, quote: creatorFeesQuote });
+      if (creatorFeesStack > 0 || creatorFeesQuote > 0) {
+        const creatorQuoteReceiver = await getOrCreateAtaAndLog(adminPubkey, quoteMint, "creator_quote_receiver");
         const txSig = await program.methods
           .claimCreatorFees()
           .accounts({
             creator: adminPubkey,
             pool: poolPda,
             stackInfo: stackInfoPda,
+            stackMint: stackMint,
             quoteMint: quoteMint,
-            creatorFeeVault: creatorFeeVault,
-            creatorReceiver: creatorReceiver,
+            stackVault: stackVault,
+            quoteVault: quoteVault,
+            creatorStackReceiver: userStackAta,
+            creatorQuoteReceiver: creatorQuoteReceiver,
             vaultAuthority: vaultAuthPda,
             tokenProgram: TOKEN_PROGRAM_ID,
+            stackTokenProgram: TOKEN_PROGRAM_ID,
             // NEW: ClaimCreatorFees context requires global
             global: globalPda,
           })
@@ -669,12 +1058,986 @@ describe("stackmint_amm diagnostics test", () => {
         console.log("claim_creator_fees tx:", txSig);
         await printTxLogs(txSig);
       } else {
-        console.log("no creator_claimable; skipping claim");
+        console.log("no creator fees accrued; skipping claim");
       }
     } catch (err) {
       console.warn("claim_creator_fees encountered error (non-fatal):", err);
     }
 
+    // Step 12: get_twap over the last second (the observations go back to create_pool), and a read
+    // with the vaults swapped must be rejected
+    console.log("\n=== Step 12: get_twap ===");
+    try {
+      const twap: any = await program.methods
+        .getTwap(1)
+        .accounts({
+          pool: poolPda,
+          observations: observationsPda,
+          stackMint: stackMint,
+          quoteMint: quoteMint,
+          stackVault: stackVault,
+          quoteVault: quoteVault,
+        })
+        .view();
+      const quotePerStack = twap.quotePerStackX ?? twap.quote_per_stack_x;
+      const stackPerQuote = twap.stackPerQuoteX ?? twap.stack_per_quote_x;
+      console.log("twap (1s):", {
+        quote_per_stack_x: quotePerStack.toString(),
+        stack_per_quote_x: stackPerQuote.toString(),
+      });
+      assert(new BN(quotePerStack).gtn(0) && new BN(stackPerQuote).gtn(0), "twap prices are positive");
+
+      let rejected = false;
+      try {
+        await program.methods
+          .getTwap(1)
+          .accounts({
+            pool: poolPda,
+            observations: observationsPda,
+            stackMint: stackMint,
+            quoteMint: quoteMint,
+            stackVault: quoteVault,
+            quoteVault: stackVault,
+          })
+          .view();
+      } catch (e) {
+        rejected = true;
+        console.log("get_twap with swapped vaults rejected as expected");
+      }
+      assert(rejected, "get_twap only reads the pool's own vaults");
+    } catch (err) {
+      console.error("get_twap failed:", err);
+      throw err;
+    }
+
+    // Step 13: rebalance hook — allowlist a program, queue it on the stack, apply is timelocked, crank has
+    // nothing to run
+    console.log("\n=== Step 13: rebalance hook ===");
+    try {
+      const rebalanceProgram = Keypair.generate().publicKey;
+      let txSig = await program.methods
+        .setRebalanceHookPrograms([rebalanceProgram])
+        .accounts({ admin: adminPubkey, global: globalPda })
+        .rpc();
+      console.log("set_rebalance_hook_programs tx:", txSig);
+
+      txSig = await program.methods
+        .queueRebalanceHook(rebalanceProgram, 500)
+        .accounts({ creator: adminPubkey, stackInfo: stackInfoPda, global: globalPda })
+        .rpc();
+      console.log("queue_rebalance_hook tx:", txSig);
+      await printTxLogs(txSig);
+      const info: any = await program.account.stackInfo.fetch(stackInfoPda);
+      assert.equal(info.pendingRebalanceHook.hook.toBase58(), rebalanceProgram.toBase58(), "hook change queued");
+      assert.equal(info.rebalanceHook, null, "hook not active before the timelock");
+      assert.equal(info.pendingRebalance, null, "no hook, so no trade has requested a rebalance");
+
+      let applied = false;
+      try {
+        await program.methods
+          .applyRebalanceHook()
+          .accounts({ creator: adminPubkey, stackInfo: stackInfoPda, global: globalPda })
+          .rpc();
+        applied = true;
+      } catch (e) {
+        console.log("apply_rebalance_hook rejected as expected (timelocked)");
+      }
+      assert.equal(applied, false, "apply must wait for the timelock");
+
+      const [hookSigner] = PublicKey.findProgramAddressSync(
+        [Buffer.from("rebalance_hook_signer"), stackInfoPda.toBuffer()],
+        progId
+      );
+      const [hookState] = PublicKey.findProgramAddressSync(
+        [Buffer.from("rebalance_hook"), stackInfoPda.toBuffer()],
+        rebalanceProgram
+      );
+      let cranked = false;
+      try {
+        await program.methods
+          .executeRebalanceHook()
+          .accounts({
+            stackInfo: stackInfoPda,
+            pool: poolPda,
+            global: globalPda,
+            hookProgram: rebalanceProgram,
+            hookSigner,
+            hookState,
+          })
+          .rpc();
+        cranked = true;
+      } catch (e) {
+        console.log("execute_rebalance_hook rejected as expected (nothing pending)");
+      }
+      assert.equal(cranked, false, "crank needs a pending request");
+
+      // leave the stack without a hook for the rest of the run
+      txSig = await program.methods
+        .queueRebalanceHook(null, 0)
+        .accounts({ creator: adminPubkey, stackInfo: stackInfoPda, global: globalPda })
+        .rpc();
+      console.log("queue_rebalance_hook (clear) tx:", txSig);
+    } catch (err) {
+      console.error("rebalance hook failed:", err);
+      throw err;
+    }
+
+    // Step 14: supply limits — set, read back, reject a window limit without a window, then clear
+    console.log("\n=== Step 14: set_supply_limits ===");
+    try {
+      const limits = {
+        maxSupply: new BN("1000000000000000"),
+        maxMintPerTx: new BN(1_000_000_000),
+        maxRedeemPerTx: new BN(1_000_000_000),
+        windowSecs: 3600,
+        mintPerWindow: new BN(5_000_000_000),
+        redeemPerWindow: new BN(5_000_000_000),
+      };
+      let txSig = await program.methods
+        .setSupplyLimits(limits)
+        .accounts({ authority: adminPubkey, stackInfo: stackInfoPda, global: globalPda })
+        .rpc();
+      console.log("set_supply_limits tx:", txSig);
+      await printTxLogs(txSig);
+      const info: any = await program.account.stackInfo.fetch(stackInfoPda);
+      assert.equal(info.supplyLimits.windowSecs, 3600);
+      assert.equal(info.supplyLimits.mintPerWindow.toString(), "5000000000");
+
+      let rejected = false;
+      try {
+        await program.methods
+          .setSupplyLimits({ ...limits, windowSecs: 0 })
+          .accounts({ authority: adminPubkey, stackInfo: stackInfoPda, global: globalPda })
+          .rpc();
+      } catch (e) {
+        rejected = true;
+        console.log("window limit without a window rejected as expected");
+      }
+      assert(rejected, "window limits need window_secs > 0");
+
+      const none = { maxSupply: new BN(0), maxMintPerTx: new BN(0), maxRedeemPerTx: new BN(0), windowSecs: 0, mintPerWindow: new BN(0), redeemPerWindow: new BN(0) };
+      txSig = await program.methods
+        .setSupplyLimits(none)
+        .accounts({ authority: adminPubkey, stackInfo: stackInfoPda, global: globalPda })
+        .rpc();
+      console.log("set_supply_limits (clear) tx:", txSig);
+    } catch (err) {
+      console.error("set_supply_limits failed:", err);
+      throw err;
+    }
+
+    // Step 15: governance multisig — 1-of-1 with the admin: propose a no-op set_pool_params, approve,
+    // execute, consume it (a different payload is rejected, a second use finds it closed), then cancel a second proposal
+    console.log("\n=== Step 15: governance proposals ===");
+    try {
+      const [governanceConfigPda] = await PublicKey.findProgramAddress([Buffer.from("governance_config")], progId);
+      if (!(await connection.getAccountInfo(governanceConfigPda))) {
+        const txSig = await program.methods
+          .initGovernance([adminPubkey], 1)
+          .accounts({ admin: adminPubkey, governanceConfig: governanceConfigPda, global: globalPda, systemProgram: SystemProgram.programId })
+          .rpc();
+        console.log("init_governance tx:", txSig);
+        await printTxLogs(txSig);
+      }
+      const proposalPda = async () => {
+        const config: any = await program.account.governanceConfig.fetch(governanceConfigPda);
+        return (await PublicKey.findProgramAddress([Buffer.from("proposal"), config.proposalCount.toArrayLike(Buffer, "le", 8)], progId))[0];
+      };
+      const expiry = new BN(Math.floor(Date.now() / 1000) + 3600);
+      // payload of set_pool_params with both fields None: two borsh `None` bytes
+      const payloadHash = Array.from(Buffer.from(anchor.utils.sha256.hash("\0".repeat(2)), "hex"));
+
+      const proposal = await proposalPda();
+      let txSig = await program.methods
+        .createProposal(poolPda, { setPoolParams: {} }, payloadHash, expiry)
+        .accounts({ proposer: adminPubkey, governanceConfig: governanceConfigPda, proposal, systemProgram: SystemProgram.programId })
+        .rpc();
+      console.log("create_proposal tx:", txSig);
+      txSig = await program.methods
+        .approveProposal()
+        .accounts({ signer: adminPubkey, governanceConfig: governanceConfigPda, proposal })
+        .rpc();
+      console.log("approve_proposal tx:", txSig);
+      const [approvalPda] = await PublicKey.findProgramAddress([Buffer.from("governance_approval"), proposal.toBuffer()], progId);
+      txSig = await program.methods
+        .executeProposal()
+        .accounts({ payer: adminPubkey, governanceConfig: governanceConfigPda, proposal, governanceApproval: approvalPda, systemProgram: SystemProgram.programId })
+        .rpc();
+      console.log("execute_proposal tx:", txSig);
+      await printTxLogs(txSig);
+      const approval: any = await program.account.governanceApproval.fetch(approvalPda);
+      assert(approval.approved, "executed proposal should write an approval");
+      assert.equal(approval.target.toBase58(), poolPda.toBase58());
+      assert.deepEqual(Array.from(approval.payloadHash), payloadHash);
+
+      const setParamsAccounts = { admin: adminPubkey, pool: poolPda, global: globalPda, governanceApproval: approvalPda, approvalRentReceiver: adminPubkey };
+      let rejected = false;
+      try {
+        await program.methods.setPoolParams(new BN(30), null, true).accounts(setParamsAccounts).rpc();
+      } catch (e) {
+        rejected = true;
+        console.log("approval used with different arguments rejected as expected");
+      }
+      assert(rejected, "approval must be bound to its payload");
+      txSig = await program.methods.setPoolParams(null, null, true).accounts(setParamsAccounts).rpc();
+      console.log("set_pool_params (governance approval) tx:", txSig);
+      assert.equal(await connection.getAccountInfo(approvalPda), null, "consumed approval should be closed");
+
+      const second = await proposalPda();
+      await program.methods
+        .createProposal(poolPda, { stopRampAmp: {} }, payloadHash, expiry)
+        .accounts({ proposer: adminPubkey, governanceConfig: governanceConfigPda, proposal: second, systemProgram: SystemProgram.programId })
+        .rpc();
+      txSig = await program.methods
+        .cancelProposal()
+        .accounts({ caller: adminPubkey, proposer: adminPubkey, proposal: second })
+        .rpc();
+      console.log("cancel_proposal tx:", txSig);
+      assert.equal(await connection.getAccountInfo(second), null, "cancelled proposal should be closed");
+    } catch (err) {
+      console.error("governance proposals failed:", err);
+      throw err;
+    }
+
+    // Step 16: timelocked pool params — queue a fee change, execute is rejected before the ETA, governance vetoes
+    console.log("\n=== Step 16: queue_pool_params ===");
+    try {
+      const [pendingParamsPda] = await PublicKey.findProgramAddress([Buffer.from("pending_pool_params"), poolPda.toBuffer()], progId);
+      let txSig = await program.methods
+        .queuePoolParams(25, null, null, false)
+        .accounts({
+          admin: adminPubkey,
+          pool: poolPda,
+          global: globalPda,
+          pendingParams: pendingParamsPda,
+          governanceApproval: null,
+          approvalRentReceiver: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      console.log("queue_pool_params tx:", txSig);
+      await printTxLogs(txSig);
+      const pending: any = await program.account.pendingPoolParams.fetch(pendingParamsPda);
+      console.log("pending fee_bps:", pending.newFeeBps, "eta:", pending.eta.toString());
+
+      let rejected = false;
+      try {
+        await program.methods
+          .executePoolParams()
+          .accounts({ caller: adminPubkey, pool: poolPda, global: globalPda, pendingParams: pendingParamsPda, queuedBy: adminPubkey })
+          .rpc();
+      } catch (e) {
+        rejected = true;
+        console.log("execute before the ETA rejected as expected");
+      }
+      assert(rejected, "queued params must wait out the timelock");
+
+      // governance is the admin in this setup
+      txSig = await program.methods
+        .cancelPoolParams()
+        .accounts({ caller: adminPubkey, global: globalPda, pendingParams: pendingParamsPda, queuedBy: adminPubkey })
+        .rpc();
+      console.log("cancel_pool_params tx:", txSig);
+      assert.equal(await connection.getAccountInfo(pendingParamsPda), null, "cancelled change should be closed");
+    } catch (err) {
+      console.error("queue_pool_params failed:", err);
+      throw err;
+    }
+
+    // Step 17: role rotation + global params — hand fee_manager to a fresh key and back, no-op param update
+    console.log("\n=== Step 17: propose_role / accept_role / set_global_params ===");
+    try {
+      const nextFeeManager = Keypair.generate();
+      let txSig = await program.methods
+        .proposeRole({ feeManager: {} }, nextFeeManager.publicKey)
+        .accounts({ admin: adminPubkey, global: globalPda })
+        .rpc();
+      console.log("propose_role tx:", txSig);
+      txSig = await program.methods
+        .acceptRole({ feeManager: {} })
+        .accounts({ newHolder: nextFeeManager.publicKey, global: globalPda })
+        .signers([nextFeeManager])
+        .rpc();
+      console.log("accept_role tx:", txSig);
+      let g: any = await program.account.global.fetch(globalPda);
+      assert.equal(g.feeManager.toBase58(), nextFeeManager.publicKey.toBase58());
+      assert.equal(g.pendingFeeManager, null);
+
+      await program.methods.proposeRole({ feeManager: {} }, adminPubkey).accounts({ admin: adminPubkey, global: globalPda }).rpc();
+      await program.methods.acceptRole({ feeManager: {} }).accounts({ newHolder: adminPubkey, global: globalPda }).rpc();
+      g = await program.account.global.fetch(globalPda);
+      assert.equal(g.feeManager.toBase58(), adminPubkey.toBase58());
+
+      let rejected = false;
+      try {
+        await program.methods.setGlobalParams(null, null, null, new BN(-1)).accounts({ admin: adminPubkey, global: globalPda, treasury: null }).rpc();
+      } catch (e) {
+        rejected = true;
+        console.log("negative claim lock rejected as expected");
+      }
+      assert(rejected, "set_global_params must bound its inputs");
+      txSig = await program.methods
+        .setGlobalParams(null, null, null, null)
+        .accounts({ admin: adminPubkey, global: globalPda, treasury: treasuryAta })
+        .rpc();
+      console.log("set_global_params tx:", txSig);
+      await printTxLogs(txSig);
+    } catch (err) {
+      console.error("role rotation / global params failed:", err);
+      throw err;
+    }
+
+    // Step 18: granular pause flags — pause swaps on one pool (zaps stop with them), deposits protocol-wide, then
+    // resume both
+    console.log("\n=== Step 18: pause_pool / pause_operations ===");
+    try {
+      const PAUSE_SWAPS = 1;
+      const PAUSE_DEPOSITS = 2;
+      let txSig = await program.methods
+        .pausePool(PAUSE_SWAPS)
+        .accounts({ admin: adminPubkey, global: globalPda, pool: poolPda })
+        .rpc();
+      console.log("pause_pool tx:", txSig);
+      await printTxLogs(txSig);
+      let p: any = await program.account.pool.fetch(poolPda);
+      assert.equal(p.pauseFlags, PAUSE_SWAPS);
+
+      let rejected = false;
+      try {
+        await program.methods.pausePool(64).accounts({ admin: adminPubkey, global: globalPda, pool: poolPda }).rpc();
+      } catch (e) {
+        rejected = true;
+        console.log("unknown pause flag rejected as expected");
+      }
+      assert(rejected, "only PAUSE_* bits may be set");
+
+      // a zap swaps part of its input, so it stops with swaps even though deposits are open
+      let zapRejected = false;
+      try {
+        await program.methods
+          .provideLiquiditySingleSided({ quoteToStack: {} }, new BN(10_000), new BN(1), null, null)
+          .accounts(await zapAccounts())
+          .rpc();
+      } catch (e) {
+        zapRejected = String(e).includes("PoolPaused");
+        console.log("zap while pool swaps are paused rejected:", zapRejected);
+      }
+      assert(zapRejected, "zaps fail with PoolPaused while the pool's swaps are paused");
+
+      txSig = await program.methods.resumePool(PAUSE_SWAPS).accounts({ admin: adminPubkey, global: globalPda, pool: poolPda }).rpc();
+      console.log("resume_pool tx:", txSig);
+      p = await program.account.pool.fetch(poolPda);
+      assert.equal(p.pauseFlags, 0);
+
+      txSig = await program.methods.pauseOperations(PAUSE_DEPOSITS).accounts({ admin: adminPubkey, global: globalPda }).rpc();
+      console.log("pause_operations tx:", txSig);
+      let g: any = await program.account.global.fetch(globalPda);
+      assert.equal(g.pauseFlags, PAUSE_DEPOSITS);
+      assert(!g.paused, "granular pause must not set the emergency flag");
+      txSig = await program.methods.resumeOperations(PAUSE_DEPOSITS).accounts({ admin: adminPubkey, global: globalPda }).rpc();
+      console.log("resume_operations tx:", txSig);
+      g = await program.account.global.fetch(globalPda);
+      assert.equal(g.pauseFlags, 0);
+    } catch (err) {
+      console.error("pause flags failed:", err);
+      throw err;
+    }
+
+    // Step 19: bonding-curve launch — fresh stack, buy, sell back, buy through graduation, then NAV mint/redeem
+    console.log("\n=== Step 19: bonding-curve launch ===");
+    try {
+      // curve buys mint through the stack_mint_auth PDA, so hand the mint authority over to it
+      const pda = async (...seeds: Buffer[]) => (await PublicKey.findProgramAddress(seeds, progId))[0];
+      const launchMint = await createMintDecimals(6, adminPubkey);
+      const launchMintAuthPda = await pda(Buffer.from("stack_mint_auth"), launchMint.toBuffer());
+      await providerSendAndConfirm(
+        new Transaction().add(
+          (splToken as any).createSetAuthorityInstruction(launchMint, adminPubkey, 0, launchMintAuthPda, [], TOKEN_PROGRAM_ID)
+        ),
+        []
+      );
+      const launchInfoPda = await pda(Buffer.from("stack_info"), launchMint.toBuffer());
+      const launchPoolPda = await pda(Buffer.from("pool"), launchMint.toBuffer(), quoteMint.toBuffer());
+      const launchPoolAuth = await pda(Buffer.from("vault_authority"), launchPoolPda.toBuffer());
+      const launchObservations = await pda(Buffer.from("observations"), launchPoolPda.toBuffer());
+      const launchLockedLp = await pda(Buffer.from("locked_lp"), launchPoolPda.toBuffer());
+      const launchAuthority = await pda(Buffer.from("vault_authority"), launchInfoPda.toBuffer());
+
+      let txSig = await program.methods
+        .registerStack(new BN(100))
+        .accounts({
+          creator: adminPubkey,
+          stackMint: launchMint,
+          stackMintAuth: launchMintAuthPda,
+          stackInfo: launchInfoPda,
+          systemProgram: SystemProgram.programId,
+          rent: SYSVAR_RENT_PUBKEY,
+          global: globalPda,
+        })
+        .rpc();
+      console.log("register_stack (launch) tx:", txSig);
+
+      const launchLpMint = await createMintDecimals(9, launchPoolAuth);
+      const launchStackVault = await createTokenAccountOwnedBy(launchMint, launchPoolAuth, "launch_pool_stack_vault");
+      const launchQuoteVault = await createTokenAccountOwnedBy(quoteMint, launchPoolAuth, "launch_pool_quote_vault");
+      txSig = await program.methods
+        .createPool(new BN(30), new BN(0), 9, { testFeed: {} }, { constantProduct: {} }, new BN(0), 0)
+        .accounts({
+          creator: adminPubkey,
+          stackMint: launchMint,
+          quoteMint: quoteMint,
+          lpMint: launchLpMint,
+          pool: launchPoolPda,
+          observations: launchObservations,
+          lockedLp: launchLockedLp,
+          stackVault: launchStackVault,
+          quoteVault: launchQuoteVault,
+          vaultAuthority: launchPoolAuth,
+          oracle: priceFeed.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: SYSVAR_RENT_PUBKEY,
+          global: globalPda,
+        })
+        .rpc();
+      console.log("create_pool (launch) tx:", txSig);
+
+      // linear curve from 1 quote per stack (native units), doubling every 1M stack sold
+      const launchVault = await createTokenAccountOwnedBy(quoteMint, launchAuthority, "launch_reserve");
+      txSig = await program.methods
+        .startLaunch({ linear: {} }, new BN("1000000000000000000"), new BN(1_000_000), new BN(50_000))
+        .accounts({ creator: adminPubkey, stackInfo: launchInfoPda, pool: launchPoolPda, launchVault })
+        .rpc();
+      console.log("start_launch tx:", txSig);
+      await printTxLogs(txSig);
+      assert.equal((await program.account.pool.fetch(launchPoolPda)).paused, true, "launch pool is paused");
+
+      const userLaunchAta = await getOrCreateAtaAndLog(adminPubkey, launchMint, "user_launch_stack");
+      const buyAccounts = {
+        user: adminPubkey,
+        stackInfo: launchInfoPda,
+        stackMint: launchMint,
+        quoteMint: quoteMint,
+        userQuoteAccount: userQuoteAta,
+        userStackAccount: userLaunchAta,
+        stackMintAuth: launchMintAuthPda,
+        launchVault,
+        launchAuthority,
+        pool: launchPoolPda,
+        poolStackVault: launchStackVault,
+        poolQuoteVault: launchQuoteVault,
+        lpMint: launchLpMint,
+        lockedLp: launchLockedLp,
+        poolVaultAuthority: launchPoolAuth,
+        observations: launchObservations,
+        stackTokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        global: globalPda,
+      };
+      txSig = await program.methods.buyLaunch(new BN(10_000), new BN(1), null, null).accounts(buyAccounts).rpc();
+      console.log("buy_launch tx:", txSig);
+      await printTxLogs(txSig);
+      const bought = Number((await connection.getTokenAccountBalance(userLaunchAta)).value.amount);
+      console.log("stack bought on the curve:", bought);
+      assert(bought > 0 && bought < 10_000, "price starts at 1 and rises");
+
+      txSig = await program.methods
+        .sellLaunch(new BN(Math.floor(bought / 2)), new BN(1), null, null)
+        .accounts({
+          user: adminPubkey,
+          stackInfo: launchInfoPda,
+          stackMint: launchMint,
+          quoteMint: quoteMint,
+          userStackAccount: userLaunchAta,
+          userQuoteAccount: userQuoteAta,
+          launchVault,
+          launchAuthority,
+          pool: launchPoolPda,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          global: globalPda,
+        })
+        .rpc();
+      console.log("sell_launch tx:", txSig);
+
+      // pushes the market cap past 50_000 quote: the pool gets seeded and unpaused
+      txSig = await program.methods.buyLaunch(new BN(100_000), new BN(1), null, null).accounts(buyAccounts).rpc();
+      console.log("buy_launch (graduating) tx:", txSig);
+      await printTxLogs(txSig);
+      const launchPool: any = await program.account.pool.fetch(launchPoolPda);
+      assert.equal(launchPool.paused, false, "graduated pool is live");
+      const lockedLp = Number((await connection.getTokenAccountBalance(launchLockedLp)).value.amount);
+      assert(lockedLp > 0, "initial LP is locked");
+      const launchInfo: any = await program.account.stackInfo.fetch(launchInfoPda);
+      assert.equal(launchInfo.launch.graduated, true);
+
+      // NAV issuance on the graduated stack: mint/redeem move only the backing reserve, never the pool vaults
+      const backingVault = await createTokenAccountOwnedBy(quoteMint, launchAuthority, "launch_backing");
+      txSig = await program.methods
+        .initBacking(new BN("1000000000000000000"), 30)
+        .accounts({ creator: adminPubkey, stackInfo: launchInfoPda, quoteMint, backingVault, global: globalPda })
+        .rpc();
+      console.log("init_backing tx:", txSig);
+      const balance = async (acct: PublicKey) => Number((await connection.getTokenAccountBalance(acct)).value.amount);
+      const poolVaultsBefore = [await balance(launchStackVault), await balance(launchQuoteVault)];
+      const navAccounts = {
+        user: adminPubkey,
+        pool: launchPoolPda,
+        stackMint: launchMint,
+        quoteMint,
+        stackVault: launchStackVault,
+        quoteVault: launchQuoteVault,
+        backingVault,
+        userQuoteAccount: userQuoteAta,
+        userStackAccount: userLaunchAta,
+        stackTokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        stackInfo: launchInfoPda,
+        global: globalPda,
+      };
+      // nothing is backed yet, so the first mint prices at initial_nav (1 quote per stack) after the 30 bps fee
+      const stackBeforeMint = await balance(userLaunchAta);
+      txSig = await program.methods
+        .mintStackViaPool(new BN(20_000), new BN(1), null, null)
+        .accounts({ ...navAccounts, stackMintAuth: launchMintAuthPda })
+        .rpc();
+      console.log("mint_stack_via_pool (NAV) tx:", txSig);
+      await printTxLogs(txSig);
+      let backing: any = (await program.account.stackInfo.fetch(launchInfoPda)).backing;
+      assert.equal(await balance(userLaunchAta) - stackBeforeMint, 19_940, "minted at initial NAV net of the fee");
+      assert.equal(backing.backedSupply.toNumber(), 19_940, "backed supply counts only NAV-minted stack");
+      assert.equal(backing.reserve.toNumber(), 20_000, "the fee stays in the reserve");
+      assert.equal(backing.reserve.toNumber(), await balance(backingVault), "reserve matches the backing vault");
+      const navAfterMint = backing.reserve.toNumber() / backing.backedSupply.toNumber();
+
+      const reserveBeforeRedeem = backing.reserve.toNumber();
+      const quoteBeforeRedeem = await balance(userQuoteAta);
+      txSig = await program.methods
+        .redeemStackViaPool(new BN(Math.floor(backing.backedSupply.toNumber() / 2)), new BN(1), null, null)
+        .accounts({ ...navAccounts, backingAuthority: launchAuthority })
+        .rpc();
+      console.log("redeem_stack_via_pool (NAV) tx:", txSig);
+      backing = (await program.account.stackInfo.fetch(launchInfoPda)).backing;
+      const redeemed = await balance(userQuoteAta) - quoteBeforeRedeem;
+      assert.equal(redeemed, 9_970, "half the backed supply redeems for half the reserve less the 30 bps fee");
+      assert.equal(reserveBeforeRedeem - backing.reserve.toNumber(), redeemed, "redeem is paid from the reserve");
+      assert.equal(backing.backedSupply.toNumber(), 19_940 - 9_970, "redeemed stack leaves the backed supply");
+      assert.equal(backing.reserve.toNumber(), await balance(backingVault), "reserve matches the backing vault");
+      assert(backing.reserve.toNumber() / backing.backedSupply.toNumber() >= navAfterMint, "NAV never decreases");
+      assert.deepEqual([await balance(launchStackVault), await balance(launchQuoteVault)], poolVaultsBefore, "pool vaults untouched");
+    } catch (err) {
+      console.error("bonding-curve launch failed:", err);
+      throw err;
+    }
+
+    // Step 20: concentrated liquidity — pool at price 2 quote/stack, a wide position, a swap inside it, a narrow
+    // position whose upper tick the next swap crosses, then fee collection
+    console.log("\n=== Step 20: concentrated liquidity ===");
+    try {
+      const tickSpacing = 10;
+      const spacingLe = Buffer.alloc(2);
+      spacingLe.writeUInt16LE(tickSpacing);
+      const i32Le = (v: number) => {
+        const b = Buffer.alloc(4);
+        b.writeInt32LE(v);
+        return b;
+      };
+      const [clPoolPda] = await PublicKey.findProgramAddress(
+        [Buffer.from("cl_pool"), stackMint.toBuffer(), quoteMint.toBuffer(), spacingLe],
+        progId
+      );
+      const [clVaultAuthPda] = await PublicKey.findProgramAddress(
+        [Buffer.from("vault_authority"), clPoolPda.toBuffer()],
+        progId
+      );
+      const clStackVault = await createTokenAccountOwnedBy(stackMint, clVaultAuthPda, "cl_stack_vault");
+      const clQuoteVault = await createTokenAccountOwnedBy(quoteMint, clVaultAuthPda, "cl_quote_vault");
+
+      // sqrt(2) in Q64.64 -> current tick 6931
+      let txSig = await program.methods
+        .createClPool(30, tickSpacing, new BN("26087635650665564424"))
+        .accounts({
+          creator: adminPubkey,
+          stackMint: stackMint,
+          quoteMint: quoteMint,
+          stackInfo: stackInfoPda,
+          clPool: clPoolPda,
+          stackVault: clStackVault,
+          quoteVault: clQuoteVault,
+          vaultAuthority: clVaultAuthPda,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          global: globalPda,
+        })
+        .rpc();
+      console.log("create_cl_pool tx:", txSig);
+      await printTxLogs(txSig);
+      let clPool: any = await program.account.clPool.fetch(clPoolPda);
+      assert.equal(Number(clPool.tickCurrent), 6931);
+
+      // ticks 6720..7030 live in one array; the position spans [6800, 7000)
+      const arrayStart = 6720;
+      const [tickArrayPda] = await PublicKey.findProgramAddress(
+        [Buffer.from("tick_array"), clPoolPda.toBuffer(), i32Le(arrayStart)],
+        progId
+      );
+      txSig = await program.methods
+        .initTickArray(arrayStart)
+        .accounts({ payer: adminPubkey, clPool: clPoolPda, tickArray: tickArrayPda, systemProgram: SystemProgram.programId })
+        .rpc();
+      console.log("init_tick_array tx:", txSig);
+
+      const [positionPda] = await PublicKey.findProgramAddress(
+        [Buffer.from("cl_position"), clPoolPda.toBuffer(), adminPubkey.toBuffer(), i32Le(6800), i32Le(7000)],
+        progId
+      );
+      txSig = await program.methods
+        .openClPosition(6800, 7000)
+        .accounts({ owner: adminPubkey, clPool: clPoolPda, position: positionPda, systemProgram: SystemProgram.programId })
+        .rpc();
+      console.log("open_cl_position tx:", txSig);
+
+      const modifyAccounts = {
+        owner: adminPubkey,
+        clPool: clPoolPda,
+        position: positionPda,
+        tickArrayLower: tickArrayPda,
+        tickArrayUpper: null,
+        stackVault: clStackVault,
+        quoteVault: clQuoteVault,
+        ownerStackAccount: userStackAta,
+        ownerQuoteAccount: userQuoteAta,
+        vaultAuthority: clVaultAuthPda,
+        stackMint: stackMint,
+        quoteMint: quoteMint,
+        stackTokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        global: globalPda,
+      };
+      txSig = await program.methods
+        .increaseClLiquidity(new BN(1_000_000), new BN(100_000), new BN(100_000), null, null)
+        .accounts(modifyAccounts)
+        .rpc();
+      console.log("increase_cl_liquidity tx:", txSig);
+      await printTxLogs(txSig);
+      clPool = await program.account.clPool.fetch(clPoolPda);
+      assert.equal(clPool.liquidity.toString(), "1000000", "position is in range");
+
+      const stackBefore = Number((await connection.getTokenAccountBalance(userStackAta)).value.amount);
+      const swapClAccounts = {
+        user: adminPubkey,
+        clPool: clPoolPda,
+        stackInfo: stackInfoPda,
+        stackVault: clStackVault,
+        quoteVault: clQuoteVault,
+        userStackAccount: userStackAta,
+        userQuoteAccount: userQuoteAta,
+        vaultAuthority: clVaultAuthPda,
+        stackMint: stackMint,
+        quoteMint: quoteMint,
+        stackTokenProgram: TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        global: globalPda,
+      };
+      txSig = await program.methods
+        .swapCl(new BN(1_000), new BN(1), { quoteToStack: {} }, null, null)
+        .accounts(swapClAccounts)
+        .remainingAccounts([{ pubkey: tickArrayPda, isWritable: true, isSigner: false }])
+        .rpc();
+      console.log("swap_cl tx:", txSig);
+      await printTxLogs(txSig);
+      const stackAfter = Number((await connection.getTokenAccountBalance(userStackAta)).value.amount);
+      console.log("swap_cl stack received:", stackAfter - stackBefore);
+      assert(stackAfter > stackBefore, "swap_cl pays stack");
+      clPool = await program.account.clPool.fetch(clPoolPda);
+      assert.equal(Number(clPool.tickCurrent), 6945, "1_000 quote in moves the price to tick 6945");
+
+      // narrow position [6900, 6960): in range now, so both positions' liquidity is active
+      const [narrowPositionPda] = await PublicKey.findProgramAddress(
+        [Buffer.from("cl_position"), clPoolPda.toBuffer(), adminPubkey.toBuffer(), i32Le(6900), i32Le(6960)],
+        progId
+      );
+      txSig = await program.methods
+        .openClPosition(6900, 6960)
+        .accounts({ owner: adminPubkey, clPool: clPoolPda, position: narrowPositionPda, systemProgram: SystemProgram.programId })
+        .rpc();
+      console.log("open_cl_position (narrow) tx:", txSig);
+      txSig = await program.methods
+        .increaseClLiquidity(new BN(1_000_000), new BN(100_000), new BN(100_000), null, null)
+        .accounts({ ...modifyAccounts, position: narrowPositionPda })
+        .rpc();
+      console.log("increase_cl_liquidity (narrow) tx:", txSig);
+      clPool = await program.account.clPool.fetch(clPoolPda);
+      assert.equal(clPool.liquidity.toString(), "2000000", "both positions are in range");
+
+      // ~2_000 quote takes the price to tick 6960 at 2M liquidity; the rest trades on the wide position alone
+      txSig = await program.methods
+        .swapCl(new BN(3_000), new BN(1), { quoteToStack: {} }, null, null)
+        .accounts(swapClAccounts)
+        .remainingAccounts([{ pubkey: tickArrayPda, isWritable: true, isSigner: false }])
+        .rpc();
+      console.log("swap_cl (crossing) tx:", txSig);
+      await printTxLogs(txSig);
+      clPool = await program.account.clPool.fetch(clPoolPda);
+      console.log("tick after crossing swap:", Number(clPool.tickCurrent));
+      assert(Number(clPool.tickCurrent) >= 6960 && Number(clPool.tickCurrent) < 7000, "swap crosses tick 6960 and stays in the wide range");
+      assert.equal(clPool.liquidity.toString(), "1000000", "crossing 6960 drops the narrow position's liquidity");
+
+      // a CL pool has no withdrawals, mint or redeem to pause, so those flags are rejected
+      let clFlagRejected = false;
+      try {
+        await program.methods.pauseClPool(4).accounts({ admin: adminPubkey, global: globalPda, clPool: clPoolPda }).rpc();
+      } catch (e) {
+        clFlagRejected = String(e).includes("InvalidPauseFlags");
+        console.log("pause_cl_pool(PAUSE_WITHDRAWALS) rejected:", clFlagRejected);
+      }
+      assert(clFlagRejected, "pause_cl_pool only takes PAUSE_SWAPS, PAUSE_DEPOSITS and PAUSE_CREATOR_CLAIMS");
+
+      // pool-level pause on a CL pool stops its swaps until resumed
+      txSig = await program.methods.pauseClPool(1).accounts({ admin: adminPubkey, global: globalPda, clPool: clPoolPda }).rpc();
+      console.log("pause_cl_pool tx:", txSig);
+      let clSwapRejected = false;
+      try {
+        await program.methods
+          .swapCl(new BN(100), new BN(1), { quoteToStack: {} }, null, null)
+          .accounts(swapClAccounts)
+          .remainingAccounts([{ pubkey: tickArrayPda, isWritable: true, isSigner: false }])
+          .rpc();
+      } catch (e) {
+        clSwapRejected = String(e).includes("PoolPaused");
+        console.log("swap_cl on a paused CL pool rejected:", clSwapRejected);
+      }
+      assert(clSwapRejected, "swap_cl fails with PoolPaused while the CL pool's swaps are paused");
+      txSig = await program.methods.resumeClPool(1).accounts({ admin: adminPubkey, global: globalPda, clPool: clPoolPda }).rpc();
+      console.log("resume_cl_pool tx:", txSig);
+      assert.equal((await program.account.clPool.fetch(clPoolPda)).pauseFlags, 0);
+
+      // the wide position earned LP fees on both swaps
+      const quoteBeforeCollect = Number((await connection.getTokenAccountBalance(userQuoteAta)).value.amount);
+      txSig = await program.methods.collectClFees().accounts(modifyAccounts).rpc();
+      console.log("collect_cl_fees tx:", txSig);
+      const collected = Number((await connection.getTokenAccountBalance(userQuoteAta)).value.amount) - quoteBeforeCollect;
+      console.log("collected quote fees:", collected);
+      assert(collected > 0, "in-range liquidity earns quote fees");
+      const position: any = await program.account.clPosition.fetch(positionPda);
+      assert.equal(Number(position.tokensOwedQuote), 0, "collected fees are paid out");
+    } catch (err) {
+      console.error("concentrated liquidity failed:", err);
+      throw err;
+    }
+
+    // Fresh stack + pool on `curveType` (oracle guardrail off) for the curve-specific steps below; pools are
+    // one per stack/quote pair, so each curve needs its own stack
+    const createCurvePool = async (label: string, curveType: any, amp: number, stackWeightBps: number) => {
+      const pda = async (...seeds: Buffer[]) => (await PublicKey.findProgramAddress(seeds, progId))[0];
+      const mint = await createMintDecimals(stackDecimals, adminPubkey);
+      const info = await pda(Buffer.from("stack_info"), mint.toBuffer());
+      const pool = await pda(Buffer.from("pool"), mint.toBuffer(), quoteMint.toBuffer());
+      const vaultAuthority = await pda(Buffer.from("vault_authority"), pool.toBuffer());
+      const observations = await pda(Buffer.from("observations"), pool.toBuffer());
+      const lockedLp = await pda(Buffer.from("locked_lp"), pool.toBuffer());
+
+      let txSig = await program.methods
+        .registerStack(new BN(300))
+        .accounts({
+          creator: adminPubkey,
+          stackMint: mint,
+          stackMintAuth: await pda(Buffer.from("stack_mint_auth"), mint.toBuffer()),
+          stackInfo: info,
+          systemProgram: SystemProgram.programId,
+          rent: SYSVAR_RENT_PUBKEY,
+          global: globalPda,
+        })
+        .rpc();
+      console.log(`register_stack (${label}) tx:`, txSig);
+
+      const lpMint = await createMintDecimals(9, vaultAuthority);
+      const vaultStack = await createTokenAccountOwnedBy(mint, vaultAuthority, `${label}_stack_vault`);
+      const vaultQuote = await createTokenAccountOwnedBy(quoteMint, vaultAuthority, `${label}_quote_vault`);
+      txSig = await program.methods
+        .createPool(new BN(30), new BN(0), 9, { none: {} }, curveType, new BN(amp), stackWeightBps)
+        .accounts({
+          creator: adminPubkey,
+          stackMint: mint,
+          quoteMint: quoteMint,
+          lpMint,
+          pool,
+          observations,
+          lockedLp,
+          stackVault: vaultStack,
+          quoteVault: vaultQuote,
+          vaultAuthority,
+          oracle: priceFeed.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: SYSVAR_RENT_PUBKEY,
+          global: globalPda,
+        })
+        .rpc();
+      console.log(`create_pool (${label}) tx:`, txSig);
+      await printTxLogs(txSig);
+
+      const userStack = await getOrCreateAtaAndLog(adminPubkey, mint, `user_${label}_stack`);
+      const userLp = await getOrCreateAtaAndLog(adminPubkey, lpMint, `user_${label}_lp`);
+      await mintTokensTo(mint, userStack, 1_000_000, adminPubkey);
+      const common = { pool, stackMint: mint, quoteMint, stackVault: vaultStack, quoteVault: vaultQuote };
+      return {
+        pool,
+        lpMint,
+        lockedLp,
+        userStack,
+        provideAccounts: {
+          ...common,
+          user: adminPubkey,
+          lpMint,
+          userLpAccount: userLp,
+          lockedLp,
+          userStackAccount: userStack,
+          userQuoteAccount: userQuoteAta,
+          treasuryTokenAccount: treasuryAta,
+          vaultAuthority,
+          observations,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          global: globalPda,
+        },
+        swapAccounts: {
+          ...common,
+          user: adminPubkey,
+          userStackAccount: userStack,
+          userQuoteAccount: userQuoteAta,
+          treasuryTokenAccount: treasuryAta,
+          vaultAuthority,
+          stackInfo: info,
+          observations,
+          oracle: priceFeed.publicKey,
+          governanceApproval: null,
+          approvalRentReceiver: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          stackTokenProgram: TOKEN_PROGRAM_ID,
+          global: globalPda,
+        },
+        quoteAccounts: { ...common, lpMint, stackInfo: info, global: globalPda },
+        paramsAccounts: { admin: adminPubkey, pool, global: globalPda, governanceApproval: null, approvalRentReceiver: null },
+      };
+    };
+    const tokenBalance = async (acct: PublicKey) => Number((await connection.getTokenAccountBalance(acct)).value.amount);
+
+    // Step 21: StableSwap pool (amp 100) — balanced seed, exact-in and exact-out swaps near 1:1, then an amp
+    // ramp that is bounded in size and duration and can be stopped
+    console.log("\n=== Step 21: StableSwap pool ===");
+    try {
+      const stable = await createCurvePool("stable", { stableSwap: {} }, 100, 0);
+      let txSig = await program.methods
+        .provideLiquidity(new BN(100_000), new BN(100_000), new BN(1), null, null)
+        .accounts(stable.provideAccounts)
+        .rpc();
+      console.log("provide_liquidity (stable) tx:", txSig);
+
+      // x*y=k would pay ~987 quote for 1_000 stack here; the stable curve stays close to 1:1 less the fee
+      const quoteBefore = await tokenBalance(userQuoteAta);
+      txSig = await program.methods
+        .swapStackToQuote(new BN(1_000), new BN(1), false, null, null)
+        .accounts(stable.swapAccounts)
+        .rpc();
+      console.log("swap_stack_to_quote (stable) tx:", txSig);
+      await printTxLogs(txSig);
+      const quoteOut = (await tokenBalance(userQuoteAta)) - quoteBefore;
+      console.log("stable exact-in quote out:", quoteOut);
+      assert(quoteOut >= 990 && quoteOut <= 997, `stable swap trades near 1:1 (got ${quoteOut})`);
+
+      const stackBefore = await tokenBalance(stable.userStack);
+      const quoteBeforeExactOut = await tokenBalance(userQuoteAta);
+      txSig = await program.methods
+        .swapQuoteToStackExactOut(new BN(500), new BN(600), false, null, null)
+        .accounts(stable.swapAccounts)
+        .rpc();
+      console.log("swap_quote_to_stack_exact_out (stable) tx:", txSig);
+      const stackOut = (await tokenBalance(stable.userStack)) - stackBefore;
+      const quoteIn = quoteBeforeExactOut - (await tokenBalance(userQuoteAta));
+      console.log("stable exact-out:", { stackOut, quoteIn });
+      assert.equal(stackOut, 500, "exact-out pays exactly the requested stack");
+      assert(quoteIn > 500 && quoteIn <= 505, `exact-out costs the fee and little slippage (paid ${quoteIn})`);
+
+      const now = Math.floor(Date.now() / 1000);
+      const rampRejected = async (target: number, endTs: number) => {
+        try {
+          await program.methods.rampAmp(new BN(target), new BN(endTs), false).accounts(stable.paramsAccounts).rpc();
+          return false;
+        } catch (e) {
+          return true;
+        }
+      };
+      assert(await rampRejected(10_000, now + 2 * 86_400), "a ramp may move amp by at most 10x");
+      assert(await rampRejected(200, now + 3_600), "a ramp must run for at least a day");
+
+      txSig = await program.methods
+        .rampAmp(new BN(200), new BN(now + 2 * 86_400), false)
+        .accounts(stable.paramsAccounts)
+        .rpc();
+      console.log("ramp_amp tx:", txSig);
+      await printTxLogs(txSig);
+      let p: any = await program.account.pool.fetch(stable.pool);
+      assert.equal(Number(p.ampInitial), 100, "ramp starts from the current amp");
+      assert.equal(Number(p.ampTarget), 200);
+      assert(Number(p.ampRampEndTs) > Number(p.ampRampStartTs), "ramp is in progress");
+
+      txSig = await program.methods.stopRampAmp(false).accounts(stable.paramsAccounts).rpc();
+      console.log("stop_ramp_amp tx:", txSig);
+      p = await program.account.pool.fetch(stable.pool);
+      assert.equal(Number(p.ampTarget), Number(p.ampInitial), "stopped ramp freezes amp");
+      assert(Number(p.ampInitial) >= 100 && Number(p.ampInitial) < 200, "amp frozen where the ramp was");
+      assert.equal(Number(p.ampRampEndTs), Number(p.ampRampStartTs));
+    } catch (err) {
+      console.error("StableSwap pool failed:", err);
+      throw err;
+    }
+
+    // Step 22: 80/20 weighted pool — the first deposit mints the weighted geometric mean of the seed (ln/exp
+    // fixed point), and a swap prices off the weights (pow) rather than x*y=k
+    console.log("\n=== Step 22: weighted 80/20 pool ===");
+    try {
+      const weighted = await createCurvePool("weighted", { weighted: {} }, 0, 8000);
+      // 100_000 stack holding 80% of the value against 50_000 quote: spot price 2 quote per stack
+      let txSig = await program.methods
+        .provideLiquidity(new BN(100_000), new BN(50_000), new BN(1), null, null)
+        .accounts(weighted.provideAccounts)
+        .rpc();
+      console.log("provide_liquidity (weighted) tx:", txSig);
+      await printTxLogs(txSig);
+      const p: any = await program.account.pool.fetch(weighted.pool);
+      assert.equal(Number(p.stackWeightBps), 8000);
+      // normalized to 9 decimals: (1e8)^0.8 * (5e7)^0.2 = 87_055_056.3; sqrt(x*y) would give 70_710_678
+      const totalLp = Number(p.totalLpSupply);
+      console.log("weighted first-deposit LP:", totalLp);
+      assert(Math.abs(totalLp - 87_055_056) <= 5, `first deposit mints the weighted mean (got ${totalLp})`);
+      assert.equal(await tokenBalance(weighted.lockedLp), 1_000, "minimum liquidity locked");
+
+      // 1_000 quote in: ~492 stack out at spot 2 after the fee; x*y=k on these reserves would pay ~1_955
+      const quote: any = await program.methods
+        .quoteExactIn(new BN(1_000), { quoteToStack: {} })
+        .accounts(weighted.quoteAccounts)
+        .view();
+      const quotedOut = Number(quote.amountOut ?? quote.amount_out);
+      const stackBefore = await tokenBalance(weighted.userStack);
+      txSig = await program.methods
+        .swapQuoteToStack(new BN(1_000), new BN(1), false, null, null)
+        .accounts(weighted.swapAccounts)
+        .rpc();
+      console.log("swap_quote_to_stack (weighted) tx:", txSig);
+      await printTxLogs(txSig);
+      const stackOut = (await tokenBalance(weighted.userStack)) - stackBefore;
+      console.log("weighted swap:", { quotedOut, stackOut });
+      assert(stackOut >= 490 && stackOut <= 492, `80/20 swap prices at the weighted spot (got ${stackOut})`);
+      assert.equal(stackOut, quotedOut, "quote_exact_in matches the executed swap");
+    } catch (err) {
+      console.error("weighted pool failed:", err);
+      throw err;
+    }
+
     // Final summary
     console.log("\n=== Final state summary ===");
     try {
@@ -689,7 +2052,10 @@ describe("stackmint_amm diagnostics test", () => {
         stack_mint: finalPool.stackMint?.toBase58?.() ?? finalPool.stack_mint?.toBase58?.(),
         quote_mint: finalPool.quoteMint?.toBase58?.() ?? finalPool.quote_mint?.toBase58?.(),
         total_lp_supply: finalPool.total_lp_supply ?? finalPool.totalLpSupply,
-        creator_claimable: finalPool.creator_claimable ?? finalPool.creatorClaimable,
+        protocol_fees_stack: (finalPool.protocolFeesStack ?? finalPool.protocol_fees_stack)?.toString?.(),
+        protocol_fees_quote: (finalPool.protocolFeesQuote ?? finalPool.protocol_fees_quote)?.toString?.(),
+        creator_fees_stack: (finalPool.creatorFeesStack ?? finalPool.creator_fees_stack)?.toString?.(),
+        creator_fees_quote: (finalPool.creatorFeesQuote ?? finalPool.creator_fees_quote)?.toString?.(),
       });
     } catch (err) {
       console.warn("Unable to fetch final state (non-fatal):", err);